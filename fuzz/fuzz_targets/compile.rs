@@ -0,0 +1,32 @@
+//! 把任意输入作为 SysY 源程序完整地编译：语法分析、生成 IR、输出 IR 文本、优化和生成汇编
+//! 编译错误是正常的结果，任何 panic 都是 bug
+//! 每个优化等级和每种寄存器分配方式都编译一遍，同一份输入覆盖所有的配置
+#![no_main]
+
+use compiler::ass_gen::{AsmConfig, AssGen, RegAlloc};
+use compiler::ir_gen::IrGen;
+use compiler::ir_opt::{optimize_program, OptConfig};
+use compiler::sysy::CompUnitParser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(ast) = CompUnitParser::new().parse(source) else {
+        return;
+    };
+    for level in 0..=2 {
+        let Some(mut program) = IrGen::new().generate_koopa_ir(ast.clone()) else {
+            return;
+        };
+        let mut generator = koopa::back::KoopaGenerator::new(Vec::new());
+        generator.generate_on(&program).unwrap();
+        optimize_program(&mut program, &OptConfig { level, ..OptConfig::default() });
+        for regalloc in [RegAlloc::Greedy, RegAlloc::Linear, RegAlloc::Graph] {
+            for frame_pointer in [false, true] {
+                let _ = AssGen::new(&program, &AsmConfig { regalloc, frame_pointer }).generate_program();
+            }
+        }
+    }
+});
@@ -0,0 +1,11 @@
+//! 把任意输入作为 SysY 源程序交给语法分析器，语法错误是正常的结果，任何 panic 都是 bug
+#![no_main]
+
+use compiler::sysy::CompUnitParser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        let _ = CompUnitParser::new().parse(source);
+    }
+});
@@ -0,0 +1,10 @@
+#!/bin/sh
+# 用 tests/cases 中的测试程序生成模糊测试的初始语料，之后运行 cargo fuzz run <parse|compile>
+set -e
+cd "$(dirname "$0")"
+for target in parse compile; do
+    mkdir -p "corpus/$target"
+    find ../tests/cases -name '*.c' | while read -r case; do
+        cp "$case" "corpus/$target/$(echo "${case#../tests/cases/}" | tr / _)"
+    done
+done
@@ -1,18 +1,80 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 
-use koopa::ir::{BinaryOp, FunctionData, Program, Value, ValueKind};
+use koopa::ir::{BasicBlock, BinaryOp, FunctionData, Program, Type, TypeKind, Value, ValueKind};
 use koopa::ir::dfg::DataFlowGraph;
 
 pub mod generate_instruction;
+pub mod error;
+mod strength_reduction;
+mod immediate;
+mod control_flow;
+mod liveness;
+mod linear_scan;
+mod graph_coloring;
 
-/// 符号表中存放的符号，可能是一个寄存器或者一个栈地址偏移
+pub use error::CodegenError;
+use error::ErrorReason;
+use crate::error_report::{describe_instruction, describe_value, kind_name};
+
+/// 可以分配给值的调用者保存寄存器，按分配的优先顺序排列
+const CALLER_SAVED_REGISTERS: [&str; 11] = ["t4", "t5", "t6", "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+/// 可以分配给值的被调用者保存寄存器，排在调用者保存寄存器之后
+/// 使用了的寄存器需要在函数开头保存、出口处恢复；s0 用作帧指针时不参与分配
+const CALLEE_SAVED_REGISTERS: [&str; 12] = ["s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "s0"];
+
+/// 判断一个寄存器是否由被调用者保存，这样的寄存器在函数调用前后不需要保存
+fn is_callee_saved(register: &str) -> bool {
+    CALLEE_SAVED_REGISTERS.contains(&register)
+}
+
+/// 寄存器分配算法
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegAlloc {
+    /// 第一次用到某个值时分配任意空闲的寄存器，分配后不再释放，用完后溢出到栈上
+    Greedy,
+    /// 基于活跃区间的线性扫描
+    Linear,
+    /// 基于冲突图的图着色，会合并基本块参数的赋值
+    Graph
+}
+
+/// 汇编生成的配置
 #[derive(Debug, Clone)]
+pub struct AsmConfig {
+    /// 寄存器分配算法
+    pub regalloc: RegAlloc,
+    /// 保留 s0 作为帧指针（-fno-omit-frame-pointer），方便调试器回溯调用栈
+    pub frame_pointer: bool
+}
+
+impl Default for AsmConfig {
+    fn default() -> Self {
+        Self { regalloc: RegAlloc::Linear, frame_pointer: false }
+    }
+}
+
+/// 符号表中存放的符号，可能是一个寄存器或者一个栈地址偏移
+#[derive(Debug, Clone, PartialEq)]
 pub enum Symbol {
     Register(String),
     Stack(i32)
 }
 
+/// 判断一个值是否是函数内 alloc 出来的局部变量
+fn is_local_alloc(dfg: &DataFlowGraph, value: Value) -> bool {
+    !value.is_global() && matches!(dfg.value(value).kind(), ValueKind::Alloc(_))
+}
+
+/// 判断一个局部变量的地址是否被当作值使用（作为函数参数或者存入内存），这样的变量只能放在栈上
+fn is_address_taken(dfg: &DataFlowGraph, alloc: Value) -> bool {
+    dfg.value(alloc).used_by().iter().any(|&user| match dfg.value(user).kind() {
+        ValueKind::Load(_) => false,
+        ValueKind::Store(s) => s.value() == alloc,
+        _ => true
+    })
+}
+
 /// 表示一个函数的汇编生成缓冲区
 /// 这是为了方便统计栈空间设置的
 #[derive(Debug)]
@@ -22,6 +84,23 @@ struct FuncAsm {
     body: Vec<String>,
     epilogue: Vec<String>,
     stack_size: i32,
+    // 函数的出口：（在 body 中的位置，恢复栈帧之后的最后一条指令 ret 或 tail）
+    exits: Vec<(usize, String)>,
+    // 是否调用了其他函数（不含尾调用），如果是，需要保存 ra
+    has_call: bool,
+    // 通过栈传递的参数：（相对调用者 sp 的偏移，复制到的栈空间）
+    stack_params: Vec<(i32, i32)>,
+    // 函数调用前保存寄存器用的栈空间
+    save_slots: HashMap<String, i32>,
+    // 基本块参数并行赋值时用的临时栈空间
+    move_slots: Vec<i32>,
+    // 可以复用当前栈帧的尾调用，以及紧随其后、不需要再生成的 ret
+    tail_calls: HashSet<Value>,
+    skipped: HashSet<Value>,
+    // 函数内部生成的辅助标签计数
+    label_counter: usize,
+    // 每条函数调用需要保存的寄存器（由寄存器分配算法给出，贪心分配时为空）
+    call_saves: HashMap<Value, Vec<String>>
 }
 
 /// 集中式代码生成上下文
@@ -32,33 +111,38 @@ pub struct AssGen<'p> {
     // 后续可扩展：当前函数信息/寄存器分配/块标签映射/目标选项等
     // 简易符号表：koopa::ir::Value 映射到一个 
     symbol_table: HashMap<Value, Symbol>,
-    // 寄存器分配状态：寄存器名称-是否占用
-    register_status: HashMap<String, bool>,
+    // 寄存器分配状态：寄存器名称-是否占用，按分配的优先顺序排列，保证每次生成的结果相同
+    register_status: Vec<(String, bool)>,
     current_func: Option<FuncAsm>,
-    // 保留寄存器的分配状态（t0, t1, t2）
-    reserved_status: HashMap<String, bool>
+    // 保留寄存器的分配状态（t1, t2, t3），按顺序申请
+    reserved_status: Vec<(String, bool)>,
+    // 基本块对应的汇编标签
+    block_labels: HashMap<BasicBlock, String>,
+    // 整个程序中已经使用的基本块标签。标签由函数名和基本块名用 “_” 拼接而成，
+    // 两者都可以包含 “_”，不同函数的标签也可能重复
+    used_labels: HashSet<String>,
+    // 使用的寄存器分配算法
+    regalloc: RegAlloc,
+    // 是否保留帧指针
+    frame_pointer: bool,
+    // 可以分配的寄存器，按优先顺序排列
+    allocatable: Vec<&'static str>
 }
 
 impl<'p> AssGen<'p> {
-    pub fn new(prog: &'p Program) -> Self {
+    pub fn new(prog: &'p Program, config: &AsmConfig) -> Self {
         // t0 寄存器被保留，在立即数大于 12 位范围时用于中继。严禁在 t0 寄存器中输入任何内容，因为其可能随时被覆盖。
+        // 因此 t0 不在下面的保留寄存器中，任何一条生成的指令序列都不能依赖 t0 的值保持不变。
         // t1、t2、t3 寄存器同样被保留，主要是用于做二元运算，如果寄存器空间不足就必须留下以加载栈空间的内容
-        // 与 t0 不同的是，可以通过方法来暂时申请这两个寄存器，利用其生成指令，但二元运算结束后必须释放。
-        let register_info = vec![
-            (String::from("t4"), false),
-            (String::from("t5"), false),
-            (String::from("t6"), false),
-            (String::from("a0"), false),
-            (String::from("a1"), false),
-            (String::from("a2"), false),
-            (String::from("a3"), false),
-            (String::from("a4"), false),
-            (String::from("a5"), false),
-            (String::from("a6"), false),
-            (String::from("a7"), false),
-        ];
+        // 与 t0 不同的是，可以通过方法来暂时申请这三个寄存器，利用其生成指令，但二元运算结束后必须释放。
+        // s0 作为帧指针时不参与分配
+        let allocatable: Vec<&'static str> = CALLER_SAVED_REGISTERS.iter()
+            .chain(&CALLEE_SAVED_REGISTERS)
+            .copied()
+            .filter(|r| !(config.frame_pointer && *r == "s0"))
+            .collect();
+        let register_info = allocatable.iter().map(|r| (r.to_string(), false));
         let reserved_register_info = vec![
-            (String::from("t0"), false),
             (String::from("t1"), false),
             (String::from("t2"), false),
             (String::from("t3"), false)
@@ -67,32 +151,46 @@ impl<'p> AssGen<'p> {
             prog,
             out: String::new(),
             symbol_table: HashMap::new(),
-            register_status: register_info.into_iter().collect(),
+            register_status: register_info.collect(),
             current_func: None,
-            reserved_status: reserved_register_info.into_iter().collect()
+            reserved_status: reserved_register_info,
+            block_labels: HashMap::new(),
+            used_labels: HashSet::new(),
+            regalloc: config.regalloc,
+            frame_pointer: config.frame_pointer,
+            allocatable
         }
     }
 
-    /// 结束并获取最终汇编字符串
-    pub fn finish(self) -> String {
-        self.out
-    }
-
-    /// 生成整个 Program 的汇编
-    pub fn generate_program(&mut self) {
+    /// 生成整个 Program 的汇编，返回汇编文本
+    /// 遇到后端不支持的内容时返回错误，指明出错的函数和指令
+    pub fn generate_program(&mut self) -> Result<String, CodegenError> {
+        // RV32 上的指针占 4 字节，koopa 默认使用宿主机的指针大小
+        Type::set_ptr_size(4);
         writeln!(self.out, "\t.text").unwrap();
         // 先声明每个函数符号
+        // 只有声明没有函数体的函数（如库函数）由其他文件提供，不需要声明和生成
         for &func in self.prog.func_layout() {
             let f = self.prog.func(func);
-            writeln!(self.out, "\t.globl {}", self.strip_symbol_prefix(f.name())).unwrap();
+            if f.layout().entry_bb().is_some() {
+                writeln!(self.out, "\t.globl {}", self.strip_symbol_prefix(f.name())).unwrap();
+            }
         }
         // 然后生成每个函数体
         for &func in self.prog.func_layout() {
+            if self.prog.func(func).layout().entry_bb().is_none() {
+                continue;
+            }
             self.enter_function(self.prog.func(func).name());
             let f = self.prog.func(func);
-            self.generate_function(f);
-            self.leave_function();
+            self.generate_function(f)?;
+            self.leave_function().map_err(|reason| CodegenError {
+                function: f.name().to_string(),
+                instruction: String::from("function prologue"),
+                reason
+            })?;
         }
+        Ok(std::mem::take(&mut self.out))
     }
 
     /// 每次翻译一个函数前，需要存储函数对象，做一些准备工作
@@ -102,34 +200,98 @@ impl<'p> AssGen<'p> {
             prologue: Vec::new(),
             body: Vec::new(),
             epilogue: Vec::new(),
-            stack_size: 0
-        })
+            stack_size: 0,
+            exits: Vec::new(),
+            has_call: false,
+            stack_params: Vec::new(),
+            save_slots: HashMap::new(),
+            move_slots: Vec::new(),
+            tail_calls: HashSet::new(),
+            skipped: HashSet::new(),
+            label_counter: 0,
+            call_saves: HashMap::new()
+        });
+        // 寄存器和符号只在函数内部有效
+        self.symbol_table.clear();
+        self.block_labels.clear();
+        for (_, used) in self.register_status.iter_mut() {
+            *used = false;
+        }
     }
 
     /// 每次翻译函数后，需要计算函数栈空间，将对象真正写为指令
-    fn leave_function(&mut self) {
+    fn leave_function(&mut self) -> Result<(), ErrorReason> {
         // 清空 self.current_func
         if let Some(mut function) = self.current_func.take() {
-            let aligned_stack = AssGen::remap_to_16(function.stack_size);
-            // 先不管函数参数之类的玩意，因为没到这个阶段
-            // 加入栈指针移动的指令
+            // 用到的被调用者保存寄存器需要在栈上保存，按分配的优先顺序排列
+            let callee_saved: Vec<&'static str> = CALLEE_SAVED_REGISTERS.iter()
+                .copied()
+                .filter(|r| self.symbol_table.values().any(|symbol| *symbol == Symbol::Register(r.to_string())))
+                .collect();
+            let mut saved_registers: Vec<(&'static str, i32)> = callee_saved.into_iter()
+                .map(|r| {
+                    let slot = function.stack_size;
+                    function.stack_size += 4;
+                    (r, slot)
+                })
+                .collect();
+            let aligned_stack = if self.frame_pointer {
+                // 帧指针指向进入函数时的栈指针，ra 和旧的帧指针依次存放在它的下方，调试器据此回溯调用栈
+                let aligned_stack = AssGen::remap_to_16(function.stack_size + 8);
+                saved_registers.push(("ra", aligned_stack - 4));
+                saved_registers.push(("s0", aligned_stack - 8));
+                aligned_stack
+            } else {
+                // 调用了其他函数时，ra 会被覆盖，需要在栈上保存
+                if function.has_call {
+                    saved_registers.insert(0, ("ra", function.stack_size));
+                    function.stack_size += 4;
+                }
+                AssGen::remap_to_16(function.stack_size)
+            };
             // RISC-V 栈向下生长，所以是负的
-            function.prologue.push(self.move_sp_inst_str(-aligned_stack));
-            // 栈指针移动回来
+            let mut prologue = vec![self.move_sp_inst_str(-aligned_stack)];
+            for &(register, slot) in &saved_registers {
+                prologue.push(self.store_inst_str(register, slot));
+            }
+            if self.frame_pointer {
+                prologue.push(self.set_frame_pointer_inst_str(aligned_stack));
+            }
+            // 第 8 个以后的参数在调用者的栈帧底部，也就是紧挨着当前栈帧的上方
+            for &(offset, slot) in &function.stack_params {
+                let register = self.get_reserved_register_without_load()?;
+                prologue.push(self.load_inst_str(&register, aligned_stack + offset));
+                prologue.push(self.store_inst_str(&register, slot));
+                self.remove_reserved_register(&register);
+            }
+            prologue.append(&mut function.prologue);
+            // 恢复保存的寄存器，栈指针移动回来
+            for &(register, slot) in &saved_registers {
+                function.epilogue.push(self.load_inst_str(register, slot));
+            }
             function.epilogue.push(self.move_sp_inst_str(aligned_stack));
 
             // 生成拼接内容
             // 函数名
             writeln!(self.out, "{}:", self.strip_symbol_prefix(&function.name)).unwrap();
             // 函数前置
-            for l in function.prologue { write!(self.out, "{}", l).unwrap(); }
-            // 函数内容
-            for l in function.body { write!(self.out, "{}", l).unwrap(); }
-            // 函数后置
-            for l in function.epilogue { write!(self.out, "{}", l).unwrap(); }
-            // 返回指令
-            writeln!(self.out, "\tret\n").unwrap();
+            for l in prologue { write!(self.out, "{}", l).unwrap(); }
+            // 函数内容，每个出口处插入函数后置和返回（或尾调用）指令
+            let mut exits = function.exits.into_iter().peekable();
+            for (i, l) in function.body.iter().enumerate() {
+                while let Some((_, exit)) = exits.next_if(|(position, _)| *position == i) {
+                    for e in &function.epilogue { write!(self.out, "{}", e).unwrap(); }
+                    write!(self.out, "{}", exit).unwrap();
+                }
+                write!(self.out, "{}", l).unwrap();
+            }
+            for (_, exit) in exits {
+                for e in &function.epilogue { write!(self.out, "{}", e).unwrap(); }
+                write!(self.out, "{}", exit).unwrap();
+            }
+            writeln!(self.out).unwrap();
         }
+        Ok(())
     }
 
     /// 将一个符号名称去除可能具有的前置 @ 和 % 符号。
@@ -141,9 +303,8 @@ impl<'p> AssGen<'p> {
         s
     }
 
-    /// 返回一个当前没有占用的寄存器。
+    /// 返回一个当前没有占用的寄存器，按优先顺序选择第一个空闲的寄存器。
     /// 如果所有寄存器都已经满了，则返回 None
-    /// 请注意：由于哈希表在遍历时的不确定性，寄存器是随机分配的，没有任何顺序和规律。
     fn fresh_register(&mut self, value: &Value) -> Option<String> {
         for (name, used) in self.register_status.iter_mut() {
             if !*used {
@@ -160,11 +321,18 @@ impl<'p> AssGen<'p> {
     /// 返回内容为该空间在函数内的栈偏移值
     /// 如果当前不在某个函数环境下，直接崩溃。
     fn new_stack_symbol(&mut self, value: &Value) -> i32 {
+        let current = self.new_stack_slot();
+        self.symbol_table.insert(*value, Symbol::Stack(current));
+        current
+    }
+
+    /// 创建一块新的 4 字节栈空间，不对应任何变量
+    /// 如果当前不在某个函数环境下，直接崩溃。
+    fn new_stack_slot(&mut self) -> i32 {
         if let Some(function) = &mut self.current_func {
             // 当前的栈指针为 function.stack_size
-            // 直接将当前位置开始分配给变量
+            // 直接将当前位置开始分配
             let current = function.stack_size;
-            self.symbol_table.insert(*value, Symbol::Stack(current));
             function.stack_size += 4;
             current
         } else {
@@ -173,7 +341,7 @@ impl<'p> AssGen<'p> {
     }
 
     /// 尝试从符号表中寻找一个值已经被分配的符号。如果失败，则分配一个新的寄存器或者栈空间并返回。
-    /// 
+    /// 线性扫描和图着色在 prepare_function 中已经为所有的值决定了位置，只有贪心分配会走到分配的分支。
     fn find_or_allocate_symbol(&mut self, value: &Value) -> Symbol {
         if let Some(result) = self.symbol_table.get(value) {
             result.clone()
@@ -187,54 +355,6 @@ impl<'p> AssGen<'p> {
         }
     }
 
-    /// find_or_allocate_symbol 的简化版本
-    /// 此函数尝试为某个 value 分配或者获得寄存器，并且在无法分配时返回栈变量
-    /// check_x0：为真时，如果输入为 Integer 的 0，那么为其分配 x0 寄存器。
-    fn get_symbol_for_value(&mut self, dfg: &DataFlowGraph, value: &Value, check_x0: bool) -> Symbol {
-        let data = dfg.value(*value);
-        if let ValueKind::Integer(i) = data.kind() {
-            if i.value() == 0 && check_x0 {
-                Symbol::Register(String::from("x0"))
-            } else {
-                self.find_or_allocate_symbol(value)
-            }
-        } else {
-            self.find_or_allocate_symbol(value)
-        }
-    }
-
-    /// 移除一个寄存器，将其设为未分配
-    /// 如果输入的寄存器不存在或者没有被使用或者成功释放，返回 Ok
-    /// 如果输入的寄存器被一个非立即数指令使用，返回错误。
-    fn remove_register(&mut self, dfg: &DataFlowGraph, register: &str) -> Result<(), String> {
-        let data: Option<&bool> = self.register_status.get(register);
-        if let Some(status) = data
-            && *status {
-                for (key, value) in self.symbol_table.iter() {
-                    if let Symbol::Register(value) = value && value == register {
-                        if let ValueKind::Integer(_) = dfg.value(*key).kind() {
-                            // 一会移除
-                        } else {
-                            return Err(format!("输入的寄存器不是不是存储常量的寄存器: {}", register));
-                        }
-                    }
-                }
-                self.symbol_table.retain(|&_, v| {
-                    // 移除逻辑（其实是保留逻辑，闭包的返回值决定是否保留）
-                    // 如果遍历到寄存器，那么检查是不是和输入寄存器一样，是的话就删
-                    // 如果遍历到栈对象，不管，直接返回“保留”
-                    if let Symbol::Register(v) = v {
-                        v != register
-                    } else {
-                        true
-                    }
-                });
-                // 没有问题，取消注册
-                self.register_status.insert(register.to_string(), false);
-            }
-        Ok(())
-    }
-
     /// 将栈空间大小对齐（增加）到最近的 16 的倍数。
     fn remap_to_16(mut a: i32) -> i32 {
         while a % 16 != 0 {
@@ -243,11 +363,11 @@ impl<'p> AssGen<'p> {
         a
     }
 
-    /// 获得二元运算用的保留寄存器 t1 和 t2
+    /// 获得二元运算用的保留寄存器（t1~t3 中的一个）
     /// 此函数的使用场景为：二元运算的两个操作数都在栈空间，因此需要加载到寄存器来计算
     /// 必须在 current_func 上下文不为空时调用此函数
-    /// 此函数会生成加载指令，返回寄存器的名称。
-    fn get_reserved_register(&mut self, stack: i32) -> String {
+    /// 此函数会生成加载指令，返回寄存器的名称。保留寄存器都被占用时返回错误。
+    fn get_reserved_register(&mut self, stack: i32) -> Result<String, ErrorReason> {
         let mut empty = None;
         for (name, used) in self.reserved_status.iter_mut() {
             if !*used {
@@ -261,42 +381,67 @@ impl<'p> AssGen<'p> {
                 let load_inst = self.load_inst_str(&s, stack);
                 if let Some(function) = &mut self.current_func {
                     function.body.push(load_inst);
-                    s
+                    Ok(s)
                 } else {
                     panic!("在非函数环境下尝试使用栈地址")
                 }
             },
-            None => panic!("预留寄存器已满")
+            None => Err(ErrorReason::ReservedRegistersExhausted)
         }
     }
 
-    fn get_reserved_register_without_load(&mut self) -> String {
+    /// 获得一个保留寄存器，不生成加载指令
+    fn get_reserved_register_without_load(&mut self) -> Result<String, ErrorReason> {
         for (name, used) in self.reserved_status.iter_mut() {
             if !*used {
                 *used = true; // 标记为占用
-                return name.clone();
+                return Ok(name.clone());
             }
         }
-        panic!("预留寄存器已满");
+        Err(ErrorReason::ReservedRegistersExhausted)
+    }
+
+    /// 查找一个操作数在符号表中的位置，找不到时返回错误
+    fn symbol_of(&self, dfg: &DataFlowGraph, value: Value) -> Result<Symbol, ErrorReason> {
+        self.symbol_table.get(&value)
+            .cloned()
+            .ok_or_else(|| ErrorReason::MissingOperand(describe_value(dfg, value)))
     }
 
-    /// 释放二元运算使用的保留寄存器 t1 和 t2
+    /// 释放二元运算使用的保留寄存器
     fn remove_reserved_register(&mut self, register: &str) {
-        if self.reserved_status.contains_key(register) {
-            self.reserved_status.insert(register.to_string(), false);
+        if let Some((_, used)) = self.reserved_status.iter_mut().find(|(name, _)| name == register) {
+            *used = false;
         }
     }
 
     /// 生成单个函数的汇编
-    fn generate_function(&mut self, f: &FunctionData) {
+    /// 失败时在错误中记录出错的函数和指令
+    fn generate_function(&mut self, f: &FunctionData) -> Result<(), CodegenError> {
         let dfg = f.dfg();
+        self.prepare_function(f);
 
-        for (&_bb, bb_node) in f.layout().bbs() {
-            // 如需基本块标签，可在此处输出
-            for &inst in bb_node.insts().keys() {
-                self.generate_instruction(dfg, inst);
+        let entry = f.layout().entry_bb();
+        for (&bb, bb_node) in f.layout().bbs() {
+            // 入口块不会被跳转到，不需要标签
+            if Some(bb) != entry {
+                self.add_inst_to_function(self.label_str(&self.block_labels[&bb]));
+            }
+            for param in dfg.bb(bb).params() {
+                self.find_or_allocate_symbol(param);
+            }
+            for (index, &inst) in bb_node.insts().keys().enumerate() {
+                if self.current_func.as_ref().unwrap().skipped.contains(&inst) {
+                    continue;
+                }
+                self.generate_instruction(dfg, inst).map_err(|reason| CodegenError {
+                    function: f.name().to_string(),
+                    instruction: describe_instruction(dfg, bb, index, inst),
+                    reason
+                })?;
             }
         }
+        Ok(())
     }
 
     /// 添加一条指令到当前函数中
@@ -310,7 +455,19 @@ impl<'p> AssGen<'p> {
     }
 
     /// 生成一条指令/值对应的汇编
-    fn generate_instruction(&mut self, dfg: &DataFlowGraph, inst: Value) {
+    fn generate_instruction(&mut self, dfg: &DataFlowGraph, inst: Value) -> Result<(), ErrorReason> {
+        // 后端还没有为全局变量分配空间
+        if dfg.value(inst).kind().value_uses().any(|v| v.is_global()) {
+            return Err(ErrorReason::Unsupported(String::from("global variable")));
+        }
+        // 局部变量的地址目前只能用来读写变量、作为函数参数或者存入内存
+        let takes_address = match dfg.value(inst).kind() {
+            ValueKind::Load(_) | ValueKind::Store(_) | ValueKind::Call(_) => false,
+            kind => kind.value_uses().any(|v| is_local_alloc(dfg, v))
+        };
+        if takes_address {
+            return Err(ErrorReason::Unsupported(String::from("address of a local variable used here")));
+        }
         match dfg.value(inst).kind() {
             // 处理返回语句
             ValueKind::Return(return_value) => {
@@ -322,72 +479,36 @@ impl<'p> AssGen<'p> {
                         ValueKind::Undef(_) => {
                             self.add_inst_to_function(self.return_value_inst_str(None));
                         },
-                        _ => {
-                            let value = self.symbol_table.get(&return_value);
-                            match value {
-                                Some(value) => {
-                                    match value {
-                                        Symbol::Register(register) =>  self.add_inst_to_function(self.return_register_inst_str(&register.clone())),
-                                        Symbol::Stack(stack) => self.add_inst_to_function(self.return_stack_inst_str(*stack)),
-                                    }
-                                },
-                                None => {
-                                    println!("{:?}", return_value);
-                                    println!("{:?}", dfg.value(return_value));
-                                    println!("{:?}", self.symbol_table);
-                                    panic!("错误：函数返回值为非 i32 类型，但其不存在于符号表中。")
-                                }
-                            }
+                        _ => match self.symbol_of(dfg, return_value)? {
+                            Symbol::Register(register) => self.add_inst_to_function(self.return_register_inst_str(&register)),
+                            Symbol::Stack(stack) => self.add_inst_to_function(self.return_stack_inst_str(stack))
                         }
                     }
-                } else {
-                    writeln!(self.out, "\t").unwrap();
                 }
+                self.add_function_exit(self.ret_inst_str());
             },
             // 处理二元运算语句
             ValueKind::Binary(binary) => {
+                // 乘除常量优先尝试强度削弱，其他运算的小常量直接作为立即数
+                if self.generate_reduced_binary(dfg, inst, binary)?
+                    || self.generate_immediate_binary(dfg, inst, binary)? {
+                    return Ok(());
+                }
                 let result_symbol = self.find_or_allocate_symbol(&inst);
-                // 操作数表达式本身
-                let left_exp = dfg.value(binary.lhs());
-                let right_exp = dfg.value(binary.rhs());
-                // 操作数的存储位置
-                // 如果操作数是 0，省略寄存器分配
-                let left_symbol = self.get_symbol_for_value(dfg, &binary.lhs(), true);
-                let right_symbol = self.get_symbol_for_value(dfg, &binary.rhs(), true);
-
+                // 操作数放入寄存器：常量和栈上的值使用临时申请的保留寄存器，0 直接使用 x0
+                let (left_register, left_temp) = self.load_operand(dfg, binary.lhs())?;
+                let (right_register, right_temp) = self.load_operand(dfg, binary.rhs())?;
                 // 给结果分配一个寄存器（如果本来就是寄存器，则不分配）
                 let result_register = match &result_symbol {
                     Symbol::Register(r) => r.clone(),
-                    Symbol::Stack(s) => self.get_reserved_register(*s)
-                };
-
-                // 如果操作数是栈空间，需要加载
-                // 如果是寄存器，就不用了
-                let left_register = match &left_symbol {
-                    Symbol::Register(r) => r.clone(),
-                    Symbol::Stack(s) => self.get_reserved_register(*s)
-                };
-                let right_register = match &right_symbol {
-                    Symbol::Register(r) => r.clone(),
-                    Symbol::Stack(s) => self.get_reserved_register(*s)
+                    Symbol::Stack(_) => self.get_reserved_register_without_load()?
                 };
-                
-                // 如果操作数表达式是立即数，那么我们就生成立即数赋值语句 li rs,?
-                if let ValueKind::Integer(i) = left_exp.kind() {
-                    self.add_inst_to_function(self.init_register_str(&left_register, i.value()));
-                }
-                if let ValueKind::Integer(i) = right_exp.kind() {
-                    self.add_inst_to_function(self.init_register_str(&right_register, i.value()));
-                }
                 match binary.op() {
                     BinaryOp::Eq => {
-                        self.add_inst_to_function(self.clear_register_str(&result_register));
                         self.add_inst_to_function(self.sub_inst_str(&result_register, &left_register, &right_register));
                         self.add_inst_to_function(self.eq0_inst_str(&result_register));
                     },
                     BinaryOp::NotEq => {
-                        // 清空目标寄存器
-                        self.add_inst_to_function(self.clear_register_str(&result_register));
                         self.add_inst_to_function(self.sub_inst_str(&result_register, &left_register, &right_register));
                         self.add_inst_to_function(self.neq0_inst_str(&result_register));
                     },
@@ -423,19 +544,19 @@ impl<'p> AssGen<'p> {
                     },
                     BinaryOp::Or => {
                         self.add_inst_to_function(self.or_inst_str(&result_register, &left_register, &right_register));
+                    },
+                    BinaryOp::Xor => {
+                        self.add_inst_to_function(self.xor_inst_str(&result_register, &left_register, &right_register));
+                    },
+                    BinaryOp::Shl => {
+                        self.add_inst_to_function(self.shl_inst_str(&result_register, &left_register, &right_register));
+                    },
+                    BinaryOp::Shr => {
+                        self.add_inst_to_function(self.shr_inst_str(&result_register, &left_register, &right_register));
+                    },
+                    BinaryOp::Sar => {
+                        self.add_inst_to_function(self.sar_inst_str(&result_register, &left_register, &right_register));
                     }
-                    _ => todo!()
-                }
-                // 简单寄存器优化
-                // 如果两个操作数表达式是立即数而非其他指令的结果，我们在生成指令后直接释放操作数寄存器
-                // 因为寄存器存放的是操作的立即数，而所有立即数都是当场分配的，之后就不可能被用，直接释放就行
-                // 如果存放的是其他指令的结果，那就不能释放，因为其他指令结果不是当场分配的，万一之后的指令还引用了这一结果就完了。
-                // PS: 不优化一下的话过不去 Lv3 的 TESTCASE 27（Complex binary）
-                if let ValueKind::Integer(_) = left_exp.kind() {
-                    self.remove_register(dfg, &left_register).unwrap();
-                }
-                if let ValueKind::Integer(_) = right_exp.kind() {
-                    self.remove_register(dfg, &right_register).unwrap();
                 }
                 // 如果操作数/结果寄存器是临时寄存器，则释放
                 if let Symbol::Stack(s) = result_symbol {
@@ -443,70 +564,87 @@ impl<'p> AssGen<'p> {
                     self.add_inst_to_function(self.store_inst_str(&result_register, s));
                     self.remove_reserved_register(&result_register);
                 }
-                if let Symbol::Stack(_) = left_symbol {
+                if left_temp {
                     self.remove_reserved_register(&left_register);
                 }
-                if let Symbol::Stack(_) = right_symbol {
+                if right_temp {
                     self.remove_reserved_register(&right_register);
                 }
             },
             ValueKind::Alloc(_) => {
-                // 分配空间
-                let symbol = self.get_symbol_for_value(dfg, &inst, true);
-                self.symbol_table.insert(inst, symbol);
+                // 分配空间，alloc 的位置就是变量本身，目前只支持 4 字节的变量
+                if let TypeKind::Pointer(base) = dfg.value(inst).ty().kind()
+                    && base.size() != 4 {
+                        return Err(ErrorReason::Unsupported(format!("local variable of type {}", base)));
+                    }
+                self.find_or_allocate_symbol(&inst);
             },
             ValueKind::Load(l) => {
-                let origin_symbol = l.src();
-                let symbol = self.symbol_table.get(&origin_symbol).unwrap();
-                // 直接复制已有符号为当前的 Symbol
-                // 不用加载，需要的时候再加载。
-                self.symbol_table.insert(inst, symbol.clone());
+                // load 的结果需要自己的位置，之后对同一变量的 store 不能影响已经读出的值
+                if dfg.value(inst).used_by().is_empty() {
+                    return Ok(());
+                }
+                let symbol = self.find_or_allocate_symbol(&inst);
+                if is_local_alloc(dfg, l.src()) {
+                    self.move_value_to_symbol(dfg, l.src(), &symbol)?;
+                } else {
+                    // 通过指针读取内存
+                    let (pointer, pointer_temp) = self.load_operand(dfg, l.src())?;
+                    match symbol {
+                        Symbol::Register(r) => self.add_inst_to_function(self.load_pointer_inst_str(&r, &pointer)),
+                        Symbol::Stack(slot) => {
+                            let register = self.get_reserved_register_without_load()?;
+                            self.add_inst_to_function(self.load_pointer_inst_str(&register, &pointer));
+                            self.add_inst_to_function(self.store_inst_str(&register, slot));
+                            self.remove_reserved_register(&register);
+                        }
+                    }
+                    if pointer_temp {
+                        self.remove_reserved_register(&pointer);
+                    }
+                }
+            },
+            ValueKind::Store(s) if is_local_alloc(dfg, s.dest()) && !is_local_alloc(dfg, s.value()) => {
+                // 变量的位置就是 alloc 的位置
+                let dest = self.symbol_of(dfg, s.dest())?;
+                self.move_value_to_symbol(dfg, s.value(), &dest)?;
             },
             ValueKind::Store(s) => {
-                
-                match dfg.value(s.value()).kind() {
-                    // 立即数赋值
-                    ValueKind::Integer(i) => {
-                        match self.symbol_table.get(&s.dest()).unwrap().clone() {
-                            Symbol::Register(r) => self.add_inst_to_function(self.init_register_str(&r, i.value())),
-                            Symbol::Stack(s) => {
-                                let register = self.get_reserved_register_without_load();
-                                self.add_inst_to_function(self.init_register_str(&register, i.value()));
-                                self.add_inst_to_function(self.store_inst_str(&register, s));
-                                self.remove_reserved_register(&register);
-                            }
-                        }
-                    },
-                    _ => {
-                        // 应当在符号表中
-                        match self.symbol_table.get(&s.value()).unwrap() {
-                            Symbol::Register(r_value) => {
-                                match self.symbol_table.get(&s.dest()).unwrap() {
-                                    Symbol::Register(r_dest) => {
-                                        self.add_inst_to_function(self.move_register_inst_str(r_dest, r_value));
-                                    },
-                                    Symbol::Stack(s_dest) => {
-                                        self.add_inst_to_function(self.store_inst_str(r_value, *s_dest));
-                                    }
-                                }
-                            },
-                            Symbol::Stack(s_value) => {
-                                match self.symbol_table.get(&s.dest()).unwrap().clone() {
-                                    Symbol::Register(r_dest) => {
-                                        self.add_inst_to_function(self.load_inst_str(&r_dest, *s_value));
-                                    },
-                                    Symbol::Stack(s_dest) => {
-                                        let temp_register = self.get_reserved_register(*s_value);
-                                        self.add_inst_to_function(self.store_inst_str(&temp_register, s_dest));
-                                        self.remove_reserved_register(&temp_register);
-                                    }
-                                }
-                            }
-                        }
+                // 存入的是局部变量的地址，或者通过指针写入内存
+                let (register, temp) = if is_local_alloc(dfg, s.value()) {
+                    let register = self.get_reserved_register_without_load()?;
+                    self.add_inst_to_function(self.local_address_str(dfg, s.value(), &register)?);
+                    (register, true)
+                } else {
+                    self.load_operand(dfg, s.value())?
+                };
+                if is_local_alloc(dfg, s.dest()) {
+                    match self.symbol_of(dfg, s.dest())? {
+                        Symbol::Register(r) => self.add_inst_to_function(self.move_register_inst_str(&r, &register)),
+                        Symbol::Stack(slot) => self.add_inst_to_function(self.store_inst_str(&register, slot))
+                    }
+                } else {
+                    let (pointer, pointer_temp) = self.load_operand(dfg, s.dest())?;
+                    self.add_inst_to_function(self.store_pointer_inst_str(&register, &pointer));
+                    if pointer_temp {
+                        self.remove_reserved_register(&pointer);
                     }
                 }
-            }
-            _ => todo!()
+                if temp {
+                    self.remove_reserved_register(&register);
+                }
+            },
+            ValueKind::Branch(branch) => self.generate_branch(dfg, branch)?,
+            ValueKind::Jump(jump) => self.generate_jump(dfg, jump)?,
+            ValueKind::Call(call) => {
+                if self.current_func.as_ref().unwrap().tail_calls.contains(&inst) {
+                    self.generate_tail_call(dfg, call)?;
+                } else {
+                    self.generate_call(dfg, inst, call)?;
+                }
+            },
+            kind => return Err(ErrorReason::Unsupported(format!("'{}' instruction", kind_name(kind))))
         }
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,395 @@
+/// 此文件存放基本块跳转、函数参数、函数调用与尾调用相关的代码生成
+/// - 每个基本块对应一个标签，跳转时把实参赋值给目标基本块的参数
+/// - 调用约定：前 8 个参数放在 a0~a7 中，其余的放在调用者栈帧的底部，返回值放在 a0 中
+/// - 函数调用前把跨越调用仍然活跃的调用者保存寄存器保存到栈上，调用结束后恢复；被调用者保存寄存器由被调用者负责
+/// - 尾调用（调用后紧跟着返回其结果）先恢复栈帧，再用 tail 跳转到被调用者，复用调用者的栈帧
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{dfg::DataFlowGraph, values::{Branch, Call, Jump}, BasicBlock, FunctionData, TypeKind, Value, ValueKind};
+
+use super::graph_coloring::graph_coloring;
+use super::linear_scan::linear_scan;
+use super::liveness::Liveness;
+use super::{is_address_taken, is_callee_saved, is_local_alloc, AssGen, ErrorReason, RegAlloc, Symbol};
+
+/// 通过寄存器传递的参数个数
+const REGISTER_ARGS: usize = 8;
+
+impl<'p> AssGen<'p> {
+    /// 生成函数体之前的准备工作
+    /// 为基本块分配标签、为参数分配栈空间、找出可以复用栈帧的尾调用、预留传参用的栈空间
+    pub(super) fn prepare_function(&mut self, f: &FunctionData) {
+        let name = self.strip_symbol_prefix(f.name()).to_string();
+        for (&bb, _) in f.layout().bbs() {
+            let bb_name = f.dfg().bb(bb).name().as_ref().map_or("bb", |n| self.strip_symbol_prefix(n));
+            let mut label = format!(".L{}_{}", name, bb_name);
+            let mut count = 0;
+            while self.used_labels.contains(&label) {
+                count += 1;
+                label = format!(".L{}_{}_{}", name, bb_name, count);
+            }
+            self.used_labels.insert(label.clone());
+            self.block_labels.insert(bb, label);
+        }
+
+        let mut tail_calls = HashSet::new();
+        let mut skipped = HashSet::new();
+        let mut has_call = false;
+        let mut outgoing_args = 0;
+        for (_, node) in f.layout().bbs() {
+            let insts: Vec<Value> = node.insts().keys().copied().collect();
+            for (i, &inst) in insts.iter().enumerate() {
+                let ValueKind::Call(call) = f.dfg().value(inst).kind() else {
+                    continue;
+                };
+                match insts.get(i + 1) {
+                    Some(&ret) if self.is_sibling_tail_call(f.dfg(), inst, call, ret) => {
+                        tail_calls.insert(inst);
+                        skipped.insert(ret);
+                    },
+                    _ => {
+                        has_call = true;
+                        outgoing_args = outgoing_args.max(call.args().len().saturating_sub(REGISTER_ARGS));
+                    }
+                }
+            }
+        }
+
+        // 栈帧的最底部留给传参，局部变量从其上方开始分配
+        let function = self.current_func.as_mut().unwrap();
+        function.stack_size = outgoing_args as i32 * 4;
+        function.has_call = has_call;
+        function.tail_calls = tail_calls;
+        function.skipped = skipped;
+
+        let allocation = match self.regalloc {
+            RegAlloc::Greedy => None,
+            RegAlloc::Linear => {
+                let liveness = Liveness::new(f);
+                let allocation = linear_scan(f, &liveness, &self.allocatable);
+                Some((liveness, allocation))
+            },
+            RegAlloc::Graph => {
+                let liveness = Liveness::new(f);
+                // 冲突图过大（例如成千上万个同时活跃的值）时退回线性扫描
+                let allocation = graph_coloring(f, &liveness, &self.allocatable)
+                    .unwrap_or_else(|| linear_scan(f, &liveness, &self.allocatable));
+                Some((liveness, allocation))
+            }
+        };
+        if let Some((liveness, allocation)) = allocation {
+            for value in &liveness.values {
+                match allocation.registers.get(value) {
+                    Some(r) => {
+                        self.symbol_table.insert(*value, Symbol::Register(r.to_string()));
+                    },
+                    None => {
+                        self.new_stack_symbol(value);
+                    }
+                }
+            }
+            self.current_func.as_mut().unwrap().call_saves = allocation.call_saves.into_iter()
+                .map(|(call, registers)| (call, registers.into_iter().map(String::from).collect()))
+                .collect();
+        }
+
+        // 地址被使用的局部变量放在栈上，通过地址读写的就是这块栈空间
+        for (_, node) in f.layout().bbs() {
+            for &inst in node.insts().keys() {
+                if is_local_alloc(f.dfg(), inst) && is_address_taken(f.dfg(), inst)
+                    && !matches!(self.symbol_table.get(&inst), Some(Symbol::Stack(_))) {
+                    self.new_stack_symbol(&inst);
+                }
+            }
+        }
+
+        // 分配到寄存器的参数一定就在原本的 ai 中，不需要移动
+        // 其他参数复制到栈上（贪心分配时一律放在栈上，避免被之后的函数调用覆盖）
+        for (i, &param) in f.params().iter().enumerate() {
+            let slot = match self.symbol_table.get(&param) {
+                Some(Symbol::Register(_)) => continue,
+                Some(Symbol::Stack(slot)) => *slot,
+                None => self.new_stack_symbol(&param)
+            };
+            if i < REGISTER_ARGS {
+                let store = self.store_inst_str(&format!("a{}", i), slot);
+                self.current_func.as_mut().unwrap().prologue.push(store);
+            } else {
+                self.current_func.as_mut().unwrap().stack_params.push((((i - REGISTER_ARGS) * 4) as i32, slot));
+            }
+        }
+    }
+
+    /// 判断一条 call 能否作为尾调用复用当前栈帧
+    /// 要求：紧随其后的是返回该调用结果的 ret，参数都能通过寄存器传递，
+    /// 并且没有指向当前栈帧的指针参数（栈帧在跳转前就被释放了）
+    fn is_sibling_tail_call(&self, dfg: &DataFlowGraph, inst: Value, call: &Call, ret: Value) -> bool {
+        let ValueKind::Return(r) = dfg.value(ret).kind() else {
+            return false;
+        };
+        let returns_result = match r.value() {
+            Some(v) => v == inst,
+            None => dfg.value(inst).ty().is_unit()
+        };
+        returns_result && call.args().len() <= REGISTER_ARGS && call.args().iter().all(|&arg| {
+            arg.is_global() || !matches!(dfg.value(arg).ty().kind(), TypeKind::Pointer(_))
+                || matches!(dfg.value(arg).kind(), ValueKind::FuncArgRef(_))
+        })
+    }
+
+    /// 记录一个函数出口，出口处会依次生成函数后置和 exit 指令
+    pub(super) fn add_function_exit(&mut self, exit: String) {
+        if let Some(function) = &mut self.current_func {
+            function.exits.push((function.body.len(), exit));
+        } else {
+            panic!("当前不是函数环境")
+        }
+    }
+
+    /// 创建一个函数内部使用的辅助标签
+    /// 基本块名称中不会出现 “.”，所以不会和基本块的标签重复
+    fn new_internal_label(&mut self) -> String {
+        let function = self.current_func.as_mut().unwrap();
+        function.label_counter += 1;
+        format!(".L{}.{}", &function.name[1..], function.label_counter)
+    }
+
+    /// 生成条件跳转
+    /// beqz 的跳转范围有限，所以只让它跳过紧随其后的真分支，真假分支都用 j 跳到目标基本块
+    pub(super) fn generate_branch(&mut self, dfg: &DataFlowGraph, branch: &Branch) -> Result<(), ErrorReason> {
+        let (cond, temp) = self.load_operand(dfg, branch.cond())?;
+        let false_label = self.new_internal_label();
+        self.add_inst_to_function(self.beqz_inst_str(&cond, &false_label));
+        if temp {
+            self.remove_reserved_register(&cond);
+        }
+        self.generate_block_args(dfg, branch.true_bb(), branch.true_args())?;
+        self.add_inst_to_function(self.jump_inst_str(&self.block_labels[&branch.true_bb()]));
+        self.add_inst_to_function(self.label_str(&false_label));
+        self.generate_block_args(dfg, branch.false_bb(), branch.false_args())?;
+        self.add_inst_to_function(self.jump_inst_str(&self.block_labels[&branch.false_bb()]));
+        Ok(())
+    }
+
+    /// 生成无条件跳转
+    pub(super) fn generate_jump(&mut self, dfg: &DataFlowGraph, jump: &Jump) -> Result<(), ErrorReason> {
+        self.generate_block_args(dfg, jump.target(), jump.args())?;
+        self.add_inst_to_function(self.jump_inst_str(&self.block_labels[&jump.target()]));
+        Ok(())
+    }
+
+    /// 把跳转的实参赋值给目标基本块的参数
+    /// 实参已经在参数的位置上时（例如图着色合并了两者）不需要赋值。
+    /// 实参和参数的位置有重叠时（例如交换两个参数），逐个赋值会互相覆盖，
+    /// 此时先把所有实参存到临时栈空间，再依次取出
+    fn generate_block_args(&mut self, dfg: &DataFlowGraph, target: BasicBlock, args: &[Value]) -> Result<(), ErrorReason> {
+        let params = dfg.bb(target).params().to_vec();
+        let dests: Vec<Symbol> = params.iter().map(|p| self.find_or_allocate_symbol(p)).collect();
+        let (args, dests): (Vec<Value>, Vec<Symbol>) = args.iter()
+            .copied()
+            .zip(dests)
+            .filter(|(arg, dest)| self.symbol_table.get(arg) != Some(dest))
+            .unzip();
+        let conflict = args.len() > 1 && args.iter().any(|arg| self.symbol_table.get(arg).is_some_and(|s| dests.contains(s)));
+        if !conflict {
+            for (&arg, dest) in args.iter().zip(&dests) {
+                self.move_value_to_symbol(dfg, arg, dest)?;
+            }
+            return Ok(());
+        }
+        let slots = self.move_slots(args.len());
+        for (&arg, &slot) in args.iter().zip(&slots) {
+            self.move_value_to_symbol(dfg, arg, &Symbol::Stack(slot))?;
+        }
+        for (dest, &slot) in dests.iter().zip(&slots) {
+            match dest {
+                Symbol::Register(r) => self.add_inst_to_function(self.load_inst_str(r, slot)),
+                Symbol::Stack(s) => {
+                    let register = self.get_reserved_register(slot)?;
+                    self.add_inst_to_function(self.store_inst_str(&register, *s));
+                    self.remove_reserved_register(&register);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 返回 n 个临时栈空间，不够时新分配，多次跳转之间复用
+    fn move_slots(&mut self, n: usize) -> Vec<i32> {
+        while self.current_func.as_ref().unwrap().move_slots.len() < n {
+            let slot = self.new_stack_slot();
+            self.current_func.as_mut().unwrap().move_slots.push(slot);
+        }
+        self.current_func.as_ref().unwrap().move_slots[..n].to_vec()
+    }
+
+    /// 生成函数调用
+    pub(super) fn generate_call(&mut self, dfg: &DataFlowGraph, inst: Value, call: &Call) -> Result<(), ErrorReason> {
+        let callee = self.strip_symbol_prefix(self.prog.func(call.callee()).name()).to_string();
+        // 调用者保存寄存器会被被调用者随意覆盖，s 寄存器则由被调用者负责恢复
+        // 需要恢复的是调用之后仍然活跃的值所在的寄存器；贪心分配时不知道活跃信息，所有已占用的寄存器都要恢复
+        let mut live: Vec<String> = match self.regalloc {
+            RegAlloc::Greedy => self.register_status.iter()
+                .filter(|&(_, used)| *used)
+                .map(|(name, _)| name.clone())
+                .collect(),
+            RegAlloc::Linear | RegAlloc::Graph => self.current_func.as_ref().unwrap().call_saves[&inst].clone()
+        };
+        live.retain(|r| !is_callee_saved(r));
+        live.sort();
+        // 作为实参来源的寄存器也要先保存，防止传参时互相覆盖
+        let mut saved_registers = live.clone();
+        saved_registers.extend(self.argument_registers(call));
+        saved_registers.sort();
+        saved_registers.dedup();
+        let saved = self.save_registers(&saved_registers);
+        self.pass_arguments(dfg, call.args(), &saved)?;
+        self.add_inst_to_function(self.call_inst_str(&callee));
+
+        let restored: HashMap<String, i32> = saved.into_iter().filter(|(r, _)| live.contains(r)).collect();
+        if dfg.value(inst).ty().is_unit() {
+            self.restore_registers(&restored);
+            return Ok(());
+        }
+        // 恢复寄存器会覆盖 a0，先把返回值转移到保留寄存器中
+        let result_register = self.get_reserved_register_without_load()?;
+        self.add_inst_to_function(self.move_register_inst_str(&result_register, "a0"));
+        self.restore_registers(&restored);
+        match self.find_or_allocate_symbol(&inst) {
+            Symbol::Register(r) => self.add_inst_to_function(self.move_register_inst_str(&r, &result_register)),
+            Symbol::Stack(s) => self.add_inst_to_function(self.store_inst_str(&result_register, s))
+        }
+        self.remove_reserved_register(&result_register);
+        Ok(())
+    }
+
+    /// 生成复用当前栈帧的尾调用
+    /// 参数放入 a0~a7 后恢复栈帧，再直接跳转到被调用者
+    pub(super) fn generate_tail_call(&mut self, dfg: &DataFlowGraph, call: &Call) -> Result<(), ErrorReason> {
+        let callee = self.strip_symbol_prefix(self.prog.func(call.callee()).name()).to_string();
+        // 只需要保存作为实参来源的寄存器，防止传参时互相覆盖
+        let saved = self.argument_registers(call);
+        let saved = self.save_registers(&saved);
+        self.pass_arguments(dfg, call.args(), &saved)?;
+        self.add_function_exit(self.tail_inst_str(&callee));
+        Ok(())
+    }
+
+    /// 返回作为实参来源的寄存器（排序去重）
+    fn argument_registers(&self, call: &Call) -> Vec<String> {
+        let mut sources: Vec<String> = call.args().iter()
+            .filter_map(|arg| match self.symbol_table.get(arg) {
+                Some(Symbol::Register(r)) => Some(r.clone()),
+                _ => None
+            })
+            .collect();
+        sources.sort();
+        sources.dedup();
+        sources
+    }
+
+    /// 把寄存器保存到各自的栈空间中，返回寄存器到栈空间的映射
+    fn save_registers(&mut self, registers: &[String]) -> HashMap<String, i32> {
+        let mut saved = HashMap::new();
+        for register in registers {
+            let slot = match self.current_func.as_ref().unwrap().save_slots.get(register) {
+                Some(&slot) => slot,
+                None => {
+                    let slot = self.new_stack_slot();
+                    self.current_func.as_mut().unwrap().save_slots.insert(register.clone(), slot);
+                    slot
+                }
+            };
+            self.add_inst_to_function(self.store_inst_str(register, slot));
+            saved.insert(register.clone(), slot);
+        }
+        saved
+    }
+
+    /// 从栈空间中恢复 save_registers 保存的寄存器
+    fn restore_registers(&mut self, saved: &HashMap<String, i32>) {
+        let mut registers: Vec<(&String, &i32)> = saved.iter().collect();
+        registers.sort();
+        for (register, &slot) in registers {
+            self.add_inst_to_function(self.load_inst_str(register, slot));
+        }
+    }
+
+    /// 按调用约定放置实参
+    /// saved 中的寄存器已经保存到栈上，从栈上读取，这样写入 a0~a7 时不会覆盖还没读取的实参
+    fn pass_arguments(&mut self, dfg: &DataFlowGraph, args: &[Value], saved: &HashMap<String, i32>) -> Result<(), ErrorReason> {
+        for (i, &arg) in args.iter().enumerate() {
+            let register = if i < REGISTER_ARGS {
+                format!("a{}", i)
+            } else {
+                self.get_reserved_register_without_load()?
+            };
+            match dfg.value(arg).kind() {
+                ValueKind::Integer(integer) => self.add_inst_to_function(self.init_register_str(&register, integer.value())),
+                ValueKind::Undef(_) => {},
+                ValueKind::Alloc(_) => self.add_inst_to_function(self.local_address_str(dfg, arg, &register)?),
+                _ => match self.symbol_of(dfg, arg)? {
+                    Symbol::Register(r) => match saved.get(&r) {
+                        Some(&slot) => self.add_inst_to_function(self.load_inst_str(&register, slot)),
+                        None => self.add_inst_to_function(self.move_register_inst_str(&register, &r))
+                    },
+                    Symbol::Stack(s) => self.add_inst_to_function(self.load_inst_str(&register, s))
+                }
+            }
+            if i >= REGISTER_ARGS {
+                self.add_inst_to_function(self.store_inst_str(&register, ((i - REGISTER_ARGS) * 4) as i32));
+                self.remove_reserved_register(&register);
+            }
+        }
+        Ok(())
+    }
+
+    /// 返回把局部变量 alloc 的地址放到 register 中的指令
+    /// 地址被使用的局部变量在 prepare_function 中已经被放到了栈上
+    pub(super) fn local_address_str(&self, dfg: &DataFlowGraph, alloc: Value, register: &str) -> Result<String, ErrorReason> {
+        match self.symbol_of(dfg, alloc)? {
+            Symbol::Stack(slot) => Ok(self.stack_address_inst_str(register, slot)),
+            Symbol::Register(_) => unreachable!("地址被使用的局部变量总是放在栈上")
+        }
+    }
+
+    /// 把一个值放到寄存器中，返回所在的寄存器，以及它是否是临时申请的保留寄存器（用完后需要释放）
+    /// 保留寄存器不够用或者操作数没有位置时返回错误
+    pub(super) fn load_operand(&mut self, dfg: &DataFlowGraph, value: Value) -> Result<(String, bool), ErrorReason> {
+        Ok(match dfg.value(value).kind() {
+            ValueKind::Integer(i) if i.value() == 0 => (String::from("x0"), false),
+            ValueKind::Undef(_) => (String::from("x0"), false),
+            ValueKind::Integer(i) => {
+                let register = self.get_reserved_register_without_load()?;
+                self.add_inst_to_function(self.init_register_str(&register, i.value()));
+                (register, true)
+            },
+            _ => match self.symbol_of(dfg, value)? {
+                Symbol::Register(r) => (r, false),
+                Symbol::Stack(s) => (self.get_reserved_register(s)?, true)
+            }
+        })
+    }
+
+    /// 把一个值赋给某个符号（寄存器或栈空间）
+    pub(super) fn move_value_to_symbol(&mut self, dfg: &DataFlowGraph, value: Value, dest: &Symbol) -> Result<(), ErrorReason> {
+        match dest {
+            Symbol::Register(r) => match dfg.value(value).kind() {
+                ValueKind::Integer(i) => self.add_inst_to_function(self.init_register_str(r, i.value())),
+                ValueKind::Undef(_) => {},
+                _ => match self.symbol_of(dfg, value)? {
+                    Symbol::Register(src) => self.add_inst_to_function(self.move_register_inst_str(r, &src)),
+                    Symbol::Stack(s) => self.add_inst_to_function(self.load_inst_str(r, s))
+                }
+            },
+            Symbol::Stack(s) => {
+                let (register, temp) = self.load_operand(dfg, value)?;
+                self.add_inst_to_function(self.store_inst_str(&register, *s));
+                if temp {
+                    self.remove_reserved_register(&register);
+                }
+            }
+        }
+        Ok(())
+    }
+}
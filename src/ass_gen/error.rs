@@ -0,0 +1,55 @@
+/// 此文件存放汇编生成阶段的错误
+/// - 生成单条指令的过程中只知道失败的原因（ErrorReason）
+/// - generate_function 负责补上出错的函数和指令，组成完整的 CodegenError
+use std::fmt;
+
+use crate::error_report::ProblemInfo;
+
+/// 汇编生成失败的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorReason {
+    /// 后端还不支持的指令或者用法，附带说明
+    Unsupported(String),
+    /// 一条指令需要的临时寄存器超过了保留寄存器的个数
+    ReservedRegistersExhausted,
+    /// 操作数没有被分配位置，通常是操作数在使用之前没有被定义
+    MissingOperand(String)
+}
+
+/// 汇编生成错误：在哪个函数的哪条指令上，因为什么原因失败
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodegenError {
+    pub function: String,
+    pub instruction: String,
+    pub reason: ErrorReason
+}
+
+impl fmt::Display for ErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorReason::Unsupported(what) => write!(f, "{} is not supported by the RISC-V backend", what),
+            ErrorReason::ReservedRegistersExhausted => write!(f, "ran out of reserved temporary registers"),
+            ErrorReason::MissingOperand(operand) => write!(f, "operand {} has no assigned location", operand)
+        }
+    }
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (in function '{}', at {})", self.reason, self.function, self.instruction)
+    }
+}
+
+impl CodegenError {
+    /// 转换为和前端错误同样格式的诊断信息
+    pub fn to_problem(&self) -> ProblemInfo {
+        ProblemInfo::error(
+            format!("code generation failed: {}", self.reason),
+            Vec::new(),
+            Some(vec![
+                format!("in function '{}'", self.function),
+                format!("at {}", self.instruction)
+            ])
+        )
+    }
+}
@@ -1,18 +1,10 @@
-/// 此文件存放 AssGen 汇编类中，用于向输出字符串打印各种语句的函数
+/// 此文件存放 AssGen 汇编类中，用于生成各种语句的汇编文本的函数
 use super::AssGen;
 
 impl<'p> AssGen<'p> {
     /// 创建一个初始化寄存器的语句
     /// register: 寄存器名称
     /// value：初始化值
-    pub(super) fn init_register(&mut self, register: &str, value: i32) {
-        let s = self.init_register_str(register, value);
-        if !s.is_empty() {
-            self.out.push_str(&s);
-        }
-    }
-
-    /// String 版本：创建一个初始化寄存器的语句
     pub(super) fn init_register_str(&self, register: &str, value: i32) -> String {
         if register == "x0" {
             String::new()
@@ -21,36 +13,8 @@ impl<'p> AssGen<'p> {
         }
     }
 
-    /// 创建一个清空某寄存器的语句
-    /// register: 寄存器名称
-    /// 实现上，通过 xor <register>, <register>, x0 来强行清空寄存器
-    pub(super) fn clear_register(&mut self, register: &str) {
-        let s = self.clear_register_str(register);
-        if !s.is_empty() {
-            self.out.push_str(&s);
-        }
-    }
-
-    /// String 版本：创建一个清空某寄存器的语句
-    pub(super) fn clear_register_str(&self, register: &str) -> String {
-        if register == "x0" {
-            String::new()
-        } else {
-            format!("\txor\t{},{},x0\n", register, register)
-        }
-    }
-
-
     /// 创建一个减法的指令
     /// 计算 lhr 寄存器 - rhr 寄存器的值，将其存入 result 寄存器中
-    pub(super) fn sub_inst(&mut self, result: &str, lhr: &str, rhr: &str) {
-        let s = self.sub_inst_str(result, lhr, rhr);
-        if !s.is_empty() {
-            self.out.push_str(&s);
-        }
-    }
-
-    /// String 版本：创建一个减法的指令
     pub(super) fn sub_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
         if rhr == "x0" && result == lhr {
             String::new()
@@ -61,132 +25,94 @@ impl<'p> AssGen<'p> {
 
     /// 创建一个加法的指令
     /// 计算 lhr 寄存器 + rhr 寄存器的值，将其存入 result 寄存器中
-    pub(super) fn add_inst(&mut self, result: &str, lhr: &str, rhr: &str) {
-        let s = self.add_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个加法的指令
     pub(super) fn add_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
         format!("\tadd\t{},{},{}\n", result, lhr, rhr)
     }
 
     /// 创建一个乘法的指令
     /// 计算 lhr 寄存器 * rhr 寄存器的值，将其存入 result 寄存器中
-    pub(super) fn mul_inst(&mut self, result: &str, lhr: &str, rhr: &str) {
-        let s = self.mul_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个乘法的指令
     pub(super) fn mul_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
         format!("\tmul\t{},{},{}\n", result, lhr, rhr)
     }
 
     /// 创建一个除法的指令
     /// 计算 lhr 寄存器 / rhr 寄存器的值，将其存入 result 寄存器中
-    pub(super) fn div_inst(&mut self, result: &str, lhr: &str, rhr: &str) {
-        let s = self.div_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个除法的指令
     pub(super) fn div_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
         format!("\tdiv\t{},{},{}\n", result, lhr, rhr)
     }
     
     /// 创建一个取余数的指令
     /// 计算 lhr 寄存器 % rhr 寄存器的值，将其存入 result 寄存器中
-    pub(super) fn mod_inst(&mut self, result: &str, lhr: &str, rhr: &str) {
-        let s = self.mod_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个取余数的指令
     pub(super) fn mod_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
         format!("\trem\t{},{},{}\n", result, lhr, rhr)
     }
 
     /// 创建一个 AND 的指令
     /// 计算 lhr 寄存器 AND rhr 寄存器的值，将其存入 result 寄存器中
-    pub(super) fn and_inst(&mut self, result: &str, lhr: &str, rhr: &str) {
-        let s = self.and_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个 AND 的指令
     pub(super) fn and_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
         format!("\tand\t{},{},{}\n", result, lhr, rhr)
     }
 
     /// 创建一个 OR 的指令
     /// 计算 lhr 寄存器 OR rhr 寄存器的值，将其存入 result 寄存器中
-    pub(super) fn or_inst(&mut self, result: &str, lhr: &str, rhr: &str) {
-        let s = self.or_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个 OR 的指令
     pub(super) fn or_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
         format!("\tor\t{},{},{}\n", result, lhr, rhr)
     }
 
-    /// 创建一个 neq 0 比较指令
-    /// 如果 register 的值不是 0，存储 1 到 register 寄存器中；否则，存储 0 到 register 中。
-    pub(super) fn eq0_inst(&mut self, register: &str) {
-        let s = self.eq0_inst_str(register);
-        self.out.push_str(&s);
+    /// String 版本：创建一个 XOR 的指令
+    /// 计算 lhr 寄存器 XOR rhr 寄存器的值，将其存入 result 寄存器中
+    pub(super) fn xor_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
+        format!("\txor\t{},{},{}\n", result, lhr, rhr)
     }
 
-    /// String 版本：创建一个 eq 0 比较指令
-    pub(super) fn eq0_inst_str(&self, register: &str) -> String {
-        format!("\tseqz\t{},{}\n", register, register)
+    /// String 版本：创建一个左移（sll）的指令
+    /// 将 lhr 寄存器左移 rhr 寄存器低 5 位表示的位数，存入 result 寄存器中
+    pub(super) fn shl_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
+        format!("\tsll\t{},{},{}\n", result, lhr, rhr)
+    }
+
+    /// String 版本：创建一个逻辑右移（srl）的指令
+    pub(super) fn shr_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
+        format!("\tsrl\t{},{},{}\n", result, lhr, rhr)
+    }
+
+    /// String 版本：创建一个算术右移（sra）的指令
+    pub(super) fn sar_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
+        format!("\tsra\t{},{},{}\n", result, lhr, rhr)
+    }
+
+    /// String 版本：创建一个寄存器和立即数运算的指令，例如 addi、xori、slti
+    /// 计算 src 寄存器 op 立即数 imm，存入 result 寄存器中，imm 需要在 12 位有符号范围内
+    pub(super) fn imm_inst_str(&self, op: &str, result: &str, src: &str, imm: i32) -> String {
+        format!("\t{}\t{},{},{}\n", op, result, src, imm)
     }
 
     /// 创建一个 eq 0 比较指令
-    /// 如果 register 的值是 0，存储 1 到 register 寄存器中；否则，存储 1 到 register 中。
-    pub(super) fn neq0_inst(&mut self, register: &str) {
-        let s = self.neq0_inst_str(register);
-        self.out.push_str(&s);
+    /// 如果 register 的值是 0，存储 1 到 register 寄存器中；否则，存储 0 到 register 中。
+    pub(super) fn eq0_inst_str(&self, register: &str) -> String {
+        format!("\tseqz\t{},{}\n", register, register)
     }
 
-    /// String 版本：创建一个 neq 0 比较指令
+    /// 创建一个 neq 0 比较指令
+    /// 如果 register 的值不是 0，存储 1 到 register 寄存器中；否则，存储 0 到 register 中。
     pub(super) fn neq0_inst_str(&self, register: &str) -> String {
         format!("\tsnez\t{},{}\n", register, register)
     }
 
     /// 创建一个小于比较指令
     /// 如果 lhr < rhr，写入 1 到 result 中，否则写入 0
-    pub(super) fn lt_inst(&mut self, result: &str, lhr: &str, rhr: &str)  {
-        let s = self.lt_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个小于比较指令
     pub(super) fn lt_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String  {
         format!("\tslt\t{},{},{}\n", result, lhr, rhr)
     }
 
     /// 创建一个大于比较指令
     /// 如果 lhr > rhr，写入 1 到 result 中，否则写入 0
-    pub(super) fn gt_inst(&mut self, result: &str, lhr: &str, rhr: &str)  {
-        let s = self.gt_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个大于比较指令
     pub(super) fn gt_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String  {
         format!("\tsgt\t{},{},{}\n", result, lhr, rhr)
     }
 
     /// 创建一个小于等于比较指令
     /// 如果 lhr <= rhr，写入 1 到 result 中，否则写入 0
-    pub(super) fn le_inst(&mut self, result: &str, lhr: &str, rhr: &str)  {
-        let s = self.le_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个小于等于比较指令
     pub(super) fn le_inst_str(&self, result: &str, lhr: &str, rhr: &str)  -> String {
         let mut s = self.gt_inst_str(result, lhr, rhr);
         s.push_str(&format!("\tseqz\t{},{}\n", result, result));
@@ -195,111 +121,96 @@ impl<'p> AssGen<'p> {
 
     /// 创建一个大于等于比较指令
     /// 如果 lhr > rhr，写入 1 到 result 中，否则写入 0
-    pub(super) fn ge_inst(&mut self, result: &str, lhr: &str, rhr: &str)  {
-        let s = self.ge_inst_str(result, lhr, rhr);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个大于等于比较指令
     pub(super) fn ge_inst_str(&self, result: &str, lhr: &str, rhr: &str)  -> String {
         let mut s = self.lt_inst_str(result, lhr, rhr);
         s.push_str(&format!("\tseqz\t{},{}\n", result, result));
         s
     }
 
-    /// 创建一个返回指令
+    /// 创建一个设置返回值的指令
     /// value: 返回值
-    pub(super) fn return_value_inst(&mut self, value: Option<i32>) {
-        let s = self.return_value_inst_str(value);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个返回指令（可选立即数）
+    /// 真正的 ret 在恢复栈帧之后生成，见 ret_inst_str
     pub(super) fn return_value_inst_str(&self, value: Option<i32>) -> String {
-        let mut s = String::new();
-        if let Some(i) = value {
-            s.push_str(&self.init_register_str("a0", i));
+        match value {
+            Some(i) => self.init_register_str("a0", i),
+            None => String::new()
         }
-        s.push_str("\tret\n");
-        s
     }
 
-    /// 创建一个返回指令
-    /// register: 返回值目前存储在哪个寄存器中
-    pub(super) fn return_register_inst(&mut self, register: &str) {
-        let s = self.return_register_inst_str(register);
-        self.out.push_str(&s);
+    /// String 版本：创建一个 ret 指令
+    pub(super) fn ret_inst_str(&self) -> String {
+        String::from("\tret\n")
     }
 
-    /// String 版本：创建一个返回指令（寄存器）
+    /// 创建一个返回指令
+    /// register: 返回值目前存储在哪个寄存器中
     pub(super) fn return_register_inst_str(&self, register: &str) -> String {
         format!("\tmv\ta0,{}\n", register)
     }
 
     pub(super) fn return_stack_inst_str(&self, stack: i32) -> String {
-        format!("\tlw\ta0,{}(sp)\n", stack)
+        self.load_inst_str("a0", stack)
+    }
+
+    /// 返回访问 sp + offset 处栈空间的地址写法，以及计算这个地址需要的前置指令
+    /// offset 超出 12 位有符号立即数的范围时，先用 t0 中继计算出完整的地址
+    fn stack_address_str(&self, offset: i32) -> (String, String) {
+        if (-2048..=2047).contains(&offset) {
+            (String::new(), format!("{}(sp)", offset))
+        } else {
+            let mut s = String::new();
+            s.push_str(&self.init_register_str("t0", offset));
+            s.push_str(&self.add_inst_str("t0", "sp", "t0"));
+            (s, String::from("0(t0)"))
+        }
     }
 
     /// 创建一个加载（lw）指令
     /// result: 加载后的内容放在哪个寄存器中
     /// offset: 加载栈地址相对 sp 指针的偏移量。此编译器的栈使用规定为：
     /// 函数栈从高地址向低地址生长（RISC-V 要求），在每个函数栈内部，变量从低向高生长
-    /// 因为此规定，offset 一定大于 0（不然你就指到其他函数或者鬼知道哪里的栈去了），小于 0 的 offset 会直接引发崩溃。
-    /// 这里不用 u32 而使用 i32 是为了和 RISC-V 指令集采用的有符号整数对应。用 u32（32位无符号）的话，可能会超过 RISC-V 的最大立即数（32位有符号）限制
-    /// 目前每个变量大小都是 4 字节，没有函数参数
-    pub(super) fn load_inst(&mut self, result: &str, offset: i32) {
-        let s = self.load_inst_str(result, offset);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：创建一个加载（lw）指令
+    /// 偏移量可以是任意的 i32，超出 12 位立即数范围时通过 t0 中继
     pub(super) fn load_inst_str(&self, result: &str, offset: i32) -> String {
-        if offset < 0 {
-            panic!("Offset 必须大于等于 0，得到 {}", offset)
-        }
-        if offset > 2047 {
-            let mut s = String::new();
-            s.push_str(&self.init_register_str("t0", offset));
-            s.push_str(&self.add_inst_str("t0", "sp", "t0"));
-            s.push_str(&format!("\tlw\t{}, 0(t0)\n", result));
-            s
-        } else {
-            format!("\tlw\t{},{}(sp)\n", result, offset)
-        }
+        let (mut s, address) = self.stack_address_str(offset);
+        s.push_str(&format!("\tlw\t{},{}\n", result, address));
+        s
     }
 
     /// 创建一个存储（sw）指令
     /// result: 待存储的内容放在哪个寄存器中
     /// offset: 存储栈地址相对 sp 指针的偏移量
-    pub(super) fn store_inst(&mut self, source: &str, offset: i32) {
-        let s = self.store_inst_str(source, offset);
-        self.out.push_str(&s);
+    /// source 不能是 t0，因为 t0 可能被用来中继地址
+    pub(super) fn store_inst_str(&self, source: &str, offset: i32) -> String {
+        let (mut s, address) = self.stack_address_str(offset);
+        s.push_str(&format!("\tsw\t{},{}\n", source, address));
+        s
     }
 
-    /// String 版本：创建一个存储（sw）指令
-    pub(super) fn store_inst_str(&self, source: &str, offset: i32) -> String {
-        if offset < 0 {
-            panic!("Offset 必须大于等于 0，得到 {}", offset)
-        }
-        if offset > 2047 {
-            let mut s = String::new();
-            s.push_str(&self.init_register_str("t0", offset));
-            s.push_str(&self.add_inst_str("t0", "sp", "t0"));
-            s.push_str(&format!("\tsw\t{}, 0(t0)\n", source));
-            s
+    /// 创建一个读取指针指向的内存的指令
+    /// pointer 寄存器中是地址，读出的内容存入 result 寄存器中
+    pub(super) fn load_pointer_inst_str(&self, result: &str, pointer: &str) -> String {
+        format!("\tlw\t{},0({})\n", result, pointer)
+    }
+
+    /// 创建一个写入指针指向的内存的指令
+    pub(super) fn store_pointer_inst_str(&self, source: &str, pointer: &str) -> String {
+        format!("\tsw\t{},0({})\n", source, pointer)
+    }
+
+    /// 创建一个计算栈地址 sp + offset 的指令，结果存入 result 寄存器中
+    /// offset 超出 12 位有符号立即数的范围时通过 t0 中继
+    pub(super) fn stack_address_inst_str(&self, result: &str, offset: i32) -> String {
+        if (-2048..=2047).contains(&offset) {
+            format!("\taddi\t{},sp,{}\n", result, offset)
         } else {
-            format!("\tsw\t{},{}(sp)\n", source, offset)
+            let mut s = self.init_register_str("t0", offset);
+            s.push_str(&self.add_inst_str(result, "sp", "t0"));
+            s
         }
     }
 
     /// 移动栈指针 sp 的指令
     /// 用于在函数开始前和结束后修改栈边界
-    pub(super) fn move_sp_inst(&mut self, value: i32) {
-        let s = self.move_sp_inst_str(value);
-        self.out.push_str(&s);
-    }
-
-    /// String 版本：移动栈指针 sp 的指令
     pub(super) fn move_sp_inst_str(&self, value: i32) -> String {
         if value == 0 {
             String::new()
@@ -313,6 +224,18 @@ impl<'p> AssGen<'p> {
         }
     }
 
+    /// String 版本：设置帧指针 s0 为进入函数时的栈指针，也就是 sp + value
+    pub(super) fn set_frame_pointer_inst_str(&self, value: i32) -> String {
+        if !(-2048..=2047).contains(&value) {
+            let mut s = String::new();
+            s.push_str(&self.init_register_str("t0", value));
+            s.push_str(&self.add_inst_str("s0", "sp", "t0"));
+            s
+        } else {
+            format!("\taddi\ts0,sp,{}\n", value)
+        }
+    }
+
     pub(super) fn move_register_inst_str(&self, dest: &str, src: &str) -> String {
         if src != dest {
             format!("\tmv\t{},{}\n", dest, src)
@@ -320,4 +243,59 @@ impl<'p> AssGen<'p> {
             String::new()
         }
     }
-}
\ No newline at end of file
+
+    /// String 版本：创建一个取高位乘法（mulh）的指令
+    /// 计算 lhr 寄存器 * rhr 寄存器的 64 位有符号乘积，将高 32 位存入 result 寄存器中
+    pub(super) fn mulh_inst_str(&self, result: &str, lhr: &str, rhr: &str) -> String {
+        format!("\tmulh\t{},{},{}\n", result, lhr, rhr)
+    }
+
+    /// String 版本：创建一个立即数左移（slli）的指令
+    /// 将 src 寄存器左移 shamt 位，存入 result 寄存器中
+    pub(super) fn shl_imm_inst_str(&self, result: &str, src: &str, shamt: u32) -> String {
+        format!("\tslli\t{},{},{}\n", result, src, shamt)
+    }
+
+    /// String 版本：创建一个立即数算术右移（srai）的指令
+    pub(super) fn sar_imm_inst_str(&self, result: &str, src: &str, shamt: u32) -> String {
+        format!("\tsrai\t{},{},{}\n", result, src, shamt)
+    }
+
+    /// String 版本：创建一个立即数逻辑右移（srli）的指令
+    pub(super) fn shr_imm_inst_str(&self, result: &str, src: &str, shamt: u32) -> String {
+        format!("\tsrli\t{},{},{}\n", result, src, shamt)
+    }
+
+    /// String 版本：创建一个取相反数的指令
+    /// 实现上为 sub <result>, x0, <src>
+    pub(super) fn neg_inst_str(&self, result: &str, src: &str) -> String {
+        self.sub_inst_str(result, "x0", src)
+    }
+
+    /// String 版本：创建一个标签
+    pub(super) fn label_str(&self, label: &str) -> String {
+        format!("{}:\n", label)
+    }
+
+    /// String 版本：创建一个无条件跳转（j）的指令
+    pub(super) fn jump_inst_str(&self, label: &str) -> String {
+        format!("\tj\t{}\n", label)
+    }
+
+    /// String 版本：创建一个为 0 时跳转（beqz）的指令
+    /// 条件跳转的偏移范围只有 ±4KiB，调用方需要保证 label 离得足够近
+    pub(super) fn beqz_inst_str(&self, register: &str, label: &str) -> String {
+        format!("\tbeqz\t{},{}\n", register, label)
+    }
+
+    /// String 版本：创建一个函数调用（call）的指令
+    pub(super) fn call_inst_str(&self, function: &str) -> String {
+        format!("\tcall\t{}\n", function)
+    }
+
+    /// String 版本：创建一个尾调用（tail）的指令
+    /// 跳转到 function 且不修改 ra，被调用者返回时直接返回到当前函数的调用者
+    pub(super) fn tail_inst_str(&self, function: &str) -> String {
+        format!("\ttail\t{}\n", function)
+    }
+}
@@ -0,0 +1,225 @@
+/// 此文件存放图着色寄存器分配（Chaitin/Briggs）
+/// - 冲突图：逆序遍历每个基本块，定义一个值时，它与此刻所有活跃的值冲突；同一基本块的参数同时定义，互相冲突
+/// - 合并：跳转实参和目标基本块参数之间的赋值，如果两端不冲突并且满足 Briggs 的保守条件，就合并为一个结点，省去 mv
+/// - 简化：反复移除度数小于寄存器个数的结点；没有这样的结点时，乐观地移除溢出代价与度数之比最小的结点
+/// - 选择：按移除的逆序着色，乐观移除的结点找不到颜色时才真正溢出到栈上；
+///   跨越函数调用的结点优先使用被调用者保存寄存器，其他结点优先使用调用者保存寄存器
+/// - 前 8 个函数参数预先着色为对应的 ai，不需要在函数开头移动；其余参数留在栈上
+///
+/// 冲突图的边数在最坏情况下是值的个数的平方，超过 MAX_INTERFERENCE_EDGES 时放弃图着色，由调用方退回线性扫描。
+use std::collections::{BTreeSet, HashMap};
+
+use koopa::ir::{BasicBlock, FunctionData, Value, ValueKind};
+
+use super::is_callee_saved;
+use super::liveness::{Allocation, Liveness};
+
+/// 冲突图边数的上限
+const MAX_INTERFERENCE_EDGES: usize = 1 << 20;
+
+/// 冲突图，结点是 Liveness::values 中的下标
+struct Graph {
+    adj: Vec<BTreeSet<usize>>,
+    /// 边数
+    edges: usize,
+    /// 合并后的代表结点（并查集）
+    alias: Vec<usize>
+}
+
+impl Graph {
+    fn add_edge(&mut self, a: usize, b: usize) {
+        if a != b && self.adj[a].insert(b) {
+            self.adj[b].insert(a);
+            self.edges += 1;
+        }
+    }
+
+    fn find(&mut self, node: usize) -> usize {
+        let parent = self.alias[node];
+        if parent == node {
+            return node;
+        }
+        let root = self.find(parent);
+        self.alias[node] = root;
+        root
+    }
+
+    /// 把 b 合并到 a 中，b 的邻居都改为 a 的邻居
+    fn merge(&mut self, a: usize, b: usize) {
+        for neighbor in std::mem::take(&mut self.adj[b]) {
+            self.adj[neighbor].remove(&b);
+            self.add_edge(a, neighbor);
+        }
+        self.alias[b] = a;
+    }
+}
+
+/// 对一个函数做图着色分配，pool 为可分配的寄存器，按优先顺序排列
+/// 冲突图过大时返回 None
+pub(super) fn graph_coloring(f: &FunctionData, liveness: &Liveness, pool: &[&'static str]) -> Option<Allocation> {
+    let dfg = f.dfg();
+    let n = liveness.values.len();
+    let index: HashMap<Value, usize> = liveness.values.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+    let node_of = |value: Value| if value.is_global() { None } else { index.get(&value).copied() };
+    let mut graph = Graph { adj: vec![BTreeSet::new(); n], edges: 0, alias: (0..n).collect() };
+    // 跳转时的赋值（实参，基本块参数）
+    let mut moves: Vec<(usize, usize)> = Vec::new();
+    // 每条函数调用之后仍然活跃的值
+    let mut call_live: Vec<(Value, Vec<usize>)> = Vec::new();
+
+    // 建立冲突图
+    let entry = f.layout().entry_bb();
+    for (&bb, node) in f.layout().bbs() {
+        let mut live: BTreeSet<usize> = liveness.live_out[&bb].iter().map(|&v| index[&v]).collect();
+        let insts: Vec<Value> = node.insts().keys().copied().collect();
+        for &inst in insts.iter().rev() {
+            let kind = dfg.value(inst).kind();
+            if let Some(def) = node_of(inst) {
+                live.remove(&def);
+                for &other in &live {
+                    graph.add_edge(def, other);
+                }
+                if graph.edges > MAX_INTERFERENCE_EDGES {
+                    return None;
+                }
+            }
+            let mut record_moves = |target: BasicBlock, args: &[Value]| {
+                for (&arg, &param) in args.iter().zip(dfg.bb(target).params()) {
+                    if let Some(arg) = node_of(arg) {
+                        moves.push((arg, index[&param]));
+                    }
+                }
+            };
+            match kind {
+                ValueKind::Call(_) => call_live.push((inst, live.iter().copied().collect())),
+                ValueKind::Jump(jump) => record_moves(jump.target(), jump.args()),
+                ValueKind::Branch(branch) => {
+                    record_moves(branch.true_bb(), branch.true_args());
+                    record_moves(branch.false_bb(), branch.false_args());
+                },
+                _ => {}
+            }
+            live.extend(kind.value_uses().filter_map(node_of));
+        }
+        // 基本块参数（入口块还有函数参数）在基本块开头同时定义，即使没有被使用也会被跳转时的赋值写入
+        let mut params: Vec<usize> = dfg.bb(bb).params().iter().map(|p| index[p]).collect();
+        if Some(bb) == entry {
+            params.extend(f.params().iter().map(|p| index[p]));
+        }
+        for &param in &params {
+            live.remove(&param);
+        }
+        for (i, &param) in params.iter().enumerate() {
+            for &other in live.iter().chain(&params[i + 1..]) {
+                graph.add_edge(param, other);
+            }
+        }
+    }
+
+    // 预先着色的函数参数，通过栈传递的参数不参与着色
+    // 颜色是寄存器在 pool 中的下标
+    let mut color: Vec<Option<usize>> = vec![None; n];
+    let mut precolored = vec![false; n];
+    let mut removed = vec![false; n];
+    for (i, param) in f.params().iter().enumerate() {
+        let node = index[param];
+        match pool.iter().position(|r| *r == format!("a{}", i)) {
+            Some(register) => {
+                color[node] = Some(register);
+                precolored[node] = true;
+            },
+            None => removed[node] = true
+        }
+    }
+
+    // 保守合并，直到没有可以合并的赋值
+    let k = pool.len();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &(arg, param) in &moves {
+            let (a, b) = (graph.find(arg), graph.find(param));
+            if a == b || graph.adj[a].contains(&b) || precolored[a] || precolored[b] || removed[a] || removed[b] {
+                continue;
+            }
+            // Briggs：合并后的结点中度数不小于 k 的邻居少于 k 个，合并不会让图变得不可着色
+            let significant = graph.adj[a].union(&graph.adj[b])
+                .filter(|&&m| graph.adj[m].len() >= k)
+                .count();
+            if significant < k {
+                graph.merge(a, b);
+                changed = true;
+            }
+        }
+    }
+
+    // 每个代表结点的溢出代价为所有被合并的值的代价之和
+    let mut cost = vec![0u64; n];
+    for (i, value) in liveness.values.iter().enumerate() {
+        let root = graph.find(i);
+        cost[root] = cost[root].saturating_add(liveness.intervals[value].cost);
+    }
+    for (i, r) in removed.iter_mut().enumerate() {
+        *r |= graph.alias[i] != i;
+    }
+
+    // 简化：low 中是度数小于 k 的结点，度数只会减小，所以每个结点最多进入一次
+    let mut degree: Vec<usize> = (0..n).map(|v| graph.adj[v].iter().filter(|&&m| !removed[m]).count()).collect();
+    let mut remaining = (0..n).filter(|&v| !removed[v] && !precolored[v]).count();
+    let mut low: Vec<usize> = (0..n).rev().filter(|&v| !removed[v] && !precolored[v] && degree[v] < k).collect();
+    let mut stack = Vec::new();
+    while remaining > 0 {
+        let node = match low.pop() {
+            Some(v) => v,
+            // 没有度数小于 k 的结点，乐观地移除溢出代价与度数之比最小的结点
+            None => (0..n)
+                .filter(|&v| !removed[v] && !precolored[v])
+                .min_by_key(|&v| (cost[v] as u128 * 1_000_000 / degree[v] as u128, v))
+                .unwrap()
+        };
+        removed[node] = true;
+        remaining -= 1;
+        for &neighbor in &graph.adj[node] {
+            degree[neighbor] -= 1;
+            if degree[neighbor] == k - 1 && !removed[neighbor] && !precolored[neighbor] {
+                low.push(neighbor);
+            }
+        }
+        stack.push(node);
+    }
+
+    // 选择
+    let mut crosses_call = vec![false; n];
+    for (_, live) in &call_live {
+        for &v in live {
+            crosses_call[graph.find(v)] = true;
+        }
+    }
+    while let Some(node) = stack.pop() {
+        let mut used = vec![false; k];
+        for &neighbor in &graph.adj[node] {
+            if let Some(c) = color[neighbor] {
+                used[c] = true;
+            }
+        }
+        let mut free = (0..k).filter(|&c| !used[c]);
+        color[node] = free.clone().find(|&c| is_callee_saved(pool[c]) == crosses_call[node]).or_else(|| free.next());
+    }
+
+    let registers: HashMap<Value, &'static str> = liveness.values.iter()
+        .enumerate()
+        .filter_map(|(i, &v)| color[graph.find(i)].map(|c| (v, pool[c])))
+        .collect();
+    let call_saves = call_live.into_iter()
+        .map(|(call, live)| {
+            let mut saves: Vec<&'static str> = live.iter()
+                .filter_map(|&v| registers.get(&liveness.values[v]).copied())
+                .collect();
+            saves.sort();
+            saves.dedup();
+            (call, saves)
+        })
+        .collect();
+
+    Some(Allocation { registers, call_saves })
+}
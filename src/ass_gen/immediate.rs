@@ -0,0 +1,107 @@
+/// 此文件存放一个操作数是小常量时的二元运算，直接使用 RISC-V 的立即数指令，不再用 li 加载常量
+/// - add/sub/and/or/xor：addi、andi、ori、xori，减去常量改写为加上相反数
+/// - 移位：slli、srli、srai，和寄存器版本一样只取移位量的低 5 位
+/// - 比较：slti 加上 seqz 取反；x > c 和 x <= c 改写为和 c + 1 比较；相等比较先用 xori 求差异
+///
+/// 常量超出 12 位有符号立即数的范围时不适用，调用方仍然用 li 把常量放入寄存器
+use koopa::ir::{dfg::DataFlowGraph, values::Binary, BinaryOp, Value, ValueKind};
+
+use super::{AssGen, ErrorReason, Symbol};
+
+/// 立即数指令之后，对结果的处理
+enum Then {
+    /// 立即数指令的结果就是最终结果
+    Keep,
+    /// 结果为 0 时得到 1，否则得到 0
+    Seqz,
+    /// 结果不为 0 时得到 1，否则得到 0
+    Snez
+}
+
+/// 判断一个数能否作为 12 位有符号立即数
+fn fits_imm(c: i64) -> bool {
+    (-2048..=2047).contains(&c)
+}
+
+/// 交换左右操作数之后等价的运算，不能交换时返回 None
+fn swapped(op: BinaryOp) -> Option<BinaryOp> {
+    match op {
+        BinaryOp::Add | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor
+            | BinaryOp::Eq | BinaryOp::NotEq => Some(op),
+        BinaryOp::Lt => Some(BinaryOp::Gt),
+        BinaryOp::Gt => Some(BinaryOp::Lt),
+        BinaryOp::Le => Some(BinaryOp::Ge),
+        BinaryOp::Ge => Some(BinaryOp::Le),
+        _ => None
+    }
+}
+
+/// 计算 x op c 使用的立即数指令、立即数以及之后的处理
+/// 不存在对应的立即数形式时返回 None
+fn immediate_form(op: BinaryOp, c: i32) -> Option<(&'static str, i32, Then)> {
+    let c = c as i64;
+    let (name, imm, then) = match op {
+        BinaryOp::Add => ("addi", c, Then::Keep),
+        BinaryOp::Sub => ("addi", -c, Then::Keep),
+        BinaryOp::And => ("andi", c, Then::Keep),
+        BinaryOp::Or => ("ori", c, Then::Keep),
+        BinaryOp::Xor => ("xori", c, Then::Keep),
+        BinaryOp::Shl => ("slli", c & 31, Then::Keep),
+        BinaryOp::Shr => ("srli", c & 31, Then::Keep),
+        BinaryOp::Sar => ("srai", c & 31, Then::Keep),
+        BinaryOp::Eq => ("xori", c, Then::Seqz),
+        BinaryOp::NotEq => ("xori", c, Then::Snez),
+        BinaryOp::Lt => ("slti", c, Then::Keep),
+        BinaryOp::Ge => ("slti", c, Then::Seqz),
+        // x <= c 即 x < c + 1，x > c 即 !(x < c + 1)
+        BinaryOp::Le => ("slti", c + 1, Then::Keep),
+        BinaryOp::Gt => ("slti", c + 1, Then::Seqz),
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => return None
+    };
+    fits_imm(imm).then_some((name, imm as i32, then))
+}
+
+impl<'p> AssGen<'p> {
+    /// 尝试用立即数指令生成一条二元运算
+    /// 返回 false 表示不适用，调用方需要按普通的二元运算处理
+    pub(super) fn generate_immediate_binary(&mut self, dfg: &DataFlowGraph, inst: Value, binary: &Binary) -> Result<bool, ErrorReason> {
+        let constant = |v: Value| match dfg.value(v).kind() {
+            ValueKind::Integer(i) => Some(i.value()),
+            _ => None
+        };
+        // 常量统一放到右边，两边都是常量时左边用 li 加载
+        let (x, op, c) = match (constant(binary.lhs()), constant(binary.rhs())) {
+            (_, Some(c)) => (binary.lhs(), binary.op(), c),
+            (Some(c), None) => match swapped(binary.op()) {
+                Some(op) => (binary.rhs(), op, c),
+                None => return Ok(false)
+            },
+            (None, None) => return Ok(false)
+        };
+        let Some((name, imm, then)) = immediate_form(op, c) else {
+            return Ok(false);
+        };
+
+        let result_symbol = self.find_or_allocate_symbol(&inst);
+        let (x_register, x_temp) = self.load_operand(dfg, x)?;
+        let result_register = match &result_symbol {
+            Symbol::Register(r) => r.clone(),
+            Symbol::Stack(_) => self.get_reserved_register_without_load()?
+        };
+        self.add_inst_to_function(self.imm_inst_str(name, &result_register, &x_register, imm));
+        match then {
+            Then::Keep => {},
+            Then::Seqz => self.add_inst_to_function(self.eq0_inst_str(&result_register)),
+            Then::Snez => self.add_inst_to_function(self.neq0_inst_str(&result_register))
+        }
+
+        if let Symbol::Stack(s) = result_symbol {
+            self.add_inst_to_function(self.store_inst_str(&result_register, s));
+            self.remove_reserved_register(&result_register);
+        }
+        if x_temp {
+            self.remove_reserved_register(&x_register);
+        }
+        Ok(true)
+    }
+}
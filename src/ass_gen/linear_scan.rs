@@ -0,0 +1,98 @@
+/// 此文件存放线性扫描寄存器分配（Poletto & Sarkar）
+/// - 活跃区间按起点排序依次处理，处理前释放已经结束的区间占用的寄存器
+/// - 没有空闲寄存器时，在当前活跃的区间和新区间中选择溢出代价最小的溢出到栈上
+/// - 第 i 个（i < 8）函数参数优先分配到 ai 寄存器，这样不需要在函数开头移动参数
+/// - 跨越函数调用的区间优先使用被调用者保存寄存器，省去调用前后的保存和恢复；其他区间优先使用调用者保存寄存器
+use std::collections::HashMap;
+
+use koopa::ir::{FunctionData, Value, ValueKind};
+
+use super::is_callee_saved;
+use super::liveness::{Allocation, Interval, Liveness};
+
+/// 对一个函数做线性扫描分配，pool 为可分配的寄存器，按优先顺序排列
+pub(super) fn linear_scan(f: &FunctionData, liveness: &Liveness, pool: &[&'static str]) -> Allocation {
+    let mut order = liveness.values.clone();
+    // 稳定排序，起点相同的按定义顺序处理，保证结果确定
+    order.sort_by_key(|v| liveness.intervals[v].start);
+
+    let mut call_positions: Vec<usize> = liveness.positions.iter()
+        .filter(|&(inst, _)| matches!(f.dfg().value(*inst).kind(), ValueKind::Call(_)))
+        .map(|(_, &position)| position)
+        .collect();
+    call_positions.sort();
+
+    let mut registers: HashMap<Value, &'static str> = HashMap::new();
+    // 当前占用寄存器的区间
+    let mut active: Vec<Value> = Vec::new();
+    for value in order {
+        let interval = &liveness.intervals[&value];
+        active.retain(|v| liveness.intervals[v].end >= interval.start);
+
+        let param_index = match f.dfg().value(value).kind() {
+            ValueKind::FuncArgRef(arg) => Some(arg.index()),
+            _ => None
+        };
+        let free: Vec<&'static str> = pool.iter()
+            .copied()
+            .filter(|r| active.iter().all(|v| registers[v] != *r))
+            .collect();
+        let register = match param_index {
+            // 通过栈传递的参数直接留在栈上
+            Some(i) if i >= 8 => None,
+            // 寄存器参数只能留在原本的寄存器中，否则溢出
+            Some(i) => {
+                let preferred = format!("a{}", i);
+                free.iter().copied().find(|r| *r == preferred)
+            },
+            None => match free.iter().find(|r| is_callee_saved(r) == crosses_call(&call_positions, interval)).or(free.first()) {
+                Some(&r) => Some(r),
+                None => {
+                    // 选择溢出代价最小的区间，代价相同时溢出结束得最晚的
+                    let victim = active.iter()
+                        .copied()
+                        .min_by_key(|v| (liveness.intervals[v].cost, std::cmp::Reverse(liveness.intervals[v].end)))
+                        .filter(|v| {
+                            let victim = &liveness.intervals[v];
+                            (victim.cost, std::cmp::Reverse(victim.end)) < (interval.cost, std::cmp::Reverse(interval.end))
+                        });
+                    victim.map(|victim| {
+                        active.retain(|v| *v != victim);
+                        registers.remove(&victim).unwrap()
+                    })
+                }
+            }
+        };
+        if let Some(register) = register {
+            registers.insert(value, register);
+            active.push(value);
+        }
+    }
+
+    // 找出每条调用指令处跨越调用的寄存器
+    let mut call_saves = HashMap::new();
+    for (&inst, &position) in &liveness.positions {
+        if !matches!(f.dfg().value(inst).kind(), ValueKind::Call(_)) {
+            continue;
+        }
+        let mut saves: Vec<&'static str> = liveness.values.iter()
+            .filter(|v| **v != inst)
+            .filter(|v| {
+                let interval = &liveness.intervals[*v];
+                interval.start < position && interval.end > position
+            })
+            .filter_map(|v| registers.get(v).copied())
+            .collect();
+        saves.sort();
+        saves.dedup();
+        call_saves.insert(inst, saves);
+    }
+
+    Allocation { registers, call_saves }
+}
+
+/// 判断一个区间是否跨越了某条函数调用
+fn crosses_call(call_positions: &[usize], interval: &Interval) -> bool {
+    let first = call_positions.partition_point(|&p| p <= interval.start);
+    call_positions.get(first).is_some_and(|&p| p < interval.end)
+}
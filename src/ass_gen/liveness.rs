@@ -0,0 +1,158 @@
+/// 此文件存放寄存器分配使用的活跃变量分析
+/// - 指令编号：按布局顺序遍历基本块，每个基本块的开头（定义基本块参数的位置）占一个编号，之后每条指令各占一个编号
+/// - 活跃变量：在基本块粒度上迭代求解 live-in / live-out 直到不动点
+/// - 活跃区间：每个值的区间覆盖它的定义、所有使用，以及它活跃的基本块的范围（循环中的值因此覆盖整个循环）
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{dfg::DataFlowGraph, BasicBlock, FunctionData, Value, ValueKind};
+
+use crate::ir_opt::successors;
+
+/// 一个值的活跃区间，两端都是闭区间
+#[derive(Debug, Clone)]
+pub(super) struct Interval {
+    pub start: usize,
+    pub end: usize,
+    /// 溢出代价：定义和使用的次数，按所在循环的深度加权
+    pub cost: u64
+}
+
+/// 一个函数的活跃变量分析结果
+pub(super) struct Liveness {
+    /// 需要分配位置的值，按定义的先后排列
+    pub values: Vec<Value>,
+    /// 每个值的活跃区间
+    pub intervals: HashMap<Value, Interval>,
+    /// 每条指令的编号
+    pub positions: HashMap<Value, usize>,
+    /// 每个基本块出口处活跃的值
+    pub live_out: HashMap<BasicBlock, HashSet<Value>>
+}
+
+/// 寄存器分配的结果
+pub(super) struct Allocation {
+    /// 分配到寄存器的值，其余的值放在栈上
+    pub registers: HashMap<Value, &'static str>,
+    /// 每条函数调用前后需要保存和恢复的寄存器（跨越调用仍然活跃的值所在的寄存器）
+    pub call_saves: HashMap<Value, Vec<&'static str>>
+}
+
+/// 判断一个值是否需要寄存器或者栈空间来存放
+/// 常量在使用时现场生成；没有结果的指令不需要位置；alloc 需要一个位置作为变量本身
+pub(super) fn needs_location(dfg: &DataFlowGraph, value: Value) -> bool {
+    if value.is_global() {
+        return false;
+    }
+    let data = dfg.value(value);
+    match data.kind() {
+        ValueKind::Integer(_) | ValueKind::ZeroInit(_) | ValueKind::Undef(_) | ValueKind::Aggregate(_) => false,
+        _ => !data.ty().is_unit()
+    }
+}
+
+impl Liveness {
+    pub fn new(f: &FunctionData) -> Self {
+        let dfg = f.dfg();
+        let order: Vec<BasicBlock> = f.layout().bbs().keys().copied().collect();
+        let index: HashMap<BasicBlock, usize> = order.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+
+        // 指令编号，同时收集每个基本块中定义的值和向上暴露的使用
+        let mut values: Vec<Value> = f.params().to_vec();
+        let mut def_position: HashMap<Value, usize> = f.params().iter().map(|&p| (p, 0)).collect();
+        let mut use_positions: HashMap<Value, Vec<usize>> = HashMap::new();
+        let mut positions = HashMap::new();
+        let mut block_range = Vec::new();
+        let mut defs: Vec<HashSet<Value>> = Vec::new();
+        let mut uses: Vec<HashSet<Value>> = Vec::new();
+        let mut counter = 0;
+        for &bb in &order {
+            let start = counter;
+            counter += 1;
+            let mut def = HashSet::new();
+            let mut used = HashSet::new();
+            for &param in dfg.bb(bb).params() {
+                values.push(param);
+                def_position.insert(param, start);
+                def.insert(param);
+            }
+            for &inst in f.layout().bbs().node(&bb).unwrap().insts().keys() {
+                positions.insert(inst, counter);
+                for operand in dfg.value(inst).kind().value_uses() {
+                    if !needs_location(dfg, operand) {
+                        continue;
+                    }
+                    use_positions.entry(operand).or_default().push(counter);
+                    if !def.contains(&operand) {
+                        used.insert(operand);
+                    }
+                }
+                if needs_location(dfg, inst) {
+                    values.push(inst);
+                    def_position.insert(inst, counter);
+                    def.insert(inst);
+                }
+                counter += 1;
+            }
+            block_range.push((start, counter - 1));
+            defs.push(def);
+            uses.push(used);
+        }
+
+        // 循环深度的近似：布局中向前跳的边视为回边，回边覆盖的所有基本块深度加一
+        let succs: Vec<Vec<usize>> = order.iter().map(|&bb| successors(f, bb).iter().map(|s| index[s]).collect()).collect();
+        let mut depth = vec![0u32; order.len()];
+        for (i, targets) in succs.iter().enumerate() {
+            for &j in targets {
+                if j <= i {
+                    depth[j..=i].iter_mut().for_each(|d| *d += 1);
+                }
+            }
+        }
+
+        // 逆序迭代求解活跃变量
+        let mut live_in: Vec<HashSet<Value>> = vec![HashSet::new(); order.len()];
+        let mut live_out: Vec<HashSet<Value>> = vec![HashSet::new(); order.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in (0..order.len()).rev() {
+                let out: HashSet<Value> = succs[i].iter().flat_map(|&s| live_in[s].iter().copied()).collect();
+                let mut new_in: HashSet<Value> = out.difference(&defs[i]).copied().collect();
+                new_in.extend(uses[i].iter().copied());
+                if new_in != live_in[i] || out != live_out[i] {
+                    live_in[i] = new_in;
+                    live_out[i] = out;
+                    changed = true;
+                }
+            }
+        }
+
+        // 计算活跃区间
+        let block_of = |position: usize| block_range.partition_point(|&(_, end)| end < position).min(order.len() - 1);
+        let weight = |position: usize| 10u64.saturating_pow(depth[block_of(position)].min(6));
+        let mut intervals: HashMap<Value, Interval> = values.iter().map(|&v| {
+            let def = def_position[&v];
+            let mut interval = Interval { start: def, end: def, cost: weight(def) };
+            for &position in use_positions.get(&v).into_iter().flatten() {
+                interval.end = interval.end.max(position);
+                interval.cost = interval.cost.saturating_add(weight(position));
+            }
+            (v, interval)
+        }).collect();
+        for (i, &(start, end)) in block_range.iter().enumerate() {
+            for v in &live_in[i] {
+                let interval = intervals.get_mut(v).unwrap();
+                interval.start = interval.start.min(start);
+                interval.end = interval.end.max(start);
+            }
+            for v in &live_out[i] {
+                let interval = intervals.get_mut(v).unwrap();
+                interval.start = interval.start.min(start);
+                interval.end = interval.end.max(end);
+            }
+        }
+
+        let live_out = order.into_iter().zip(live_out).collect();
+        Self { values, intervals, positions, live_out }
+    }
+}
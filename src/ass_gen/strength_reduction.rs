@@ -0,0 +1,214 @@
+/// 此文件存放乘法、除法、取余运算在一个操作数是常量时的强度削弱
+/// - 乘以 2 的幂以及 2^k±1 形式的常量：改写为移位和加减法
+/// - 有符号除以 2 的幂：带偏置的算术右移（保证向零取整）
+/// - 除以其他常量：魔数乘法（mulh）加移位，参见 Hacker's Delight 第 10 章
+/// - 取余：x - (x / c) * c，其中除法部分同样使用上面的方法
+use koopa::ir::{dfg::DataFlowGraph, values::Binary, BinaryOp, Value, ValueKind};
+
+use super::{AssGen, ErrorReason, Symbol};
+
+/// 计算有符号 32 位除法的魔数和移位量
+/// 要求 |d| >= 2 且 |d| 不是 2 的幂
+fn signed_magic(d: i32) -> (i32, u32) {
+    let two31: u32 = 0x8000_0000;
+    let ad = d.unsigned_abs();
+    let t = two31.wrapping_add((d as u32) >> 31);
+    let anc = t - 1 - t % ad;
+    let mut p = 31;
+    let mut q1 = two31 / anc;
+    let mut r1 = two31 - q1 * anc;
+    let mut q2 = two31 / ad;
+    let mut r2 = two31 - q2 * ad;
+    loop {
+        p += 1;
+        q1 = q1.wrapping_mul(2);
+        r1 = r1.wrapping_mul(2);
+        if r1 >= anc {
+            q1 = q1.wrapping_add(1);
+            r1 = r1.wrapping_sub(anc);
+        }
+        q2 = q2.wrapping_mul(2);
+        r2 = r2.wrapping_mul(2);
+        if r2 >= ad {
+            q2 = q2.wrapping_add(1);
+            r2 = r2.wrapping_sub(ad);
+        }
+        let delta = ad - r2;
+        if !(q1 < delta || (q1 == delta && r1 == 0)) {
+            break;
+        }
+    }
+    let magic = q2.wrapping_add(1) as i32;
+    (if d < 0 { magic.wrapping_neg() } else { magic }, p - 32)
+}
+
+/// 如果 u 是 2 的幂，返回其指数
+fn log2_exact(u: u32) -> Option<u32> {
+    if u.is_power_of_two() { Some(u.trailing_zeros()) } else { None }
+}
+
+/// 判断乘以常量 c 时能否削弱为移位和加减法
+fn mul_reducible(c: i32) -> bool {
+    let u = c.unsigned_abs();
+    u <= 1 || u.is_power_of_two()
+        || (u - 1).is_power_of_two()
+        || u.checked_add(1).is_some_and(|v| v.is_power_of_two())
+}
+
+impl<'p> AssGen<'p> {
+    /// 尝试为一条 mul/div/mod 指令生成削弱后的指令序列
+    /// 返回 false 表示不适用，调用方需要按普通的二元运算处理
+    pub(super) fn generate_reduced_binary(&mut self, dfg: &DataFlowGraph, inst: Value, binary: &Binary) -> Result<bool, ErrorReason> {
+        let constant = |v: Value| match dfg.value(v).kind() {
+            ValueKind::Integer(i) => Some(i.value()),
+            _ => None
+        };
+        let (x, c) = match (binary.op(), constant(binary.lhs()), constant(binary.rhs())) {
+            (BinaryOp::Mul, None, Some(c)) if mul_reducible(c) => (binary.lhs(), c),
+            (BinaryOp::Mul, Some(c), None) if mul_reducible(c) => (binary.rhs(), c),
+            // 除数为 0 的行为留给运行时
+            (BinaryOp::Div | BinaryOp::Mod, None, Some(c)) if c != 0 => (binary.lhs(), c),
+            _ => return Ok(false)
+        };
+
+        let result_symbol = self.find_or_allocate_symbol(&inst);
+        let (x_register, x_temp) = self.load_operand(dfg, x)?;
+        let result_register = match &result_symbol {
+            Symbol::Register(r) => r.clone(),
+            Symbol::Stack(_) => self.get_reserved_register_without_load()?
+        };
+        let temp = self.get_reserved_register_without_load()?;
+
+        let code = match binary.op() {
+            BinaryOp::Mul => self.mul_by_constant_str(&result_register, &x_register, c, &temp),
+            BinaryOp::Div => self.div_by_constant_str(&result_register, &x_register, c, &temp),
+            _ => {
+                // 取余的一般情况需要第二个临时寄存器
+                // 结果寄存器和 x 不同时可以直接借用结果寄存器，否则再申请一个
+                if result_register != x_register {
+                    self.mod_by_constant_str(&result_register, &x_register, c, &temp, &result_register.clone())
+                } else {
+                    let scratch = self.get_reserved_register_without_load()?;
+                    let code = self.mod_by_constant_str(&result_register, &x_register, c, &temp, &scratch);
+                    self.remove_reserved_register(&scratch);
+                    code
+                }
+            }
+        };
+        self.add_inst_to_function(code);
+
+        self.remove_reserved_register(&temp);
+        if let Symbol::Stack(s) = result_symbol {
+            self.add_inst_to_function(self.store_inst_str(&result_register, s));
+            self.remove_reserved_register(&result_register);
+        }
+        if x_temp {
+            self.remove_reserved_register(&x_register);
+        }
+        Ok(true)
+    }
+
+    /// 生成 result = x * c 的移位/加减序列，调用前需保证 mul_reducible(c)
+    /// result 可以和 x 是同一个寄存器；temp 为临时寄存器
+    fn mul_by_constant_str(&self, result: &str, x: &str, c: i32, temp: &str) -> String {
+        let u = c.unsigned_abs();
+        let mut s = String::new();
+        if u == 0 {
+            return self.init_register_str(result, 0);
+        } else if u == 1 {
+            s.push_str(&self.move_register_inst_str(result, x));
+        } else if let Some(k) = log2_exact(u) {
+            s.push_str(&self.shl_imm_inst_str(result, x, k));
+        } else if let Some(k) = log2_exact(u - 1) {
+            // x * (2^k + 1) = (x << k) + x
+            s.push_str(&self.shl_imm_inst_str(temp, x, k));
+            s.push_str(&self.add_inst_str(result, temp, x));
+        } else {
+            // x * (2^k - 1) = (x << k) - x
+            let k = log2_exact(u + 1).unwrap();
+            s.push_str(&self.shl_imm_inst_str(temp, x, k));
+            s.push_str(&self.sub_inst_str(result, temp, x));
+        }
+        // 负数常量再取相反数；i32::MIN 左移 31 位本身就是正确结果
+        if c < 0 && c != i32::MIN {
+            s.push_str(&self.neg_inst_str(result, result));
+        }
+        s
+    }
+
+    /// 生成 temp = x + (x < 0 ? 2^k - 1 : 0) 的序列
+    /// 之后算术右移 k 位即可得到向零取整的 x / 2^k
+    fn bias_for_pow2_str(&self, x: &str, k: u32, temp: &str) -> String {
+        let mut s = String::new();
+        if k == 1 {
+            s.push_str(&self.shr_imm_inst_str(temp, x, 31));
+        } else {
+            s.push_str(&self.sar_imm_inst_str(temp, x, 31));
+            s.push_str(&self.shr_imm_inst_str(temp, temp, 32 - k));
+        }
+        s.push_str(&self.add_inst_str(temp, x, temp));
+        s
+    }
+
+    /// 生成 dest = x / c（向零取整）的魔数乘法序列，会覆盖 temp 和 scratch
+    /// 要求 |c| 不是 2 的幂。scratch 只在最后一步才写入，此时已经不再读取 x，
+    /// 因此 scratch 可以和 dest 相同，也可以和 x 相同（只要调用方之后不再需要 x）
+    fn magic_div_str(&self, dest: &str, x: &str, c: i32, temp: &str, scratch: &str) -> String {
+        let (magic, shift) = signed_magic(c);
+        let mut s = String::new();
+        s.push_str(&self.init_register_str(temp, magic));
+        s.push_str(&self.mulh_inst_str(temp, x, temp));
+        if c > 0 && magic < 0 {
+            s.push_str(&self.add_inst_str(temp, temp, x));
+        } else if c < 0 && magic > 0 {
+            s.push_str(&self.sub_inst_str(temp, temp, x));
+        }
+        if shift > 0 {
+            s.push_str(&self.sar_imm_inst_str(temp, temp, shift));
+        }
+        // 商为负数时加一，修正为向零取整
+        s.push_str(&self.shr_imm_inst_str(scratch, temp, 31));
+        s.push_str(&self.add_inst_str(dest, temp, scratch));
+        s
+    }
+
+    /// 生成 result = x / c 的序列
+    fn div_by_constant_str(&self, result: &str, x: &str, c: i32, temp: &str) -> String {
+        let u = c.unsigned_abs();
+        let mut s = String::new();
+        if u == 1 {
+            s.push_str(&self.move_register_inst_str(result, x));
+        } else if let Some(k) = log2_exact(u) {
+            s.push_str(&self.bias_for_pow2_str(x, k, temp));
+            s.push_str(&self.sar_imm_inst_str(result, temp, k));
+        } else {
+            return self.magic_div_str(result, x, c, temp, result);
+        }
+        if c < 0 {
+            s.push_str(&self.neg_inst_str(result, result));
+        }
+        s
+    }
+
+    /// 生成 result = x % c 的序列
+    /// 余数的符号只和被除数有关，所以只看 |c|
+    fn mod_by_constant_str(&self, result: &str, x: &str, c: i32, temp: &str, scratch: &str) -> String {
+        let u = c.unsigned_abs();
+        let mut s = String::new();
+        if u == 1 {
+            return self.init_register_str(result, 0);
+        } else if let Some(k) = log2_exact(u) {
+            // x - ((x + bias) >> k << k)
+            s.push_str(&self.bias_for_pow2_str(x, k, temp));
+            s.push_str(&self.sar_imm_inst_str(temp, temp, k));
+            s.push_str(&self.shl_imm_inst_str(temp, temp, k));
+        } else {
+            // x - (x / c) * c
+            s.push_str(&self.magic_div_str(temp, x, c, temp, scratch));
+            s.push_str(&self.init_register_str(scratch, c));
+            s.push_str(&self.mul_inst_str(temp, temp, scratch));
+        }
+        s.push_str(&self.sub_inst_str(result, x, temp));
+        s
+    }
+}
@@ -0,0 +1,336 @@
+/// 此文件存放命令行参数的解析
+/// - 模式、选项和输入路径可以按任意顺序出现，`--` 之后的参数都视为输入路径
+/// - 输入路径或者输出路径为 `-` 时使用标准输入或者标准输出
+/// - 参数有误时返回 UsageError，由 main 打印并以 EXIT_USAGE 退出
+use std::fmt;
+
+use compiler::ass_gen::RegAlloc;
+use compiler::Options;
+
+/// 成功；执行模式下是 main 的返回值
+pub const EXIT_SUCCESS: i32 = 0;
+/// 编译错误、执行时的错误、读写文件失败或者测试失败
+pub const EXIT_FAILURE: i32 = 1;
+/// 命令行参数有误
+pub const EXIT_USAGE: i32 = 2;
+/// 编译器内部错误（panic）
+pub const EXIT_INTERNAL: i32 = 3;
+
+pub const HELP: &str = "\
+Usage: compiler [MODE] <INPUT> [OPTIONS]
+
+Compiles a SysY program (or Koopa IR) to Koopa IR or RISC-V assembly.
+The mode, the options and INPUT may appear in any order; arguments after `--` are
+always taken as INPUT. An INPUT or output path of `-` means stdin or stdout.
+
+Modes:
+  -koopa                       Emit Koopa IR (same as --emit=koopa)
+  -riscv                       Emit RISC-V assembly (same as --emit=asm)
+  -dump-ast                    Print the syntax tree (same as --emit=ast)
+  -run-koopa                   Interpret the Koopa IR; main's return value is the exit code
+  -run-riscv                   Run the RISC-V assembly in the built-in simulator;
+                               main's return value is the exit code
+  -diff-test                   Run the differential tests in the directory INPUT
+  -sysy-fuzz                   Check INPUT random programs against a reference evaluator
+
+Output:
+  --emit=<KIND>[=PATH],...     Outputs to produce; may be repeated. KIND is one of:
+                                 ast       the syntax tree as an indented tree, with spans
+                                           as line:column
+                                 ast-json  the syntax tree as JSON
+                                 koopa     Koopa IR
+                                 asm       RISC-V assembly
+                               An output without its own PATH is written to the -o path
+  -o <PATH>                    Output path (default: stdout). With several outputs, each
+                               one is written to PATH with the extension .ast, .ast.json,
+                               .koopa or .S
+
+Input:
+  --input-format=sysy|koopa    Language of INPUT (default: koopa for *.koopa, sysy otherwise)
+
+Optimization and code generation:
+  -O0, -O1, -O2                Optimization level (default: -O1)
+  -finline-limit=<N>           Inline functions of at most N instructions; 0 disables
+                               inlining (default: 40)
+  --regalloc=greedy|linear|graph
+                               Register allocator (default: graph at -O2, linear otherwise)
+  -fomit-frame-pointer         Use s0 as an ordinary register (default)
+  -fno-omit-frame-pointer      Keep s0 as the frame pointer
+
+Testing:
+  --seed=<N>                   First seed used by -sysy-fuzz (default: 0)
+
+Other:
+  -h, --help                   Print this help
+  -V, --version                Print the version
+
+Exit status:
+  0  success (in run modes: the return value of main)
+  1  compile errors, runtime errors in run modes, I/O errors or failed tests
+  2  invalid command-line arguments
+  3  internal compiler error
+";
+
+/// 输入文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// SysY 源程序，经过 IrGen 生成 Koopa IR
+    Sysy,
+    /// Koopa IR 文本，直接交给优化和后端
+    Koopa
+}
+
+/// 编译器的工作模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// 生成 --emit 指定的输出
+    Compile,
+    /// 直接解释执行 Koopa IR，main 的返回值作为退出码
+    RunKoopa,
+    /// 生成 RISC-V 汇编并在内置的模拟器中执行，main 的返回值作为退出码
+    RunRiscv,
+    /// 对目录下的测试用例做差分测试，比较解释器、模拟器和期望的输出
+    DiffTest,
+    /// 生成随机程序，检查编译结果和参考求值一致
+    SysyFuzz
+}
+
+/// --emit 可以生成的输出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitKind {
+    Ast,
+    AstJson,
+    Koopa,
+    Asm
+}
+
+impl EmitKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ast" => Some(EmitKind::Ast),
+            "ast-json" => Some(EmitKind::AstJson),
+            "koopa" => Some(EmitKind::Koopa),
+            "asm" => Some(EmitKind::Asm),
+            _ => None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EmitKind::Ast => "ast",
+            EmitKind::AstJson => "ast-json",
+            EmitKind::Koopa => "koopa",
+            EmitKind::Asm => "asm"
+        }
+    }
+
+    /// 语法树的输出不需要生成 IR
+    pub fn is_ast(self) -> bool {
+        matches!(self, EmitKind::Ast | EmitKind::AstJson)
+    }
+
+    /// 同时生成多个输出时，-o 路径换成的扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            EmitKind::Ast => "ast",
+            EmitKind::AstJson => "ast.json",
+            EmitKind::Koopa => "koopa",
+            EmitKind::Asm => "S"
+        }
+    }
+}
+
+/// 一个要生成的输出，path 为 None 时写到 -o 指定的位置
+#[derive(Debug, Clone)]
+pub struct Emit {
+    pub kind: EmitKind,
+    pub path: Option<String>
+}
+
+/// 解析好的命令行参数
+pub struct Args {
+    pub mode: Mode,
+    pub input: String,
+    pub input_format: InputFormat,
+    pub output: Option<String>,
+    pub emits: Vec<Emit>,
+    pub options: Options,
+    /// 随机程序测试的第一个种子
+    pub fuzz_seed: u64
+}
+
+impl Args {
+    /// 输出写入的路径，None 表示标准输出
+    pub fn output_path(&self, emit: &Emit) -> Option<String> {
+        let path = match &emit.path {
+            Some(path) => path.clone(),
+            None => match &self.output {
+                Some(output) if output != "-" && self.emits.len() > 1 => {
+                    std::path::Path::new(output).with_extension(emit.kind.extension()).to_string_lossy().into_owned()
+                },
+                Some(output) => output.clone(),
+                None => return None
+            }
+        };
+        Some(path).filter(|path| path != "-")
+    }
+}
+
+/// 命令行要求做的事情
+pub enum Command {
+    Compile(Box<Args>),
+    Help,
+    Version
+}
+
+/// 命令行参数的错误
+#[derive(Debug)]
+pub struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn usage_error<T>(message: impl ToString) -> Result<T, UsageError> {
+    Err(UsageError(message.to_string()))
+}
+
+/// 解析命令行参数（不包括程序名）
+pub fn parse_args(args: &[String]) -> Result<Command, UsageError> {
+    let mut mode = None;
+    let mut input = None;
+    let mut input_format = None;
+    let mut output = None;
+    let mut emits: Vec<Emit> = Vec::new();
+    let mut options = Options::default();
+    // 默认使用线性扫描寄存器分配，-O2 时使用图着色，可以用 --regalloc 指定
+    let mut regalloc = None;
+    let mut fuzz_seed = 0;
+
+    let mut set_mode = |new_mode: Mode, arg: &str| {
+        if mode.is_some_and(|mode| mode != new_mode) {
+            return usage_error(format!("'{}' conflicts with an earlier mode", arg));
+        }
+        mode = Some(new_mode);
+        Ok(())
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.as_str();
+        match arg {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--" => {
+                for rest in args.by_ref() {
+                    set_input(&mut input, rest)?;
+                }
+            },
+            "-koopa" => emits.push(Emit { kind: EmitKind::Koopa, path: None }),
+            "-riscv" => emits.push(Emit { kind: EmitKind::Asm, path: None }),
+            "-dump-ast" => emits.push(Emit { kind: EmitKind::Ast, path: None }),
+            "-run-koopa" => set_mode(Mode::RunKoopa, arg)?,
+            "-run-riscv" => set_mode(Mode::RunRiscv, arg)?,
+            "-diff-test" => set_mode(Mode::DiffTest, arg)?,
+            "-sysy-fuzz" => set_mode(Mode::SysyFuzz, arg)?,
+            "-o" => match args.next() {
+                Some(path) if output.is_none() => output = Some(path.clone()),
+                Some(_) => return usage_error("'-o' is given more than once"),
+                None => return usage_error("'-o' requires a path")
+            },
+            "-fno-omit-frame-pointer" => options.asm.frame_pointer = true,
+            "-fomit-frame-pointer" => options.asm.frame_pointer = false,
+            "-" => set_input(&mut input, arg)?,
+            _ => {
+                if let Some(list) = arg.strip_prefix("--emit=") {
+                    for item in list.split(',') {
+                        let (name, path) = match item.split_once('=') {
+                            Some((name, path)) => (name, Some(path.to_string())),
+                            None => (item, None)
+                        };
+                        let Some(kind) = EmitKind::from_name(name) else {
+                            return usage_error(format!("unknown output kind '{}' in '{}' (expected ast, ast-json, koopa or asm)", name, arg));
+                        };
+                        emits.push(Emit { kind, path });
+                    }
+                } else if let Some(level) = arg.strip_prefix("-O") {
+                    options.opt.level = match level.parse() {
+                        Ok(level) if level <= 2 => level,
+                        _ => return usage_error(format!("invalid optimization level '{}' (expected -O0, -O1 or -O2)", arg))
+                    };
+                } else if let Some(limit) = arg.strip_prefix("-finline-limit=") {
+                    options.opt.inline_limit = parse_number(arg, limit)?;
+                } else if let Some(name) = arg.strip_prefix("--regalloc=") {
+                    regalloc = Some(match name {
+                        "greedy" => RegAlloc::Greedy,
+                        "linear" => RegAlloc::Linear,
+                        "graph" => RegAlloc::Graph,
+                        _ => return usage_error(format!("unknown register allocator '{}' (expected greedy, linear or graph)", name))
+                    });
+                } else if let Some(format) = arg.strip_prefix("--input-format=") {
+                    input_format = Some(match format {
+                        "sysy" => InputFormat::Sysy,
+                        "koopa" => InputFormat::Koopa,
+                        _ => return usage_error(format!("unknown input format '{}' (expected sysy or koopa)", format))
+                    });
+                } else if let Some(seed) = arg.strip_prefix("--seed=") {
+                    fuzz_seed = parse_number(arg, seed)?;
+                } else if arg.starts_with('-') {
+                    return usage_error(format!("unknown option '{}'", arg));
+                } else {
+                    set_input(&mut input, arg)?;
+                }
+            }
+        }
+    }
+
+    // -koopa、-riscv 和 --emit 都表示生成输出，不能和其他模式一起使用
+    let mode = match (mode, emits.is_empty()) {
+        (Some(mode), true) => mode,
+        (None, false) => Mode::Compile,
+        (Some(_), false) => return usage_error("outputs cannot be emitted together with a run or test mode"),
+        (None, true) => return usage_error("no mode given; use -koopa, -riscv, --emit=..., or one of the run and test modes")
+    };
+    for (index, emit) in emits.iter().enumerate() {
+        if emits[..index].iter().any(|other| other.kind == emit.kind) {
+            return usage_error(format!("output kind '{}' is requested more than once", emit.kind.name()));
+        }
+    }
+    if output.is_some() && mode != Mode::Compile {
+        return usage_error("'-o' can only be used when emitting outputs");
+    }
+    let Some(input) = input else {
+        return usage_error(match mode {
+            Mode::DiffTest => "missing test directory",
+            Mode::SysyFuzz => "missing the number of programs to check",
+            _ => "missing input file"
+        });
+    };
+    if mode == Mode::SysyFuzz {
+        parse_number::<u64>("-sysy-fuzz", &input)?;
+    }
+    // 默认根据扩展名判断输入格式，.koopa 文件视为 Koopa IR
+    let input_format = input_format.unwrap_or(if input.ends_with(".koopa") { InputFormat::Koopa } else { InputFormat::Sysy });
+    if input_format == InputFormat::Koopa && let Some(emit) = emits.iter().find(|emit| emit.kind.is_ast()) {
+        return usage_error(format!("'--emit={}' requires SysY input", emit.kind.name()));
+    }
+    options.asm.regalloc = regalloc.unwrap_or(if options.opt.level >= 2 { RegAlloc::Graph } else { RegAlloc::Linear });
+
+    Ok(Command::Compile(Box::new(Args { mode, input, input_format, output, emits, options, fuzz_seed })))
+}
+
+fn set_input(input: &mut Option<String>, arg: &str) -> Result<(), UsageError> {
+    match input {
+        Some(first) => usage_error(format!("unexpected argument '{}' (the input is already '{}')", arg, first)),
+        None => {
+            *input = Some(arg.to_string());
+            Ok(())
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, UsageError> {
+    value.parse().or_else(|_| usage_error(format!("invalid number '{}' in '{}'", value, arg)))
+}
@@ -0,0 +1,178 @@
+/// 此文件存放差分测试：在同一个进程中用解释器执行 Koopa IR、用模拟器执行生成的汇编，和期望的输出比较
+/// - 测试用例使用北大课程测试用例的格式：`x.c`（或 `x.sy`）是源程序，`x.in` 是可选的标准输入，
+///   `x.out` 是期望的标准输出，最后一行是 main 的返回值（退出码）
+/// - 解释器的结果不对时，再解释没有优化过的 IR，区分问题出在 IrGen 还是 IR 优化
+/// - 解释器的结果正确而模拟器的结果不对时，问题出在 AssGen
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use koopa::ir::Program;
+
+use crate::ass_gen::{AsmConfig, AssGen};
+use crate::ir_gen::IrGen;
+use crate::ir_interp::Interpreter;
+use crate::ir_opt::{optimize_program, OptConfig};
+use crate::riscv_sim;
+use crate::sysy::CompUnitParser;
+
+/// 产生错误结果的编译阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// 语法分析或者 IrGen 生成的 IR 有误
+    IrGen,
+    /// 没有优化的 IR 正确，优化之后出错
+    IrOpt,
+    /// IR 正确，生成的汇编有误
+    AssGen
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stage::IrGen => write!(f, "IrGen"),
+            Stage::IrOpt => write!(f, "IR optimization"),
+            Stage::AssGen => write!(f, "AssGen")
+        }
+    }
+}
+
+/// 一个测试用例
+struct TestCase {
+    name: String,
+    source: PathBuf,
+    input: Option<PathBuf>,
+    expected: PathBuf
+}
+
+/// 运行 dir 下的所有测试用例并打印结果，全部通过时返回 true
+/// 一个用例都没有找到时也算作失败，多半是给错了目录；dir 或者其中的目录无法读取时返回 I/O 错误
+pub fn run(dir: &Path, opt_config: &OptConfig, asm_config: &AsmConfig) -> std::io::Result<bool> {
+    let mut cases = Vec::new();
+    collect_cases(dir, dir, &mut cases)?;
+    if cases.is_empty() {
+        eprintln!("error: no differential test cases found in '{}'", dir.display());
+        return Ok(false);
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+
+    println!("running {} differential test cases", cases.len());
+    let mut failed = 0;
+    for case in &cases {
+        match run_case(case, opt_config, asm_config) {
+            Ok(()) => println!("case {} ... ok", case.name),
+            Err((stage, detail)) => {
+                println!("case {} ... FAILED in {}\n    {}", case.name, stage, detail.replace('\n', "\n    "));
+                failed += 1;
+            }
+        }
+    }
+    println!("{} passed; {} failed", cases.len() - failed, failed);
+    Ok(failed == 0)
+}
+
+/// 递归地找出所有带有 .out 文件的源程序
+fn collect_cases(root: &Path, dir: &Path, cases: &mut Vec<TestCase>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_cases(root, &path, cases)?;
+            continue;
+        }
+        let is_source = path.extension().is_some_and(|extension| extension == "c" || extension == "sy");
+        let expected = path.with_extension("out");
+        if is_source && expected.is_file() {
+            let input = Some(path.with_extension("in")).filter(|input| input.is_file());
+            let name = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().into_owned();
+            cases.push(TestCase { name, source: path, input, expected });
+        }
+    }
+    Ok(())
+}
+
+/// 运行一个测试用例，失败时返回出错的阶段和说明
+fn run_case(case: &TestCase, opt_config: &OptConfig, asm_config: &AsmConfig) -> Result<(), (Stage, String)> {
+    let read = |path: &Path| std::fs::read_to_string(path).map_err(|e| (Stage::IrGen, format!("cannot read {}: {}", path.display(), e)));
+    let source = read(&case.source)?;
+    let input = match &case.input {
+        Some(path) => read(path)?,
+        None => String::new()
+    };
+    let expected = read(&case.expected)?;
+
+    let mut program = generate_ir(&source).map_err(|e| (Stage::IrGen, e))?;
+    optimize_program(&mut program, opt_config);
+    let interpreted = interpret(&program, &input);
+    if !matches(&interpreted, &expected) {
+        // 没有优化过的 IR 结果正确时，是优化引入的问题
+        let unoptimized_correct = opt_config.level > 0
+            && generate_ir(&source).is_ok_and(|program| matches(&interpret(&program, &input), &expected));
+        let stage = if unoptimized_correct { Stage::IrOpt } else { Stage::IrGen };
+        return Err((stage, describe_mismatch("interpreting the Koopa IR", &interpreted, &expected)));
+    }
+
+    let asm = AssGen::new(&program, asm_config).generate_program()
+        .map_err(|e| (Stage::AssGen, e.to_string()))?;
+    let simulated = simulate(&asm, &input);
+    if !matches(&simulated, &expected) {
+        return Err((Stage::AssGen, describe_mismatch("simulating the RISC-V assembly", &simulated, &expected)));
+    }
+    Ok(())
+}
+
+/// 解析源程序并生成（没有优化的）Koopa IR
+fn generate_ir(source: &str) -> Result<Program, String> {
+    let ast = CompUnitParser::new().parse(source).map_err(|e| format!("syntax error: {}", e))?;
+    let mut generator = IrGen::new();
+    generator.generate_koopa_ir(ast).ok_or_else(|| {
+        let messages: Vec<String> = generator.get_problems().into_iter().map(|problem| problem.message).collect();
+        format!("compilation failed: {}", messages.join("; "))
+    })
+}
+
+/// 解释执行 IR，返回测试用例格式的输出，出错时返回错误信息
+fn interpret(program: &Program, input: &str) -> Result<String, String> {
+    let mut output = Vec::new();
+    let exit_code = Interpreter::new(program, input.as_bytes(), &mut output).run().map_err(|e| e.to_string())?;
+    Ok(format_output(&output, exit_code))
+}
+
+/// 在模拟器中执行汇编，返回测试用例格式的输出，出错时返回错误信息
+fn simulate(asm: &str, input: &str) -> Result<String, String> {
+    let mut output = Vec::new();
+    let exit_code = riscv_sim::run(asm, input.as_bytes(), &mut output).map_err(|e| e.to_string())?;
+    Ok(format_output(&output, exit_code))
+}
+
+/// 标准输出之后另起一行写退出码，和测试用例的 .out 文件格式相同
+fn format_output(stdout: &[u8], exit_code: i32) -> String {
+    let mut result = String::from_utf8_lossy(stdout).into_owned();
+    if !result.is_empty() && !result.ends_with('\n') {
+        result.push('\n');
+    }
+    // 退出码只保留低 8 位
+    result += &format!("{}\n", exit_code & 0xff);
+    result
+}
+
+/// 忽略末尾的空白比较输出
+fn matches(actual: &Result<String, String>, expected: &str) -> bool {
+    actual.as_ref().is_ok_and(|actual| actual.trim_end() == expected.trim_end())
+}
+
+/// 说明实际结果和期望的输出哪里不同
+fn describe_mismatch(action: &str, actual: &Result<String, String>, expected: &str) -> String {
+    let actual = match actual {
+        Ok(actual) => actual,
+        Err(e) => return format!("{} failed: {}", action, e)
+    };
+    let expected_lines: Vec<&str> = expected.trim_end().lines().collect();
+    let actual_lines: Vec<&str> = actual.trim_end().lines().collect();
+    let line = expected_lines.iter().zip(&actual_lines).take_while(|(e, a)| e == a).count();
+    format!(
+        "{} gave a different result at line {}:\nexpected: {}\nactual:   {}",
+        action,
+        line + 1,
+        expected_lines.get(line).unwrap_or(&"<end of output>"),
+        actual_lines.get(line).unwrap_or(&"<end of output>")
+    )
+}
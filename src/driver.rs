@@ -0,0 +1,261 @@
+/// 此文件存放编译器的驱动接口，命令行程序和其他使用者都通过它编译程序
+/// - Compiler 保存编译选项，为每个输入创建一个 Session
+/// - Session 依次完成解析（parse）、生成并优化 IR（lower）和生成目标代码（codegen）
+/// - 每一步的错误和警告作为 ProblemInfo 保存在 Session 中，不打印任何内容，由调用者决定如何汇报
+use koopa::back::KoopaGenerator;
+use koopa::front::ast::AstKind;
+use koopa::front::builder::Builder;
+use koopa::front::lexer::Lexer;
+use koopa::front::parser::Parser;
+use koopa::front::span::{Error as KoopaError, FileType, Span as KoopaSpan};
+use koopa::ir::Program;
+use lalrpop_util::lexer::Token;
+use lalrpop_util::ParseError;
+
+use crate::ass_gen::{AsmConfig, AssGen};
+use crate::error_report::{Label, ProblemInfo, ProblemLevel};
+use crate::function_ast::{CompUnit, Span, SysyParseError};
+use crate::ir_gen::IrGen;
+use crate::ir_opt::{optimize_program, OptConfig};
+use crate::sysy::CompUnitParser;
+
+/// 解析得到的语法树
+pub type Ast = CompUnit;
+
+/// 生成的目标代码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Koopa IR 文本
+    Koopa,
+    /// RISC-V 汇编
+    Riscv
+}
+
+/// 编译选项
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub opt: OptConfig,
+    pub asm: AsmConfig
+}
+
+/// 编译器，同一组选项可以编译多个输入
+#[derive(Debug, Clone, Default)]
+pub struct Compiler {
+    options: Options
+}
+
+impl Compiler {
+    pub fn new(options: Options) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// 为一个输入创建编译会话
+    pub fn session(&self) -> Session {
+        Session { options: self.options.clone(), diagnostics: Vec::new() }
+    }
+
+    /// 一次完成解析、生成 IR 和生成目标代码，返回目标代码（出错时为 None）和所有的错误和警告
+    pub fn compile(&self, source: &str, target: Target) -> (Option<String>, Vec<ProblemInfo>) {
+        let mut session = self.session();
+        let output = session.parse(source)
+            .and_then(|ast| session.lower(ast))
+            .and_then(|program| session.codegen(&program, target));
+        (output, session.take_diagnostics())
+    }
+}
+
+/// 一个输入的编译过程
+/// 每一步出错时返回 None，错误和警告都可以通过 diagnostics 取得
+pub struct Session {
+    options: Options,
+    diagnostics: Vec<ProblemInfo>
+}
+
+impl Session {
+    /// 解析 SysY 源程序
+    /// 源程序原样解析，不像命令行程序那样先去掉公共的缩进：缩进不影响语法，错误的位置总是相对于传入的 source
+    pub fn parse(&mut self, source: &str) -> Option<Ast> {
+        match CompUnitParser::new().parse(source) {
+            Ok(ast) => Some(ast),
+            Err(e) => {
+                self.diagnostics.push(parse_error_to_problem(source, e));
+                None
+            }
+        }
+    }
+
+    /// 生成 Koopa IR 并按照选项优化，语义错误和警告都会记录下来
+    pub fn lower(&mut self, ast: Ast) -> Option<Program> {
+        let mut generator = IrGen::new();
+        let result = generator.generate_koopa_ir(ast);
+        self.diagnostics.extend(generator.get_problems());
+        let mut program = result?;
+        optimize_program(&mut program, &self.options.opt);
+        Some(program)
+    }
+
+    /// 解析 Koopa IR 文本并按照选项优化，得到和 lower 相同的结果
+    pub fn lower_koopa(&mut self, text: &str) -> Option<Program> {
+        match parse_koopa(text) {
+            Ok(mut program) => {
+                optimize_program(&mut program, &self.options.opt);
+                Some(program)
+            },
+            Err(problem) => {
+                self.diagnostics.push(problem);
+                None
+            }
+        }
+    }
+
+    /// 生成目标代码
+    pub fn codegen(&mut self, program: &Program, target: Target) -> Option<String> {
+        match target {
+            Target::Koopa => {
+                let mut generator = KoopaGenerator::new(Vec::new());
+                generator.generate_on(program).unwrap();
+                Some(String::from_utf8(generator.writer()).unwrap())
+            },
+            Target::Riscv => match AssGen::new(program, &self.options.asm).generate_program() {
+                Ok(asm) => Some(asm),
+                Err(e) => {
+                    self.diagnostics.push(e.to_problem());
+                    None
+                }
+            }
+        }
+    }
+
+    /// 到目前为止的所有错误和警告
+    pub fn diagnostics(&self) -> &[ProblemInfo] {
+        &self.diagnostics
+    }
+
+    /// 取出到目前为止的所有错误和警告，之后的步骤重新开始记录
+    pub fn take_diagnostics(&mut self) -> Vec<ProblemInfo> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|problem| problem.level == ProblemLevel::Error)
+    }
+}
+
+/// 把语法分析器的错误转换为带有源码位置的 ProblemInfo
+fn parse_error_to_problem(source: &str, error: ParseError<usize, Token<'_>, SysyParseError>) -> ProblemInfo {
+    let expected_note = |expected: Vec<String>| {
+        (!expected.is_empty()).then(|| vec![format!("expected one of {}", expected.join(", "))])
+    };
+    match error {
+        ParseError::InvalidToken { location } => {
+            // 标出无法识别的那个字符
+            let width = source[location..].chars().next().map_or(0, char::len_utf8);
+            let span = Span { start: location, end: location + width };
+            ProblemInfo::error("invalid token", vec![Label::primary("this character cannot start a token", span)], None)
+        },
+        ParseError::UnrecognizedEof { location, expected } => {
+            let span = Span { start: location, end: location };
+            ProblemInfo::error("unexpected end of file", vec![Label::primary("the program ends here", span)], expected_note(expected))
+        },
+        ParseError::UnrecognizedToken { token: (start, token, end), expected } => {
+            let span = Span { start, end };
+            ProblemInfo::error(format!("unexpected token '{}'", token), vec![Label::primary("unexpected token", span)], expected_note(expected))
+        },
+        ParseError::ExtraToken { token: (start, token, end) } => {
+            let span = Span { start, end };
+            ProblemInfo::error(format!("extra token '{}'", token), vec![Label::primary("extra token", span)], None)
+        },
+        ParseError::User { error: error @ SysyParseError::IntegerLiteralTooLarge(_, span) } => {
+            ProblemInfo::error(error, vec![Label::primary("this literal does not fit in 32 bits", span)], None)
+        },
+        // 其余语法动作中的错误没有记录位置
+        ParseError::User { error } => ProblemInfo::error(error, vec![], None)
+    }
+}
+
+/// 用 koopa 的前端解析 Koopa IR 文本，遇到第一个错误时停止
+/// koopa 的前端（开启 no-front-logger 之后）不打印任何内容，但只有致命错误带有错误信息，
+/// 其余的错误只能通过错误计数发现，这时标出无法解析的语句或者出错的那个全局定义
+fn parse_koopa(text: &str) -> Result<Program, ProblemInfo> {
+    KoopaSpan::reset(FileType::Buffer);
+    let mut parser = Parser::new(Lexer::new(text.as_bytes())).map_err(|e| koopa_error_to_problem(text, e))?;
+    let mut builder = Builder::new();
+    loop {
+        let errors = KoopaSpan::error_num();
+        let ast = parser.parse_next().map_err(|e| koopa_error_to_problem(text, e))?;
+        // 语法错误恢复之后留下 Error 节点，它可能在函数体的语句中
+        if KoopaSpan::error_num() > errors {
+            let stmts = match &ast.kind {
+                AstKind::FunDef(def) => def.bbs.iter().flat_map(|bb| match &bb.kind {
+                    AstKind::Block(block) => block.stmts.as_slice(),
+                    _ => &[]
+                }).collect(),
+                _ => vec![]
+            };
+            let error = stmts.into_iter().find(|stmt| matches!(stmt.kind, AstKind::Error(_))).unwrap_or(&ast);
+            // 恢复时跳过的范围可能很长，只标出开始的位置
+            let labels = koopa_span(text, error.span).map(|Span { start, .. }| {
+                let span = Span { start, end: start + text[start..].chars().next().map_or(0, char::len_utf8) };
+                Label::primary("cannot parse from here", span)
+            });
+            return Err(ProblemInfo::error("invalid Koopa IR syntax", labels.into_iter().collect(), None));
+        }
+        let (kind, name) = match &ast.kind {
+            AstKind::End(_) => break,
+            AstKind::GlobalDef(def) => ("global variable", &def.name),
+            AstKind::FunDef(def) => ("function", &def.name),
+            AstKind::FunDecl(decl) => ("function declaration", &decl.name),
+            _ => unreachable!()
+        };
+        builder.build_on(&ast);
+        if KoopaSpan::error_num() > errors {
+            let labels = koopa_span(text, ast.span).map(|span| {
+                Label::primary(format!("this {} uses an undefined symbol or a value of the wrong type", kind), span)
+            });
+            return Err(ProblemInfo::error(format!("invalid Koopa IR in {} '{}'", kind, name), labels.into_iter().collect(), None));
+        }
+    }
+    Ok(builder.program())
+}
+
+/// 把 koopa 前端返回的错误转换为 ProblemInfo，错误信息的格式是 "<buffer>:行:列: 信息"
+fn koopa_error_to_problem(text: &str, error: KoopaError) -> ProblemInfo {
+    let (KoopaError::Normal(message) | KoopaError::Fatal(message)) = error;
+    let message = message.strip_prefix(&format!("{}:", FileType::Buffer)).unwrap_or(&message);
+    let mut parts = message.splitn(3, ':');
+    match (parts.next().map(str::parse), parts.next().map(str::parse), parts.next()) {
+        (Some(Ok(line)), Some(Ok(col)), Some(rest)) => {
+            let start = koopa_offset(text, line, col);
+            let span = Span { start, end: koopa_offset(text, line, col + 1) };
+            ProblemInfo::error(format!("invalid Koopa IR: {}", rest.trim()), vec![Label::primary("here", span)], None)
+        },
+        _ => ProblemInfo::error(format!("invalid Koopa IR: {}", message), vec![], None)
+    }
+}
+
+/// 把 koopa 的位置（行号和列号都从 1 开始）换算为该字符的字节偏移
+fn koopa_offset(text: &str, line: usize, col: usize) -> usize {
+    let line_start = text.split_inclusive('\n').take(line.saturating_sub(1)).map(str::len).sum::<usize>();
+    let line_text = text[line_start..].split('\n').next().unwrap_or("");
+    line_start + line_text.char_indices().nth(col.saturating_sub(1)).map_or(line_text.len(), |(i, _)| i)
+}
+
+/// koopa 的 Span 不公开起止位置，只能从 Debug 输出（"行:列-行:列"）中读出，
+/// 格式对不上时返回 None，由调用者报告不带位置的错误
+fn koopa_span(text: &str, span: KoopaSpan) -> Option<Span> {
+    let debug = format!("{:?}", span);
+    let parse_pos = |pos: &str| {
+        let (line, col) = pos.split_once(':')?;
+        Some((line.parse().ok()?, col.parse::<usize>().ok()?))
+    };
+    let (start, end) = debug.split_once('-')?;
+    let ((start_line, start_col), (end_line, end_col)) = (parse_pos(start)?, parse_pos(end)?);
+    // 结束位置是最后一个字符
+    let start = koopa_offset(text, start_line, start_col);
+    let end = koopa_offset(text, end_line, end_col + 1).max(start);
+    Some(Span { start, end })
+}
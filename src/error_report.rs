@@ -1,21 +1,25 @@
 use codespan_reporting::diagnostic::Diagnostic;
+use koopa::ir::{dfg::DataFlowGraph, BasicBlock, Value, ValueKind};
 
 use crate::function_ast::Span;
 
 /// 错误等级：错误/警告
 #[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProblemLevel {
     Error,
     Warning
 }
 
 /// 标签等级：首要和次要（红色/蓝色）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LabelLevel {
     Primary,
     Secondary
 }
 
 /// 存储错误 tag（描述）的结构体
+#[derive(Debug, Clone)]
 pub struct Label {
     pub level: LabelLevel,
     pub message: String,
@@ -24,6 +28,7 @@ pub struct Label {
 
 /// 一个存储单个编译错误信息的结构体
 #[allow(dead_code)]
+#[derive(Debug, Clone)]
 pub struct ProblemInfo {
     pub code: Option<String>,
     pub message: String, 
@@ -113,4 +118,49 @@ impl ProblemInfo {
         }
         diagnostic.with_labels(labels).with_notes(self.notes.iter().map(|one| unindent::unindent(one)).collect())
     }
-}
\ No newline at end of file
+}
+
+// 以下函数在后端和解释器的错误中描述 Koopa IR 里的位置
+
+/// 描述一条指令的位置和种类，例如 "instruction #2 in %entry (load)"
+pub fn describe_instruction(dfg: &DataFlowGraph, bb: BasicBlock, index: usize, inst: Value) -> String {
+    let kind = match dfg.value(inst).kind() {
+        ValueKind::Binary(b) => b.op().to_string(),
+        kind => kind_name(kind).to_string()
+    };
+    format!("instruction #{} in {} ({})", index, block_name(dfg, bb), kind)
+}
+
+/// 描述一个操作数：有名字时使用名字，否则使用它的种类
+pub fn describe_value(dfg: &DataFlowGraph, value: Value) -> String {
+    let data = dfg.value(value);
+    data.name().clone().unwrap_or_else(|| format!("({})", kind_name(data.kind())))
+}
+
+/// 值的种类在 Koopa IR 文本中的名字
+pub fn kind_name(kind: &ValueKind) -> &'static str {
+    match kind {
+        ValueKind::Integer(_) => "integer",
+        ValueKind::ZeroInit(_) => "zeroinit",
+        ValueKind::Undef(_) => "undef",
+        ValueKind::Aggregate(_) => "aggregate",
+        ValueKind::FuncArgRef(_) => "function argument",
+        ValueKind::BlockArgRef(_) => "block argument",
+        ValueKind::Alloc(_) => "alloc",
+        ValueKind::GlobalAlloc(_) => "global alloc",
+        ValueKind::Load(_) => "load",
+        ValueKind::Store(_) => "store",
+        ValueKind::GetPtr(_) => "getptr",
+        ValueKind::GetElemPtr(_) => "getelemptr",
+        ValueKind::Binary(_) => "binary",
+        ValueKind::Branch(_) => "br",
+        ValueKind::Jump(_) => "jump",
+        ValueKind::Call(_) => "call",
+        ValueKind::Return(_) => "ret"
+    }
+}
+
+/// 基本块的名字，没有名字时使用 "unnamed block"
+fn block_name(dfg: &DataFlowGraph, bb: BasicBlock) -> String {
+    dfg.bb(bb).name().clone().unwrap_or_else(|| String::from("unnamed block"))
+}
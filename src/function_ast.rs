@@ -1,26 +1,32 @@
+// 打印语法树，用来检查语法分析的结果
+pub mod dump;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SysyParseError {
     // 部分函数要求特定的返回类型（比如 main 函数），如果此函数实际不满足特定的返回类型，那么出现下方的错误
     // 参数：错误的函数名称-错误的返回类型-正确的返回类型。
-    InvalidReturnType(String, String, String)
+    InvalidReturnType(String, String, String),
+    // 整数字面量超出 32 位整数的范围，参数为字面量的原文和它的位置
+    IntegerLiteralTooLarge(String, Span)
 }
 
 
 impl std::fmt::Display for SysyParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::InvalidReturnType(func_name, wrong, correct) => write!(f, "Function '{}' must return '{}', found '{}'", func_name, correct, wrong)
+            Self::InvalidReturnType(func_name, wrong, correct) => write!(f, "Function '{}' must return '{}', found '{}'", func_name, correct, wrong),
+            Self::IntegerLiteralTooLarge(literal, _) => write!(f, "integer literal '{}' is too large", literal)
         }
     }
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CompUnit {
     pub func_def: FuncDef
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FuncDef {
     pub func_type: FuncType,
     pub ident: String,
@@ -34,137 +40,139 @@ pub enum FuncType {
     Void
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Span {
     pub start: usize,
     pub end: usize
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Decl {
     ConstDecl(ConstDecl),
     VarDecl(VarDecl)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConstDecl {
     pub b_type: BType,
     pub const_def: Vec<ConstDef>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BType {
     Int
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConstDef {
     pub ident: String,
     pub const_init_val: ConstInitVal,
     pub span: Span
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConstInitVal {
     pub const_exp: ConstExp
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VarDecl {
     pub b_type: BType,
     pub var_def: Vec<VarDef>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VarDef {
     pub ident: String,
     pub init_val: Option<InitVal>,
     pub span: Span
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InitVal {
     pub exp: Exp
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Block {
     pub block_items: Vec<BlockItem>
 }
 
-#[derive(Debug)]
+// 语句中直接保存各层表达式，比声明大得多；语法树只构造一次，不值得为此装箱
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
 pub enum BlockItem {
     Decl(Decl),
     Stmt(Stmt)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Stmt {
-    Exp(Exp),
-    LValExp(LVal, Exp)
+    Exp(Exp, Span),
+    LValExp(LVal, Exp, Span)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConstExp {
     pub exp: Exp
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Exp {
     pub l_or_exp: LOrExp
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum UnaryExp {
     PrimaryExp(PrimaryExp),
-    CompoundUnaryExp(UnaryOp, Box<UnaryExp>)
+    CompoundUnaryExp(UnaryOp, Box<UnaryExp>, Span)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LVal {
     Ident(String, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PrimaryExp {
     Exp(Box<Exp>),
-    Number(i32),
+    Number(i32, Span),
     LVal(LVal)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum UnaryOp {
     Plus,
     Minus,
     Not
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MulOp {
     Mul,
     Div,
     Mod
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MulExp {
     UnaryExp(UnaryExp),
-    CompoundMulExp(Box<MulExp>, UnaryExp, MulOp)
+    CompoundMulExp(Box<MulExp>, UnaryExp, MulOp, Span)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AddOp {
     Plus,
     Minus
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AddExp {
     MulExp(MulExp),
-    CompoundAddExp(Box<AddExp>, MulExp, AddOp)
+    CompoundAddExp(Box<AddExp>, MulExp, AddOp, Span)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RelOp {
     // 小于
     Lt,
@@ -176,34 +184,34 @@ pub enum RelOp {
     Ge
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RelExp {
     AddExp(AddExp),
-    CompoundRelExp(Box<RelExp>, AddExp, RelOp)
+    CompoundRelExp(Box<RelExp>, AddExp, RelOp, Span)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EqOp {
     Eq,
     Ne
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EqExp {
     RelExp(RelExp),
-    CompoundEqExp(Box<EqExp>, RelExp, EqOp)
+    CompoundEqExp(Box<EqExp>, RelExp, EqOp, Span)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LAndExp {
     EqExp(EqExp),
-    CompoundLAndExp(Box<LAndExp>, EqExp)
+    CompoundLAndExp(Box<LAndExp>, EqExp, Span)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LOrExp {
     LAndExp(LAndExp),
-    CompoundLOrExp(Box<LOrExp>, LAndExp)
+    CompoundLOrExp(Box<LOrExp>, LAndExp, Span)
 }
 
 impl std::fmt::Display for UnaryOp {
@@ -238,7 +246,7 @@ impl std::fmt::Display for MulOp {
 impl std::fmt::Display for EqOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Eq => write!(f, "="),
+            Self::Eq => write!(f, "=="),
             Self::Ne => write!(f, "!=")
         }
     }
@@ -270,4 +278,156 @@ impl std::fmt::Display for BType {
             Self::Int => write!(f, "int")
         }
     }
-}
\ No newline at end of file
+}
+
+// 以下实现把语法树打印为 SysY 源程序，打印结果重新解析后得到相同的语法树
+// 语法树的层次已经体现了优先级，只有 PrimaryExp::Exp 需要打印括号
+
+impl std::fmt::Display for CompUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.func_def)
+    }
+}
+
+impl std::fmt::Display for FuncDef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}() {}", self.func_type, self.ident, self.block)
+    }
+}
+
+impl std::fmt::Display for Block {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{{")?;
+        for item in &self.block_items {
+            writeln!(f, "    {}", item)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl std::fmt::Display for BlockItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Decl(decl) => write!(f, "{}", decl),
+            Self::Stmt(stmt) => write!(f, "{}", stmt)
+        }
+    }
+}
+
+impl std::fmt::Display for Decl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ConstDecl(const_decl) => {
+                let defs: Vec<String> = const_decl.const_def.iter()
+                    .map(|def| format!("{} = {}", def.ident, def.const_init_val.const_exp.exp))
+                    .collect();
+                write!(f, "const {} {};", const_decl.b_type, defs.join(", "))
+            },
+            Self::VarDecl(var_decl) => {
+                let defs: Vec<String> = var_decl.var_def.iter()
+                    .map(|def| match &def.init_val {
+                        Some(init_val) => format!("{} = {}", def.ident, init_val.exp),
+                        None => def.ident.clone()
+                    })
+                    .collect();
+                write!(f, "{} {};", var_decl.b_type, defs.join(", "))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Stmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exp(exp, _) => write!(f, "return {};", exp),
+            Self::LValExp(l_val, exp, _) => write!(f, "{} = {};", l_val, exp)
+        }
+    }
+}
+
+impl std::fmt::Display for Exp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.l_or_exp)
+    }
+}
+
+impl std::fmt::Display for LOrExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LAndExp(exp) => write!(f, "{}", exp),
+            Self::CompoundLOrExp(lhs, rhs, _) => write!(f, "{} || {}", lhs, rhs)
+        }
+    }
+}
+
+impl std::fmt::Display for LAndExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EqExp(exp) => write!(f, "{}", exp),
+            Self::CompoundLAndExp(lhs, rhs, _) => write!(f, "{} && {}", lhs, rhs)
+        }
+    }
+}
+
+impl std::fmt::Display for EqExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RelExp(exp) => write!(f, "{}", exp),
+            Self::CompoundEqExp(lhs, rhs, op, _) => write!(f, "{} {} {}", lhs, op, rhs)
+        }
+    }
+}
+
+impl std::fmt::Display for RelExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AddExp(exp) => write!(f, "{}", exp),
+            Self::CompoundRelExp(lhs, rhs, op, _) => write!(f, "{} {} {}", lhs, op, rhs)
+        }
+    }
+}
+
+impl std::fmt::Display for AddExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MulExp(exp) => write!(f, "{}", exp),
+            Self::CompoundAddExp(lhs, rhs, op, _) => write!(f, "{} {} {}", lhs, op, rhs)
+        }
+    }
+}
+
+impl std::fmt::Display for MulExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnaryExp(exp) => write!(f, "{}", exp),
+            Self::CompoundMulExp(lhs, rhs, op, _) => write!(f, "{} {} {}", lhs, op, rhs)
+        }
+    }
+}
+
+impl std::fmt::Display for UnaryExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PrimaryExp(exp) => write!(f, "{}", exp),
+            Self::CompoundUnaryExp(op, exp, _) => write!(f, "{}{}", op, exp)
+        }
+    }
+}
+
+impl std::fmt::Display for PrimaryExp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exp(exp) => write!(f, "({})", exp),
+            Self::Number(number, _) => write!(f, "{}", number),
+            Self::LVal(l_val) => write!(f, "{}", l_val)
+        }
+    }
+}
+
+impl std::fmt::Display for LVal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ident(ident, _) => write!(f, "{}", ident)
+        }
+    }
+}
@@ -0,0 +1,336 @@
+/// 此文件存放语法树的打印（--emit=ast 和 --emit=ast-json），用来检查 sysy.lalrpop 构造出的语法树
+/// - 先把语法树转换为统一的 Node，再打印为缩进的文本或者 JSON
+/// - 节点的种类是结构体的名字，或者 "枚举::变体"；标识符、字面量、类型和运算符是节点的属性
+/// - FuncDef、ConstDef、VarDef、语句、LVal、整数字面量和一元、二元运算记录了源码位置，打印为从 1 开始的 行:列
+use super::{
+    AddExp, Block, BlockItem, CompUnit, ConstDecl, ConstDef, Decl, EqExp, Exp, FuncDef, LAndExp, LOrExp, LVal, MulExp,
+    PrimaryExp, RelExp, Span, Stmt, UnaryExp, VarDecl, VarDef
+};
+
+/// 打印为缩进的文本，每行一个节点
+/// 只有一个子节点、没有属性和位置的节点（比如表达式的各层包装）和子节点打印在同一行，用 " > " 连接
+pub fn dump_tree(unit: &CompUnit, source: &str) -> String {
+    let mut out = String::new();
+    Printer::new(source).tree(&unit.to_node(), 0, &mut out);
+    out
+}
+
+/// 打印为 JSON，每个节点是一个对象：
+/// `{"kind": ..., 属性..., "span": {"start": {"line", "column", "offset"}, "end": ...}, "children": [...]}`
+/// 没有源码位置的节点没有 "span"
+pub fn dump_json(unit: &CompUnit, source: &str) -> String {
+    let mut out = String::new();
+    Printer::new(source).json(&unit.to_node(), 0, &mut out);
+    out.push('\n');
+    out
+}
+
+/// 统一的语法树节点
+struct Node {
+    kind: &'static str,
+    attrs: Vec<(&'static str, Attr)>,
+    span: Option<Span>,
+    children: Vec<Node>
+}
+
+enum Attr {
+    Str(String),
+    Int(i32)
+}
+
+impl Node {
+    fn new(kind: &'static str) -> Self {
+        Self { kind, attrs: Vec::new(), span: None, children: Vec::new() }
+    }
+
+    fn attr(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.attrs.push((name, Attr::Str(value.to_string())));
+        self
+    }
+
+    fn int(mut self, name: &'static str, value: i32) -> Self {
+        self.attrs.push((name, Attr::Int(value)));
+        self
+    }
+
+    fn span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    fn child(mut self, child: &impl ToNode) -> Self {
+        self.children.push(child.to_node());
+        self
+    }
+}
+
+/// 把源码中的字节偏移换算为行列
+struct Printer<'a> {
+    source: &'a str,
+    /// 每一行开头的字节偏移
+    line_starts: Vec<usize>
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0).chain(source.match_indices('\n').map(|(index, _)| index + 1)).collect();
+        Self { source, line_starts }
+    }
+
+    /// 偏移所在的行和列（从 1 开始），列按字符计数
+    fn locate(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let column = self.source.get(start..offset).map_or(offset - start, |text| text.chars().count());
+        (line + 1, column + 1)
+    }
+
+    fn tree(&self, node: &Node, depth: usize, out: &mut String) {
+        out.push_str(&"  ".repeat(depth));
+        let mut node = node;
+        loop {
+            out.push_str(node.kind);
+            for (name, value) in &node.attrs {
+                match value {
+                    Attr::Str(value) => out.push_str(&format!(" {}={:?}", name, value)),
+                    Attr::Int(value) => out.push_str(&format!(" {}={}", name, value))
+                }
+            }
+            if let Some(span) = node.span {
+                let ((start_line, start_column), (end_line, end_column)) = (self.locate(span.start), self.locate(span.end));
+                out.push_str(&format!(" @{}:{}-{}:{}", start_line, start_column, end_line, end_column));
+            }
+            match node.children.as_slice() {
+                [only] if node.attrs.is_empty() && node.span.is_none() => {
+                    out.push_str(" > ");
+                    node = only;
+                },
+                _ => break
+            }
+        }
+        out.push('\n');
+        for child in &node.children {
+            self.tree(child, depth + 1, out);
+        }
+    }
+
+    fn json(&self, node: &Node, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth + 1);
+        out.push_str(&format!("{{\n{}\"kind\": {}", indent, json_string(node.kind)));
+        for (name, value) in &node.attrs {
+            let value = match value {
+                Attr::Str(value) => json_string(value),
+                Attr::Int(value) => value.to_string()
+            };
+            out.push_str(&format!(",\n{}\"{}\": {}", indent, name, value));
+        }
+        if let Some(span) = node.span {
+            out.push_str(&format!(
+                ",\n{}\"span\": {{\"start\": {}, \"end\": {}}}",
+                indent,
+                self.json_position(span.start),
+                self.json_position(span.end)
+            ));
+        }
+        out.push_str(&format!(",\n{}\"children\": [", indent));
+        for (index, child) in node.children.iter().enumerate() {
+            out.push_str(if index == 0 { "\n" } else { ",\n" });
+            out.push_str(&"  ".repeat(depth + 2));
+            self.json(child, depth + 2, out);
+        }
+        if !node.children.is_empty() {
+            out.push('\n');
+            out.push_str(&indent);
+        }
+        out.push_str(&format!("]\n{}}}", "  ".repeat(depth)));
+    }
+
+    fn json_position(&self, offset: usize) -> String {
+        let (line, column) = self.locate(offset);
+        format!("{{\"line\": {}, \"column\": {}, \"offset\": {}}}", line, column, offset)
+    }
+}
+
+/// JSON 字符串字面量
+fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+    result.push('"');
+    result
+}
+
+trait ToNode {
+    fn to_node(&self) -> Node;
+}
+
+impl ToNode for CompUnit {
+    fn to_node(&self) -> Node {
+        Node::new("CompUnit").child(&self.func_def)
+    }
+}
+
+impl ToNode for FuncDef {
+    fn to_node(&self) -> Node {
+        Node::new("FuncDef").attr("func_type", self.func_type).attr("ident", &self.ident).span(self.span).child(&self.block)
+    }
+}
+
+impl ToNode for Block {
+    fn to_node(&self) -> Node {
+        self.block_items.iter().fold(Node::new("Block"), Node::child)
+    }
+}
+
+impl ToNode for BlockItem {
+    fn to_node(&self) -> Node {
+        match self {
+            BlockItem::Decl(decl) => Node::new("BlockItem::Decl").child(decl),
+            BlockItem::Stmt(stmt) => Node::new("BlockItem::Stmt").child(stmt)
+        }
+    }
+}
+
+impl ToNode for Decl {
+    fn to_node(&self) -> Node {
+        match self {
+            Decl::ConstDecl(decl) => Node::new("Decl::ConstDecl").child(decl),
+            Decl::VarDecl(decl) => Node::new("Decl::VarDecl").child(decl)
+        }
+    }
+}
+
+impl ToNode for ConstDecl {
+    fn to_node(&self) -> Node {
+        self.const_def.iter().fold(Node::new("ConstDecl").attr("b_type", &self.b_type), Node::child)
+    }
+}
+
+impl ToNode for ConstDef {
+    fn to_node(&self) -> Node {
+        // ConstInitVal 和 ConstExp 只是包装，直接打印其中的表达式
+        Node::new("ConstDef").attr("ident", &self.ident).span(self.span).child(&self.const_init_val.const_exp.exp)
+    }
+}
+
+impl ToNode for VarDecl {
+    fn to_node(&self) -> Node {
+        self.var_def.iter().fold(Node::new("VarDecl").attr("b_type", &self.b_type), Node::child)
+    }
+}
+
+impl ToNode for VarDef {
+    fn to_node(&self) -> Node {
+        let node = Node::new("VarDef").attr("ident", &self.ident).span(self.span);
+        match &self.init_val {
+            // InitVal 只是包装，直接打印其中的表达式
+            Some(init_val) => node.child(&init_val.exp),
+            None => node
+        }
+    }
+}
+
+impl ToNode for Stmt {
+    fn to_node(&self) -> Node {
+        match self {
+            Stmt::Exp(exp, span) => Node::new("Stmt::Exp").span(*span).child(exp),
+            Stmt::LValExp(l_val, exp, span) => Node::new("Stmt::LValExp").span(*span).child(l_val).child(exp)
+        }
+    }
+}
+
+impl ToNode for Exp {
+    fn to_node(&self) -> Node {
+        Node::new("Exp").child(&self.l_or_exp)
+    }
+}
+
+impl ToNode for LOrExp {
+    fn to_node(&self) -> Node {
+        match self {
+            LOrExp::LAndExp(exp) => Node::new("LOrExp::LAndExp").child(exp),
+            LOrExp::CompoundLOrExp(lhs, rhs, span) => Node::new("LOrExp::CompoundLOrExp").attr("op", "||").span(*span).child(&**lhs).child(rhs)
+        }
+    }
+}
+
+impl ToNode for LAndExp {
+    fn to_node(&self) -> Node {
+        match self {
+            LAndExp::EqExp(exp) => Node::new("LAndExp::EqExp").child(exp),
+            LAndExp::CompoundLAndExp(lhs, rhs, span) => Node::new("LAndExp::CompoundLAndExp").attr("op", "&&").span(*span).child(&**lhs).child(rhs)
+        }
+    }
+}
+
+impl ToNode for EqExp {
+    fn to_node(&self) -> Node {
+        match self {
+            EqExp::RelExp(exp) => Node::new("EqExp::RelExp").child(exp),
+            EqExp::CompoundEqExp(lhs, rhs, op, span) => Node::new("EqExp::CompoundEqExp").attr("op", op).span(*span).child(&**lhs).child(rhs)
+        }
+    }
+}
+
+impl ToNode for RelExp {
+    fn to_node(&self) -> Node {
+        match self {
+            RelExp::AddExp(exp) => Node::new("RelExp::AddExp").child(exp),
+            RelExp::CompoundRelExp(lhs, rhs, op, span) => Node::new("RelExp::CompoundRelExp").attr("op", op).span(*span).child(&**lhs).child(rhs)
+        }
+    }
+}
+
+impl ToNode for AddExp {
+    fn to_node(&self) -> Node {
+        match self {
+            AddExp::MulExp(exp) => Node::new("AddExp::MulExp").child(exp),
+            AddExp::CompoundAddExp(lhs, rhs, op, span) => Node::new("AddExp::CompoundAddExp").attr("op", op).span(*span).child(&**lhs).child(rhs)
+        }
+    }
+}
+
+impl ToNode for MulExp {
+    fn to_node(&self) -> Node {
+        match self {
+            MulExp::UnaryExp(exp) => Node::new("MulExp::UnaryExp").child(exp),
+            MulExp::CompoundMulExp(lhs, rhs, op, span) => Node::new("MulExp::CompoundMulExp").attr("op", op).span(*span).child(&**lhs).child(rhs)
+        }
+    }
+}
+
+impl ToNode for UnaryExp {
+    fn to_node(&self) -> Node {
+        match self {
+            UnaryExp::PrimaryExp(exp) => Node::new("UnaryExp::PrimaryExp").child(exp),
+            UnaryExp::CompoundUnaryExp(op, exp, span) => Node::new("UnaryExp::CompoundUnaryExp").attr("op", op).span(*span).child(&**exp)
+        }
+    }
+}
+
+impl ToNode for PrimaryExp {
+    fn to_node(&self) -> Node {
+        match self {
+            PrimaryExp::Exp(exp) => Node::new("PrimaryExp::Exp").child(&**exp),
+            PrimaryExp::Number(value, span) => Node::new("PrimaryExp::Number").int("value", *value).span(*span),
+            PrimaryExp::LVal(l_val) => Node::new("PrimaryExp::LVal").child(l_val)
+        }
+    }
+}
+
+impl ToNode for LVal {
+    fn to_node(&self) -> Node {
+        match self {
+            LVal::Ident(ident, span) => Node::new("LVal::Ident").attr("ident", ident).span(*span)
+        }
+    }
+}
@@ -164,7 +164,7 @@ impl IrGen {
                 BlockItem::Stmt(stmt) => {
                     match stmt {
                         // return 语句
-                        Stmt::Exp(exp) => {
+                        Stmt::Exp(exp, _) => {
                             let r = self.generate_expression(function_data, ir_block, exp);
                             if let Ok(d) = r {
                                 result = Ok(Some(d));
@@ -173,7 +173,7 @@ impl IrGen {
                             }
                         }
                         // 赋值语句
-                        Stmt::LValExp(l_val, exp) => {
+                        Stmt::LValExp(l_val, exp, _) => {
                             self.generate_assign_statement(function_data, ir_block, l_val, exp)?;
                             // 赋值语句没有返回值
                             result = Ok(None);
@@ -12,7 +12,7 @@ impl IrGen {
             AddExp::MulExp(mul_exp) => {
                 self.generate_mul_statement(function_data, block, mul_exp)
             },
-            AddExp::CompoundAddExp(add_exp, mul_exp, add_op) => {
+            AddExp::CompoundAddExp(add_exp, mul_exp, add_op, _) => {
                 // 求值这两个表达式
                 let left_value = self.generate_add_statement(function_data, block, add_exp)?;
                 let right_value = self.generate_mul_statement(function_data, block, mul_exp)?;
@@ -40,7 +40,7 @@ impl IrGen {
             MulExp::UnaryExp(unary_exp) => {
                 self.generate_unary_statement(function_data, block, unary_exp)
             },
-            MulExp::CompoundMulExp(mul_exp, unary_exp, mul_op) => {
+            MulExp::CompoundMulExp(mul_exp, unary_exp, mul_op, _) => {
                 let left_value = self.generate_mul_statement(function_data, block, mul_exp)?;
                 let right_value = self.generate_unary_statement(function_data, block, unary_exp)?;
                 match mul_op {
@@ -1,4 +1,4 @@
-use crate::{error_report::{Label, ProblemInfo}, function_ast::{AddExp, AddOp, BType, ConstDecl, ConstDef, ConstInitVal, EqExp, EqOp, Exp, LAndExp, LOrExp, LVal, MulExp, MulOp, PrimaryExp, RelExp, RelOp, UnaryExp, UnaryOp}, ir_gen::Symbol};
+use crate::{error_report::{Label, ProblemInfo}, function_ast::{AddExp, AddOp, BType, ConstDecl, ConstDef, ConstInitVal, EqExp, EqOp, Exp, LAndExp, LOrExp, LVal, MulExp, MulOp, PrimaryExp, RelExp, RelOp, Span, UnaryExp, UnaryOp}, ir_gen::Symbol};
 
 use super::IrGen;
 
@@ -12,7 +12,7 @@ impl IrGen {
 
     
     pub(super) fn generate_const_definition(&self, _: &BType, def: &ConstDef) -> Result<(),()> {
-        let result = self.calculate_const_statement(&def.const_init_val)?;
+        let result = self.calculate_const_statement(&def.const_init_val, def.span)?;
         if self.new_const_symbol(def.ident.clone(), result).is_err() {
             self.problems.borrow_mut().push(ProblemInfo::error(format!("duplicate symbol '{}' found.", def.ident), 
                                vec![Label::primary("Note: duplicate symbol found here.", def.span)], None));
@@ -22,22 +22,22 @@ impl IrGen {
         }   
     } 
 
-    pub(super) fn calculate_const_statement(&self, const_init_val: &ConstInitVal) -> Result<i32, ()> {
-        self.calculate_expression(&const_init_val.const_exp.exp)
+    pub(super) fn calculate_const_statement(&self, const_init_val: &ConstInitVal, span: Span) -> Result<i32, ()> {
+        self.calculate_expression(&const_init_val.const_exp.exp, span)
     }
 
-    pub(super) fn calculate_expression(&self, exp: &Exp) -> Result<i32, ()> {
-        self.calculate_l_or_expression(&exp.l_or_exp)
+    pub(super) fn calculate_expression(&self, exp: &Exp, span: Span) -> Result<i32, ()> {
+        self.calculate_l_or_expression(&exp.l_or_exp, span)
     }
 
-    pub(super) fn calculate_l_or_expression(&self, l_or_exp: &LOrExp) -> Result<i32, ()> {
+    pub(super) fn calculate_l_or_expression(&self, l_or_exp: &LOrExp, span: Span) -> Result<i32, ()> {
         match l_or_exp {
             LOrExp::LAndExp(l_and_exp) => {
-                self.calculate_l_and_exp(l_and_exp)
+                self.calculate_l_and_exp(l_and_exp, span)
             },
-            LOrExp::CompoundLOrExp(l_or_exp, l_and_exp) => {
-                let left = self.calculate_l_or_expression(l_or_exp)?;
-                let right = self.calculate_l_and_exp(l_and_exp)?;
+            LOrExp::CompoundLOrExp(l_or_exp, l_and_exp, _) => {
+                let left = self.calculate_l_or_expression(l_or_exp, span)?;
+                let right = self.calculate_l_and_exp(l_and_exp, span)?;
                 if left != 0 || right != 0 {
                     Ok(1)
                 } else {
@@ -47,14 +47,14 @@ impl IrGen {
         }
     }
 
-    pub(super) fn calculate_l_and_exp(&self, l_and_exp: &LAndExp) -> Result<i32, ()> {
+    pub(super) fn calculate_l_and_exp(&self, l_and_exp: &LAndExp, span: Span) -> Result<i32, ()> {
         match l_and_exp {
             LAndExp::EqExp(eq_exp) => {
-                self.calculate_eq_exp(eq_exp)
+                self.calculate_eq_exp(eq_exp, span)
             },
-            LAndExp::CompoundLAndExp(l_and_exp, eq_exp) => {
-                let left = self.calculate_l_and_exp(l_and_exp)?;
-                let right = self.calculate_eq_exp(eq_exp)?;
+            LAndExp::CompoundLAndExp(l_and_exp, eq_exp, _) => {
+                let left = self.calculate_l_and_exp(l_and_exp, span)?;
+                let right = self.calculate_eq_exp(eq_exp, span)?;
                 if left != 0 && right != 0 {
                     Ok(1)
                 } else {
@@ -64,14 +64,14 @@ impl IrGen {
         }
     }
 
-    pub(super) fn calculate_eq_exp(&self, eq_exp: &EqExp) -> Result<i32, ()> {
+    pub(super) fn calculate_eq_exp(&self, eq_exp: &EqExp, span: Span) -> Result<i32, ()> {
         match eq_exp {
             EqExp::RelExp(rel_exp) => {
-                self.calculate_rel_exp(rel_exp)
+                self.calculate_rel_exp(rel_exp, span)
             },
-            EqExp::CompoundEqExp(eq_exp, rel_exp, eq_op) => {
-                let left = self.calculate_eq_exp(eq_exp)?;
-                let right = self.calculate_rel_exp(rel_exp)?;
+            EqExp::CompoundEqExp(eq_exp, rel_exp, eq_op, _) => {
+                let left = self.calculate_eq_exp(eq_exp, span)?;
+                let right = self.calculate_rel_exp(rel_exp, span)?;
                 match eq_op {
                     EqOp::Eq => {
                         Ok((left == right) as i32)
@@ -84,14 +84,14 @@ impl IrGen {
         }
     }
 
-    pub(super) fn calculate_rel_exp(&self, rel_exp: &RelExp) -> Result<i32, ()> {
+    pub(super) fn calculate_rel_exp(&self, rel_exp: &RelExp, span: Span) -> Result<i32, ()> {
         match rel_exp {
             RelExp::AddExp(add_exp) => {
-                self.calculate_add_exp(add_exp)
+                self.calculate_add_exp(add_exp, span)
             },
-            RelExp::CompoundRelExp(rel_exp, add_exp, rel_op) => {
-                let left = self.calculate_rel_exp(rel_exp)?;
-                let right = self.calculate_add_exp(add_exp)?;
+            RelExp::CompoundRelExp(rel_exp, add_exp, rel_op, _) => {
+                let left = self.calculate_rel_exp(rel_exp, span)?;
+                let right = self.calculate_add_exp(add_exp, span)?;
                 match rel_op {
                     RelOp::Ge => {
                         Ok((left >= right) as i32)
@@ -110,58 +110,64 @@ impl IrGen {
         }
     }
 
-    pub(super) fn calculate_add_exp(&self, add_exp: &AddExp) -> Result<i32, ()> {
+    // 常量表达式和运行时的运算一样按 32 位补码回绕，不会因为溢出而 panic
+    pub(super) fn calculate_add_exp(&self, add_exp: &AddExp, span: Span) -> Result<i32, ()> {
         match add_exp {
             AddExp::MulExp(mul_exp) => {
-                self.calculate_mul_exp(mul_exp)
+                self.calculate_mul_exp(mul_exp, span)
             },
-            AddExp::CompoundAddExp(add_exp, mul_exp, add_op) => {
-                let left = self.calculate_add_exp(add_exp)?;
-                let right = self.calculate_mul_exp(mul_exp)?;
+            AddExp::CompoundAddExp(add_exp, mul_exp, add_op, _) => {
+                let left = self.calculate_add_exp(add_exp, span)?;
+                let right = self.calculate_mul_exp(mul_exp, span)?;
                 match add_op {
-                    AddOp::Plus => Ok(left + right),
-                    AddOp::Minus => Ok(left - right)
+                    AddOp::Plus => Ok(left.wrapping_add(right)),
+                    AddOp::Minus => Ok(left.wrapping_sub(right))
                 }
             }
         }
     }
 
-    pub(super) fn calculate_mul_exp(&self, mul_exp: &MulExp) -> Result<i32, ()> {
+    pub(super) fn calculate_mul_exp(&self, mul_exp: &MulExp, span: Span) -> Result<i32, ()> {
         match mul_exp {
             MulExp::UnaryExp(unary_exp) => {
-                self.calculate_unary_exp(unary_exp)
+                self.calculate_unary_exp(unary_exp, span)
             },
-            MulExp::CompoundMulExp(mul_exp, unary_exp, mul_op) => {
-                let left = self.calculate_mul_exp(mul_exp)?;
-                let right = self.calculate_unary_exp(unary_exp)?;
+            MulExp::CompoundMulExp(mul_exp, unary_exp, mul_op, _) => {
+                let left = self.calculate_mul_exp(mul_exp, span)?;
+                let right = self.calculate_unary_exp(unary_exp, span)?;
                 match mul_op {
-                    MulOp::Mul => Ok(left * right),
-                    MulOp::Div => Ok(left / right),
-                    MulOp::Mod => Ok(left % right)
+                    MulOp::Div | MulOp::Mod if right == 0 => {
+                        self.problems.borrow_mut().push(ProblemInfo::error("division by zero in constant expression",
+                                                        vec![Label::primary("Note: the divisor in this definition evaluates to 0.", span)], None));
+                        Err(())
+                    },
+                    MulOp::Mul => Ok(left.wrapping_mul(right)),
+                    MulOp::Div => Ok(left.wrapping_div(right)),
+                    MulOp::Mod => Ok(left.wrapping_rem(right))
                 }
             }
         }
     }
 
-    pub(super) fn calculate_unary_exp(&self, unary_exp: &UnaryExp) -> Result<i32, ()> {
+    pub(super) fn calculate_unary_exp(&self, unary_exp: &UnaryExp, span: Span) -> Result<i32, ()> {
         match unary_exp {
             UnaryExp::PrimaryExp(primary_exp) => {
-                self.calculate_primary_exp(primary_exp)
+                self.calculate_primary_exp(primary_exp, span)
             },
-            UnaryExp::CompoundUnaryExp(unary_op, unary_exp) => {
-                let internal = self.calculate_unary_exp(unary_exp)?;
+            UnaryExp::CompoundUnaryExp(unary_op, unary_exp, _) => {
+                let internal = self.calculate_unary_exp(unary_exp, span)?;
                 match unary_op {
                     UnaryOp::Plus => Ok(internal),
-                    UnaryOp::Minus => Ok(-internal),
+                    UnaryOp::Minus => Ok(internal.wrapping_neg()),
                     UnaryOp::Not => Ok((internal == 0) as i32)
                 }
             }
         }
     }
 
-    pub(super) fn calculate_primary_exp(&self, primary_exp: &PrimaryExp) -> Result<i32, ()> {
+    pub(super) fn calculate_primary_exp(&self, primary_exp: &PrimaryExp, span: Span) -> Result<i32, ()> {
         match primary_exp {
-            PrimaryExp::Number(i) => Ok(*i),
+            PrimaryExp::Number(i, _) => Ok(*i),
             PrimaryExp::LVal(l_val) => {
                 match l_val {
                     LVal::Ident(s, span) => {
@@ -186,7 +192,7 @@ impl IrGen {
                 }
             },
             PrimaryExp::Exp(exp) => {
-                self.calculate_l_or_expression(&exp.l_or_exp)
+                self.calculate_l_or_expression(&exp.l_or_exp, span)
             }
         }
     }
@@ -10,7 +10,7 @@ impl IrGen {
             LOrExp::LAndExp(and_exp) => {
                 self.generate_land_statement(function_data, block, and_exp)
             },
-            LOrExp::CompoundLOrExp(or_exp, and_exp) => {
+            LOrExp::CompoundLOrExp(or_exp, and_exp, _) => {
                 let left_value = self.generate_lor_statement(function_data, block, or_exp)?;
                 let right_value = self.generate_land_statement(function_data, block, and_exp)?;
                 // 生成一条 OR 指令
@@ -32,7 +32,7 @@ impl IrGen {
             LAndExp::EqExp(eq_exp) => {
                 self.generate_eq_statement(function_data, block, eq_exp)
             },
-            LAndExp::CompoundLAndExp(and_exp, eq_exp) => {
+            LAndExp::CompoundLAndExp(and_exp, eq_exp, _) => {
                 let left_value = self.generate_land_statement(function_data, block, and_exp)?;
                 let right_value = self.generate_eq_statement(function_data, block, eq_exp)?;
                 // 生成一条 AND 指令
@@ -54,7 +54,7 @@ impl IrGen {
             EqExp::RelExp(rel_exp) => {
                 self.generate_rel_statement(function_data, block, rel_exp)
             },
-            EqExp::CompoundEqExp(eq_exp, rel_exp, eq_op) => {
+            EqExp::CompoundEqExp(eq_exp, rel_exp, eq_op, _) => {
                 let left_value = self.generate_eq_statement(function_data, block, eq_exp)?;
                 let right_value = self.generate_rel_statement(function_data, block, rel_exp)?;
 
@@ -79,7 +79,7 @@ impl IrGen {
             RelExp::AddExp(add_exp) => {
                 self.generate_add_statement(function_data, block, add_exp)
             },
-            RelExp::CompoundRelExp(rel_exp, add_exp, rel_op) => {
+            RelExp::CompoundRelExp(rel_exp, add_exp, rel_op, _) => {
                 let left_value = self.generate_rel_statement(function_data, block, rel_exp)?;
                 let right_value = self.generate_add_statement(function_data, block, add_exp)?;
 
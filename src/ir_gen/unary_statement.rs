@@ -14,7 +14,7 @@ impl IrGen {
                 // 生成内部表达式的值即可
                 self.generate_primary_statement(function_data, block, primary)
             },
-            UnaryExp::CompoundUnaryExp(op, unary_exp) => {
+            UnaryExp::CompoundUnaryExp(op, unary_exp, _) => {
                 // 先生成内部语句
                 let value = self.generate_unary_statement(function_data, block, unary_exp)?;
                 match op {
@@ -51,7 +51,7 @@ impl IrGen {
                 // 如果此表达式内部包裹了表达式，则去生成内部表达式
                 self.generate_expression(function_data, block, exp)
             },
-            PrimaryExp::Number(i) => {
+            PrimaryExp::Number(i, _) => {
                 // 如果此表达式的值是一个整数，直接将其放入 IR 并返回就可以了
                 // 注意常数是不需要加入 function 的
                 Ok(function_data.dfg_mut().new_value().integer(*i))
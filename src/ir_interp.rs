@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use koopa::ir::{dfg::DataFlowGraph, BasicBlock, Function, Program, TypeKind, Value, ValueKind};
+
+use crate::error_report::{describe_instruction, describe_value, kind_name};
+use crate::ir_opt::evaluate_binary;
+use crate::sysy_runtime::{RuntimeFunction, SysyIo};
+
+// 直接执行 Koopa IR 的解释器，用于在没有 RISC-V 工具链时检查 IrGen 和优化的结果
+mod memory;
+mod runtime;
+pub mod error;
+
+pub use error::RuntimeError;
+use error::TrapReason;
+use memory::{cells_of, Memory, Pointer, Val};
+
+/// 函数调用的最大层数，超过时报告栈溢出
+const MAX_CALL_DEPTH: usize = 100_000;
+
+/// 一个正在执行的函数
+struct Frame {
+    function: Function,
+    block: BasicBlock,
+    /// 下一条要执行的指令在基本块中的下标
+    index: usize,
+    /// 参数、基本块参数和指令的值
+    values: HashMap<Value, Val>,
+    /// 调用者中等待返回值的 call 指令，main 没有调用者
+    call: Option<Value>,
+    /// 进入函数时的对象个数，返回时释放之后分配的局部变量
+    memory_mark: usize
+}
+
+/// 解释器：在 input 和 output 上执行整个 Program
+pub struct Interpreter<'p, R: BufRead, W: Write> {
+    prog: &'p Program,
+    memory: Memory,
+    /// 全局变量对应的指针
+    globals: HashMap<Value, Val>,
+    /// 每个函数每个基本块中的指令，按顺序排列
+    blocks: HashMap<Function, HashMap<BasicBlock, Vec<Value>>>,
+    frames: Vec<Frame>,
+    io: SysyIo<R, W>
+}
+
+impl<'p, R: BufRead, W: Write> Interpreter<'p, R, W> {
+    pub fn new(prog: &'p Program, input: R, output: W) -> Self {
+        let mut memory = Memory::default();
+        let mut globals = HashMap::new();
+        for &global in prog.inst_layout() {
+            if let ValueKind::GlobalAlloc(alloc) = prog.borrow_value(global).kind() {
+                let mut cells = Vec::new();
+                global_init_cells(prog, alloc.init(), &mut cells);
+                globals.insert(global, Val::Ptr(memory.allocate(cells)));
+            }
+        }
+        Self {
+            prog,
+            memory,
+            globals,
+            blocks: HashMap::new(),
+            frames: Vec::new(),
+            io: SysyIo::new(input, output)
+        }
+    }
+
+    /// 从 @main 开始执行，返回 main 的返回值
+    /// 程序出错时返回错误，指明出错的函数和指令
+    pub fn run(&mut self) -> Result<i32, RuntimeError> {
+        let main = self.prog.func_layout().iter()
+            .copied()
+            .find(|&f| self.prog.func(f).name() == "@main" && self.prog.func(f).layout().entry_bb().is_some())
+            .ok_or_else(|| RuntimeError {
+                function: String::from("@main"),
+                instruction: String::from("program entry"),
+                reason: TrapReason::UndefinedFunction(String::from("@main"))
+            })?;
+        self.enter_function(main, Vec::new(), None);
+        loop {
+            let frame = self.frames.last().unwrap();
+            let inst = self.blocks[&frame.function][&frame.block][frame.index];
+            match self.execute(inst) {
+                Ok(Some(result)) => {
+                    self.io.flush().map_err(|e| self.error_at(inst, TrapReason::Output(e.to_string())))?;
+                    return Ok(result);
+                },
+                Ok(None) => {},
+                Err(reason) => {
+                    // 尽量把出错之前的输出写出去，写不出去也不影响报告原来的错误
+                    let _ = self.io.flush();
+                    return Err(self.error_at(inst, reason));
+                }
+            }
+        }
+    }
+
+    /// 把出错原因和当前执行的指令组合为 RuntimeError
+    fn error_at(&self, inst: Value, reason: TrapReason) -> RuntimeError {
+        let frame = self.frames.last().unwrap();
+        let func = self.prog.func(frame.function);
+        RuntimeError {
+            function: func.name().to_string(),
+            instruction: describe_instruction(func.dfg(), frame.block, frame.index, inst),
+            reason
+        }
+    }
+
+    /// 进入一个有函数体的函数
+    fn enter_function(&mut self, function: Function, args: Vec<Val>, call: Option<Value>) {
+        let func = self.prog.func(function);
+        self.blocks.entry(function).or_insert_with(|| {
+            func.layout().bbs().iter()
+                .map(|(&bb, node)| (bb, node.insts().keys().copied().collect()))
+                .collect()
+        });
+        let values = func.params().iter().copied().zip(args).collect();
+        self.frames.push(Frame {
+            function,
+            block: func.layout().entry_bb().unwrap(),
+            index: 0,
+            values,
+            call,
+            memory_mark: self.memory.mark()
+        });
+    }
+
+    /// 执行一条指令，main 返回时得到它的返回值
+    fn execute(&mut self, inst: Value) -> Result<Option<i32>, TrapReason> {
+        let prog = self.prog;
+        let func = prog.func(self.frames.last().unwrap().function);
+        let dfg = func.dfg();
+        let value = match dfg.value(inst).kind() {
+            ValueKind::Alloc(_) => {
+                let cells = vec![Val::Int(0); cells_of(&pointee(dfg, inst))];
+                Some(Val::Ptr(self.memory.allocate(cells)))
+            },
+            ValueKind::Load(load) => {
+                let pointer = self.pointer(dfg, load.src())?;
+                Some(self.memory.load(pointer)?)
+            },
+            ValueKind::Store(store) => {
+                let value = self.operand(dfg, store.value())?;
+                let pointer = self.pointer(dfg, store.dest())?;
+                self.memory.store(pointer, value)?;
+                None
+            },
+            // 两者都是按结果指向的类型计算步长
+            ValueKind::GetPtr(get) => {
+                let pointer = self.pointer(dfg, get.src())?;
+                let index = self.int(dfg, get.index())? as i64;
+                Some(Val::Ptr(pointer.offset_by(index * cells_of(&pointee(dfg, inst)) as i64)))
+            },
+            ValueKind::GetElemPtr(get) => {
+                let pointer = self.pointer(dfg, get.src())?;
+                let index = self.int(dfg, get.index())? as i64;
+                Some(Val::Ptr(pointer.offset_by(index * cells_of(&pointee(dfg, inst)) as i64)))
+            },
+            ValueKind::Binary(binary) => {
+                let lhs = self.int(dfg, binary.lhs())?;
+                let rhs = self.int(dfg, binary.rhs())?;
+                let result = evaluate_binary(binary.op(), lhs, rhs).ok_or(TrapReason::DivisionByZero)?;
+                Some(Val::Int(result))
+            },
+            ValueKind::Branch(branch) => {
+                if self.int(dfg, branch.cond())? != 0 {
+                    self.jump(dfg, branch.true_bb(), branch.true_args())?;
+                } else {
+                    self.jump(dfg, branch.false_bb(), branch.false_args())?;
+                }
+                return Ok(None);
+            },
+            ValueKind::Jump(jump) => {
+                self.jump(dfg, jump.target(), jump.args())?;
+                return Ok(None);
+            },
+            ValueKind::Call(call) => {
+                let args = call.args().iter()
+                    .map(|&arg| self.operand(dfg, arg))
+                    .collect::<Result<Vec<Val>, TrapReason>>()?;
+                let callee = prog.func(call.callee());
+                if callee.layout().entry_bb().is_some() {
+                    if self.frames.len() >= MAX_CALL_DEPTH {
+                        return Err(TrapReason::StackOverflow);
+                    }
+                    // 返回时再前进到下一条指令
+                    self.enter_function(call.callee(), args, Some(inst));
+                    return Ok(None);
+                }
+                // 只有声明的函数只能是运行时库函数
+                let name = &callee.name()[1..];
+                let function = RuntimeFunction::from_name(name)
+                    .ok_or_else(|| TrapReason::UndefinedFunction(callee.name().to_string()))?;
+                self.call_runtime(function, name, &args)?.map(Val::Int)
+            },
+            ValueKind::Return(ret) => {
+                let result = ret.value().map(|v| self.operand(dfg, v)).transpose()?;
+                return Ok(self.return_from_function(result));
+            },
+            kind => return Err(TrapReason::Unsupported(format!("'{}' instruction", kind_name(kind))))
+        };
+        let frame = self.frames.last_mut().unwrap();
+        if let Some(value) = value {
+            frame.values.insert(inst, value);
+        }
+        frame.index += 1;
+        Ok(None)
+    }
+
+    /// 从当前函数返回，main 返回时得到它的返回值
+    fn return_from_function(&mut self, result: Option<Val>) -> Option<i32> {
+        let frame = self.frames.pop().unwrap();
+        self.memory.release(frame.memory_mark);
+        let Some(caller) = self.frames.last_mut() else {
+            // main 没有返回值时视为返回 0
+            return Some(match result {
+                Some(Val::Int(value)) => value,
+                _ => 0
+            });
+        };
+        if let (Some(call), Some(result)) = (frame.call, result) {
+            caller.values.insert(call, result);
+        }
+        caller.index += 1;
+        None
+    }
+
+    /// 跳转到 target，同时给基本块参数赋值
+    fn jump(&mut self, dfg: &DataFlowGraph, target: BasicBlock, args: &[Value]) -> Result<(), TrapReason> {
+        // 先求出所有实参再赋值，实参可能用到基本块参数自己
+        let args = args.iter()
+            .map(|&arg| self.operand(dfg, arg))
+            .collect::<Result<Vec<Val>, TrapReason>>()?;
+        let frame = self.frames.last_mut().unwrap();
+        frame.values.extend(dfg.bb(target).params().iter().copied().zip(args));
+        frame.block = target;
+        frame.index = 0;
+        Ok(())
+    }
+
+    /// 求一个操作数的值
+    fn operand(&self, dfg: &DataFlowGraph, value: Value) -> Result<Val, TrapReason> {
+        if value.is_global() {
+            return Ok(self.globals[&value]);
+        }
+        match dfg.value(value).kind() {
+            ValueKind::Integer(i) => Ok(Val::Int(i.value())),
+            // 未定义的值和零初始化都视为 0
+            ValueKind::Undef(_) | ValueKind::ZeroInit(_) => Ok(Val::Int(0)),
+            _ => self.frames.last().unwrap().values.get(&value)
+                .copied()
+                .ok_or_else(|| TrapReason::UndefinedValue(describe_value(dfg, value)))
+        }
+    }
+
+    /// 求一个整数操作数的值
+    fn int(&self, dfg: &DataFlowGraph, value: Value) -> Result<i32, TrapReason> {
+        match self.operand(dfg, value)? {
+            Val::Int(i) => Ok(i),
+            Val::Ptr(_) => Err(TrapReason::Unsupported(String::from("using a pointer as an integer")))
+        }
+    }
+
+    /// 求一个指针操作数的值
+    fn pointer(&self, dfg: &DataFlowGraph, value: Value) -> Result<Pointer, TrapReason> {
+        match self.operand(dfg, value)? {
+            Val::Ptr(pointer) => Ok(pointer),
+            Val::Int(_) => Err(TrapReason::InvalidPointer)
+        }
+    }
+}
+
+/// 指针类型的值指向的类型
+fn pointee(dfg: &DataFlowGraph, value: Value) -> koopa::ir::Type {
+    match dfg.value(value).ty().kind() {
+        TypeKind::Pointer(base) => base.clone(),
+        _ => unreachable!("指令的结果不是指针")
+    }
+}
+
+/// 把全局变量的初始值按单元展开
+fn global_init_cells(prog: &Program, init: Value, cells: &mut Vec<Val>) {
+    let data = prog.borrow_value(init);
+    match data.kind() {
+        ValueKind::Integer(i) => cells.push(Val::Int(i.value())),
+        ValueKind::Aggregate(aggregate) => {
+            for &elem in aggregate.elems() {
+                global_init_cells(prog, elem, cells);
+            }
+        },
+        // zeroinit 和 undef 都初始化为 0
+        _ => cells.extend(std::iter::repeat_n(Val::Int(0), cells_of(data.ty())))
+    }
+}
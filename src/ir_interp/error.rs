@@ -0,0 +1,73 @@
+/// 此文件存放解释执行时的错误（陷阱）
+/// - 执行单条指令的过程中只知道出错的原因（TrapReason）
+/// - 解释器的主循环负责补上出错的函数和指令，组成完整的 RuntimeError
+use std::fmt;
+
+use crate::error_report::ProblemInfo;
+
+/// 程序运行出错的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrapReason {
+    /// 除法或者取余的除数为 0
+    DivisionByZero,
+    /// 访问的单元超出了对象的范围
+    OutOfBounds { offset: i64, size: usize },
+    /// 指针没有指向任何对象，例如 undef 指针或者已经返回的函数中的局部变量
+    InvalidPointer,
+    /// 调用了只有声明、也不是 SysY 运行时库的函数
+    UndefinedFunction(String),
+    /// 使用了没有被定义的值，例如在定义之前使用
+    UndefinedValue(String),
+    /// 运行时库函数读取输入失败
+    InvalidInput(String),
+    /// 写入输出失败
+    Output(String),
+    /// 函数调用层数太多
+    StackOverflow,
+    /// 解释器还不支持的指令或者用法，附带说明
+    Unsupported(String)
+}
+
+/// 运行错误：在哪个函数的哪条指令上，因为什么原因出错
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub function: String,
+    pub instruction: String,
+    pub reason: TrapReason
+}
+
+impl fmt::Display for TrapReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrapReason::DivisionByZero => write!(f, "division by zero"),
+            TrapReason::OutOfBounds { offset, size } => write!(f, "out-of-bounds access to element {} of an object with {} element{}", offset, size, if *size == 1 { "" } else { "s" }),
+            TrapReason::InvalidPointer => write!(f, "dereferencing an invalid pointer"),
+            TrapReason::UndefinedFunction(name) => write!(f, "call to undefined function {}", name),
+            TrapReason::UndefinedValue(name) => write!(f, "use of undefined value {}", name),
+            TrapReason::InvalidInput(function) => write!(f, "{} could not read an integer from the input", function),
+            TrapReason::Output(message) => write!(f, "failed to write output: {}", message),
+            TrapReason::StackOverflow => write!(f, "stack overflow"),
+            TrapReason::Unsupported(what) => write!(f, "{} is not supported by the interpreter", what)
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (in function '{}', at {})", self.reason, self.function, self.instruction)
+    }
+}
+
+impl RuntimeError {
+    /// 转换为和编译错误同样格式的诊断信息
+    pub fn to_problem(&self) -> ProblemInfo {
+        ProblemInfo::error(
+            format!("runtime error: {}", self.reason),
+            Vec::new(),
+            Some(vec![
+                format!("in function '{}'", self.function),
+                format!("at {}", self.instruction)
+            ])
+        )
+    }
+}
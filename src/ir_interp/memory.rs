@@ -0,0 +1,83 @@
+/// 此文件存放解释器的内存模型
+/// - 每次 alloc（以及每个全局变量）得到一个独立的对象，对象由若干个单元组成
+/// - 每个单元存放一个 i32 或者一个指针，数组按元素展开，[[i32, 3], 2] 占 6 个单元
+/// - 指针记录所属的对象和单元下标，访问时检查是否越界
+use koopa::ir::{Type, TypeKind};
+
+use super::error::TrapReason;
+
+/// 解释器中的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Val {
+    Int(i32),
+    Ptr(Pointer)
+}
+
+/// 指向某个对象中某个单元的指针
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pointer {
+    /// 对象的编号
+    object: usize,
+    /// 单元下标，getelemptr 可能暂时算出越界的下标，访问时才检查
+    offset: i64
+}
+
+impl Pointer {
+    /// 向后移动 n 个单元
+    pub fn offset_by(self, n: i64) -> Self {
+        Self { object: self.object, offset: self.offset.wrapping_add(n) }
+    }
+}
+
+/// 一个类型的值占用的单元个数
+pub fn cells_of(ty: &Type) -> usize {
+    match ty.kind() {
+        TypeKind::Int32 | TypeKind::Pointer(_) | TypeKind::Function(..) => 1,
+        TypeKind::Array(base, len) => cells_of(base) * len,
+        TypeKind::Unit => 0
+    }
+}
+
+/// 所有对象，局部变量的对象在函数返回时释放
+#[derive(Default)]
+pub struct Memory {
+    objects: Vec<Vec<Val>>
+}
+
+impl Memory {
+    /// 分配一个新对象，初始内容为 cells
+    pub fn allocate(&mut self, cells: Vec<Val>) -> Pointer {
+        self.objects.push(cells);
+        Pointer { object: self.objects.len() - 1, offset: 0 }
+    }
+
+    /// 当前的对象个数，配合 release 使用
+    pub fn mark(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// 释放 mark 之后分配的所有对象
+    pub fn release(&mut self, mark: usize) {
+        self.objects.truncate(mark);
+    }
+
+    /// 读取指针指向的单元
+    pub fn load(&self, pointer: Pointer) -> Result<Val, TrapReason> {
+        let object = self.objects.get(pointer.object).ok_or(TrapReason::InvalidPointer)?;
+        usize::try_from(pointer.offset).ok()
+            .and_then(|offset| object.get(offset))
+            .copied()
+            .ok_or(TrapReason::OutOfBounds { offset: pointer.offset, size: object.len() })
+    }
+
+    /// 写入指针指向的单元
+    pub fn store(&mut self, pointer: Pointer, value: Val) -> Result<(), TrapReason> {
+        let object = self.objects.get_mut(pointer.object).ok_or(TrapReason::InvalidPointer)?;
+        let size = object.len();
+        let cell = usize::try_from(pointer.offset).ok()
+            .and_then(|offset| object.get_mut(offset))
+            .ok_or(TrapReason::OutOfBounds { offset: pointer.offset, size })?;
+        *cell = value;
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+/// 此文件存放解释器对 SysY 运行时库的调用
+/// - 输入输出由 sysy_runtime 完成，这里负责在解释器的内存中读写数组参数
+/// - 输出失败和读不到整数都作为运行错误汇报
+use std::io::{BufRead, Write};
+
+use crate::sysy_runtime::RuntimeFunction;
+
+use super::error::TrapReason;
+use super::memory::{Pointer, Val};
+use super::Interpreter;
+
+impl<'p, R: BufRead, W: Write> Interpreter<'p, R, W> {
+    /// 调用运行时库函数，返回值为 None 表示函数没有返回值
+    pub(super) fn call_runtime(&mut self, function: RuntimeFunction, name: &str, args: &[Val]) -> Result<Option<i32>, TrapReason> {
+        let output_error = |e: std::io::Error| TrapReason::Output(e.to_string());
+        let result = match function {
+            RuntimeFunction::GetInt => Some(self.read_int(name)?),
+            RuntimeFunction::GetCh => Some(self.io.read_char()),
+            RuntimeFunction::GetArray => {
+                let array = pointer_arg(name, args, 0)?;
+                let len = self.read_int(name)?;
+                for i in 0..len {
+                    let value = self.read_int(name)?;
+                    self.memory.store(array.offset_by(i as i64), Val::Int(value))?;
+                }
+                Some(len)
+            },
+            RuntimeFunction::PutInt => {
+                self.io.write_int(int_arg(name, args, 0)?).map_err(output_error)?;
+                None
+            },
+            RuntimeFunction::PutCh => {
+                self.io.write_char(int_arg(name, args, 0)?).map_err(output_error)?;
+                None
+            },
+            RuntimeFunction::PutArray => {
+                let len = int_arg(name, args, 0)?;
+                let array = pointer_arg(name, args, 1)?;
+                let mut values = Vec::new();
+                for i in 0..len {
+                    match self.memory.load(array.offset_by(i as i64))? {
+                        Val::Int(value) => values.push(value),
+                        Val::Ptr(_) => return Err(TrapReason::Unsupported(String::from("printing a pointer")))
+                    }
+                }
+                self.io.write_array(&values).map_err(output_error)?;
+                None
+            },
+            RuntimeFunction::StartTime | RuntimeFunction::StopTime => None
+        };
+        Ok(result)
+    }
+
+    fn read_int(&mut self, name: &str) -> Result<i32, TrapReason> {
+        self.io.read_int().ok_or_else(|| TrapReason::InvalidInput(format!("@{}", name)))
+    }
+}
+
+/// 取出第 index 个整数实参
+fn int_arg(function: &str, args: &[Val], index: usize) -> Result<i32, TrapReason> {
+    match args.get(index) {
+        Some(Val::Int(value)) => Ok(*value),
+        _ => Err(TrapReason::Unsupported(format!("calling @{} with these arguments", function)))
+    }
+}
+
+/// 取出第 index 个指针实参
+fn pointer_arg(function: &str, args: &[Val], index: usize) -> Result<Pointer, TrapReason> {
+    match args.get(index) {
+        Some(Val::Ptr(pointer)) => Ok(*pointer),
+        _ => Err(TrapReason::Unsupported(format!("calling @{} with these arguments", function)))
+    }
+}
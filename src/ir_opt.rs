@@ -0,0 +1,188 @@
+use koopa::ir::{dfg::DataFlowGraph, BasicBlock, FunctionData, Program, Value, ValueKind};
+use koopa::ir::builder::ValueBuilder;
+use koopa::opt::{Pass, PassManager};
+
+// Koopa IR 上的优化 pass，每个 pass 放在 src/ir_opt/ 目录下的单独文件中
+mod constant_fold;
+mod dead_code;
+mod cfg_simplify;
+mod dominator;
+mod value_numbering;
+mod sccp;
+mod inline;
+mod loops;
+mod licm;
+mod tail_call;
+
+pub use constant_fold::ConstantFold;
+pub use dead_code::DeadCodeElimination;
+pub use cfg_simplify::CfgSimplify;
+pub use value_numbering::ValueNumbering;
+pub use sccp::Sccp;
+pub use inline::Inliner;
+pub use licm::Licm;
+pub use tail_call::TailRecursion;
+pub use constant_fold::evaluate_binary;
+
+/// 优化相关的选项
+#[derive(Debug, Clone)]
+pub struct OptConfig {
+    /// 优化等级，为 0 时不做任何优化
+    pub level: u32,
+    /// 被内联函数的最大指令数，为 0 时不做内联
+    pub inline_limit: usize
+}
+
+impl Default for OptConfig {
+    fn default() -> Self {
+        Self { level: 1, inline_limit: 40 }
+    }
+}
+
+/// 对整个程序运行优化 pass
+pub fn optimize_program(program: &mut Program, config: &OptConfig) {
+    if config.level == 0 {
+        return;
+    }
+    let mut passman = PassManager::new();
+    // 内联之后实参常量会传播进被内联的函数体，所以放在最前面
+    passman.register(Pass::Module(Box::new(Inliner::new(config.inline_limit))));
+    // 尾递归改写成循环之后，后面的 LICM 等循环优化也能作用于它
+    passman.register(Pass::Function(Box::new(TailRecursion)));
+    passman.register(Pass::Function(Box::new(ConstantFold)));
+    passman.register(Pass::Function(Box::new(ValueNumbering)));
+    // 条件已知的分支改写为 jump 之后，死掉的分支交给后面的 DCE 和 CFG 化简删除
+    passman.register(Pass::Function(Box::new(Sccp)));
+    passman.register(Pass::Function(Box::new(DeadCodeElimination)));
+    passman.register(Pass::Function(Box::new(CfgSimplify)));
+    // 循环结构在 CFG 化简之后才稳定，外提之后的指令再交给后面的 pass 折叠
+    passman.register(Pass::Function(Box::new(Licm)));
+    // 合并基本块后可能出现新的折叠机会和死代码
+    passman.register(Pass::Function(Box::new(ConstantFold)));
+    passman.register(Pass::Function(Box::new(DeadCodeElimination)));
+    passman.run_passes(program);
+}
+
+/// 按布局顺序返回函数中的所有指令
+fn layout_insts(function_data: &FunctionData) -> Vec<Value> {
+    function_data.layout().bbs().nodes()
+        .flat_map(|node| node.insts().keys().copied())
+        .collect()
+}
+
+/// 返回某个基本块在控制流图中的后继（由末尾的 br/jump 指令决定）
+pub(crate) fn successors(function_data: &FunctionData, bb: BasicBlock) -> Vec<BasicBlock> {
+    let Some(node) = function_data.layout().bbs().node(&bb) else {
+        return Vec::new();
+    };
+    match node.insts().back_key().map(|&inst| function_data.dfg().value(inst).kind()) {
+        Some(ValueKind::Branch(branch)) => vec![branch.true_bb(), branch.false_bb()],
+        Some(ValueKind::Jump(jump)) => vec![jump.target()],
+        _ => Vec::new()
+    }
+}
+
+/// 对某条指令的每个操作数调用 f，f 可以就地修改操作数
+fn map_operands(kind: &mut ValueKind, mut f: impl FnMut(&mut Value)) {
+    match kind {
+        ValueKind::Aggregate(a) => a.elems_mut().iter_mut().for_each(&mut f),
+        ValueKind::GlobalAlloc(g) => f(g.init_mut()),
+        ValueKind::Load(l) => f(l.src_mut()),
+        ValueKind::Store(s) => {
+            f(s.value_mut());
+            f(s.dest_mut());
+        },
+        ValueKind::GetPtr(g) => {
+            f(g.src_mut());
+            f(g.index_mut());
+        },
+        ValueKind::GetElemPtr(g) => {
+            f(g.src_mut());
+            f(g.index_mut());
+        },
+        ValueKind::Binary(b) => {
+            f(b.lhs_mut());
+            f(b.rhs_mut());
+        },
+        ValueKind::Branch(b) => {
+            f(b.cond_mut());
+            b.true_args_mut().iter_mut().for_each(&mut f);
+            b.false_args_mut().iter_mut().for_each(&mut f);
+        },
+        ValueKind::Jump(j) => j.args_mut().iter_mut().for_each(&mut f),
+        ValueKind::Call(c) => c.args_mut().iter_mut().for_each(&mut f),
+        ValueKind::Return(r) => {
+            if let Some(v) = r.value_mut() {
+                f(v);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// 将某条指令中对 old 的使用替换为 new
+fn replace_operand(kind: &mut ValueKind, old: Value, new: Value) {
+    map_operands(kind, |v| if *v == old { *v = new });
+}
+
+/// 将所有对 old 的使用替换为 new
+/// 替换完成后 old 不再被任何值使用，可以安全地删除
+fn replace_all_uses(dfg: &mut DataFlowGraph, old: Value, new: Value) {
+    let users: Vec<Value> = dfg.value(old).used_by().iter().copied().collect();
+    for user in users {
+        let mut data = dfg.value(user).clone();
+        replace_operand(data.kind_mut(), old, new);
+        dfg.replace_value_with(user).raw(data);
+    }
+}
+
+/// 从函数中彻底删除一条指令（布局和数据流图）
+/// 调用前必须保证该指令已经没有使用者
+fn remove_instruction(function_data: &mut FunctionData, inst: Value) {
+    if let Some(bb) = function_data.layout().parent_bb(inst) {
+        function_data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+    }
+    function_data.dfg_mut().remove_value(inst);
+}
+
+/// 获得某个值对应的整数常量（如果它是整数常量的话）
+fn integer_value(dfg: &DataFlowGraph, value: Value) -> Option<i32> {
+    if value.is_global() {
+        return None;
+    }
+    match dfg.value(value).kind() {
+        ValueKind::Integer(i) => Some(i.value()),
+        _ => None
+    }
+}
+
+/// 判断一个指针是否是地址从未逃逸的局部变量
+/// 这样的 alloc 只会被直接 load/store，不可能和其他任何指针指向同一块内存
+fn is_private_alloc(dfg: &DataFlowGraph, ptr: Value) -> bool {
+    if ptr.is_global() {
+        return false;
+    }
+    let data = dfg.value(ptr);
+    matches!(data.kind(), ValueKind::Alloc(_)) && data.used_by().iter().all(|&user| match dfg.value(user).kind() {
+        ValueKind::Load(_) => true,
+        ValueKind::Store(store) => store.dest() == ptr && store.value() != ptr,
+        _ => false
+    })
+}
+
+/// 判断一个 alloc（局部或全局）是否是独立的内存对象
+fn is_memory_object(dfg: &DataFlowGraph, ptr: Value) -> bool {
+    ptr.is_global() || matches!(dfg.value(ptr).kind(), ValueKind::Alloc(_))
+}
+
+/// 保守地判断两个指针是否可能指向同一块内存
+fn may_alias(dfg: &DataFlowGraph, a: Value, b: Value) -> bool {
+    if a == b {
+        return true;
+    }
+    if is_private_alloc(dfg, a) || is_private_alloc(dfg, b) {
+        return false;
+    }
+    // 两个不同的内存对象互不重叠
+    !(is_memory_object(dfg, a) && is_memory_object(dfg, b))
+}
@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+
+use koopa::ir::{BasicBlock, Function, FunctionData, Value, ValueKind};
+use koopa::opt::FunctionPass;
+
+use super::{remove_instruction, replace_all_uses, successors};
+
+/// 控制流图化简
+/// - 删除从入口不可达的基本块
+/// - 合并直线形的基本块链：A 以 jump B 结尾，且 B 只有 A 一个前驱时，把 B 并入 A
+pub struct CfgSimplify;
+
+impl FunctionPass for CfgSimplify {
+    fn run_on(&mut self, _: Function, data: &mut FunctionData) {
+        if data.layout().entry_bb().is_none() {
+            // 函数声明，没有函数体
+            return;
+        }
+        self.remove_unreachable_blocks(data);
+        while self.merge_one_chain(data) {}
+    }
+}
+
+impl CfgSimplify {
+    /// 从入口开始遍历控制流图，删除所有不可达的基本块
+    fn remove_unreachable_blocks(&self, data: &mut FunctionData) {
+        let entry = data.layout().entry_bb().unwrap();
+        let mut reachable = HashSet::from([entry]);
+        let mut stack = vec![entry];
+        while let Some(bb) = stack.pop() {
+            for succ in successors(data, bb) {
+                if reachable.insert(succ) {
+                    stack.push(succ);
+                }
+            }
+        }
+        let unreachable: Vec<BasicBlock> = data.layout().bbs().keys()
+            .filter(|bb| !reachable.contains(bb))
+            .copied()
+            .collect();
+        if unreachable.is_empty() {
+            return;
+        }
+        // 不可达块中的值只可能被不可达块使用（SSA 的支配性质），
+        // 所以不断删除其中已经没有使用者的指令，直到全部删完
+        let mut insts: Vec<Value> = unreachable.iter()
+            .flat_map(|&bb| data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect::<Vec<_>>())
+            .collect();
+        while !insts.is_empty() {
+            let before = insts.len();
+            insts.retain(|&inst| {
+                if data.dfg().value(inst).used_by().is_empty() {
+                    remove_instruction(data, inst);
+                    false
+                } else {
+                    true
+                }
+            });
+            // 理论上不会出现无法删除的循环引用；万一出现，保留剩下的不可达块而不是删出悬空的使用
+            if insts.len() == before {
+                return;
+            }
+        }
+        for bb in unreachable {
+            data.layout_mut().bbs_mut().remove(&bb);
+            data.dfg_mut().remove_bb(bb);
+        }
+    }
+
+    /// 寻找并合并一对可以合并的基本块，返回是否做了合并
+    fn merge_one_chain(&self, data: &mut FunctionData) -> bool {
+        let entry = data.layout().entry_bb().unwrap();
+        let bbs: Vec<BasicBlock> = data.layout().bbs().keys().copied().collect();
+        for pred in bbs {
+            let Some(&jump) = data.layout().bbs().node(&pred).unwrap().insts().back_key() else {
+                continue;
+            };
+            let (target, args) = match data.dfg().value(jump).kind() {
+                ValueKind::Jump(j) => (j.target(), j.args().to_vec()),
+                _ => continue
+            };
+            // 入口块不能被合并；目标只能有这一条 jump 作为前驱
+            if target == entry || target == pred || data.dfg().bb(target).used_by().len() != 1 {
+                continue;
+            }
+            // 用 jump 的参数替换目标块的参数
+            let params = data.dfg().bb(target).params().to_vec();
+            for (param, arg) in params.into_iter().zip(args) {
+                replace_all_uses(data.dfg_mut(), param, arg);
+            }
+            remove_instruction(data, jump);
+            // 把目标块的指令依次移动到前驱块末尾
+            while let Some((inst, _)) = data.layout_mut().bb_mut(target).insts_mut().pop_front() {
+                data.layout_mut().bb_mut(pred).insts_mut().push_key_back(inst).unwrap();
+            }
+            data.layout_mut().bbs_mut().remove(&target);
+            data.dfg_mut().remove_bb(target);
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,121 @@
+use koopa::ir::{builder::ValueBuilder, BinaryOp, Function, FunctionData, Value, ValueKind};
+use koopa::opt::FunctionPass;
+
+use super::{integer_value, layout_insts, remove_instruction, replace_all_uses};
+
+/// 常量折叠与代数化简
+/// - 两个操作数都是整数常量的二元运算，直接在编译期求值
+/// - x+0、x*1、x*0、x-x、x==x 等恒等式化简为已有的值或常量
+pub struct ConstantFold;
+
+/// 一条二元运算指令化简后的结果
+enum Folded {
+    // 化简为一个整数常量
+    Const(i32),
+    // 化简为已经存在的某个值
+    Value(Value)
+}
+
+impl FunctionPass for ConstantFold {
+    fn run_on(&mut self, _: Function, data: &mut FunctionData) {
+        // 化简后可能产生新的常量操作数，反复运行直到不再变化
+        while self.fold_once(data) {}
+    }
+}
+
+impl ConstantFold {
+    /// 按布局顺序扫描一遍所有指令，返回是否做了修改
+    fn fold_once(&self, data: &mut FunctionData) -> bool {
+        let mut changed = false;
+        for inst in layout_insts(data) {
+            let folded = match data.dfg().value(inst).kind() {
+                ValueKind::Binary(binary) => {
+                    let lhs = binary.lhs();
+                    let rhs = binary.rhs();
+                    let dfg = data.dfg();
+                    match (integer_value(dfg, lhs), integer_value(dfg, rhs)) {
+                        (Some(l), Some(r)) => evaluate_binary(binary.op(), l, r).map(Folded::Const),
+                        (l, r) => simplify_binary(binary.op(), lhs, l, rhs, r)
+                    }
+                },
+                _ => None
+            };
+            if let Some(folded) = folded {
+                let new = match folded {
+                    Folded::Const(i) => data.dfg_mut().new_value().integer(i),
+                    Folded::Value(v) => v
+                };
+                replace_all_uses(data.dfg_mut(), inst, new);
+                remove_instruction(data, inst);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// 在编译期计算一条二元运算的结果
+/// 加减乘采用 32 位补码回绕语义（与 RV32 的行为一致）
+/// 除数为 0 时不做计算，返回 None，留到运行时处理
+pub fn evaluate_binary(op: BinaryOp, l: i32, r: i32) -> Option<i32> {
+    let result = match op {
+        BinaryOp::NotEq => (l != r) as i32,
+        BinaryOp::Eq => (l == r) as i32,
+        BinaryOp::Gt => (l > r) as i32,
+        BinaryOp::Lt => (l < r) as i32,
+        BinaryOp::Ge => (l >= r) as i32,
+        BinaryOp::Le => (l <= r) as i32,
+        BinaryOp::Add => l.wrapping_add(r),
+        BinaryOp::Sub => l.wrapping_sub(r),
+        BinaryOp::Mul => l.wrapping_mul(r),
+        BinaryOp::Div => {
+            if r == 0 {
+                return None;
+            }
+            // i32::MIN / -1 在 RV32 上的结果是 i32::MIN
+            l.wrapping_div(r)
+        },
+        BinaryOp::Mod => {
+            if r == 0 {
+                return None;
+            }
+            l.wrapping_rem(r)
+        },
+        BinaryOp::And => l & r,
+        BinaryOp::Or => l | r,
+        BinaryOp::Xor => l ^ r,
+        // 移位量只取低 5 位，与 RV32 的 sll/srl/sra 一致
+        BinaryOp::Shl => l.wrapping_shl(r as u32),
+        BinaryOp::Shr => (l as u32).wrapping_shr(r as u32) as i32,
+        BinaryOp::Sar => l.wrapping_shr(r as u32)
+    };
+    Some(result)
+}
+
+/// 利用代数恒等式化简至少有一个操作数不是常量的二元运算
+/// l、r 为对应操作数的常量值（如果是常量的话）
+fn simplify_binary(op: BinaryOp, lhs: Value, l: Option<i32>, rhs: Value, r: Option<i32>) -> Option<Folded> {
+    // 两个操作数是同一个值
+    if lhs == rhs {
+        match op {
+            BinaryOp::Sub | BinaryOp::Xor | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::Gt => return Some(Folded::Const(0)),
+            BinaryOp::Eq | BinaryOp::Le | BinaryOp::Ge => return Some(Folded::Const(1)),
+            BinaryOp::And | BinaryOp::Or => return Some(Folded::Value(lhs)),
+            _ => {}
+        }
+    }
+    match (op, l, r) {
+        // x+0、0+x、x-0、x|0、x^0、移位 0 位
+        (BinaryOp::Add, _, Some(0)) | (BinaryOp::Sub, _, Some(0)) | (BinaryOp::Or, _, Some(0))
+        | (BinaryOp::Xor, _, Some(0)) | (BinaryOp::Shl, _, Some(0)) | (BinaryOp::Shr, _, Some(0))
+        | (BinaryOp::Sar, _, Some(0)) => Some(Folded::Value(lhs)),
+        (BinaryOp::Add, Some(0), _) | (BinaryOp::Or, Some(0), _) | (BinaryOp::Xor, Some(0), _) => Some(Folded::Value(rhs)),
+        // x*1、1*x、x/1
+        (BinaryOp::Mul, _, Some(1)) | (BinaryOp::Div, _, Some(1)) => Some(Folded::Value(lhs)),
+        (BinaryOp::Mul, Some(1), _) => Some(Folded::Value(rhs)),
+        // x*0、0*x、x&0、0&x、x%1
+        (BinaryOp::Mul, _, Some(0)) | (BinaryOp::Mul, Some(0), _) | (BinaryOp::And, _, Some(0))
+        | (BinaryOp::And, Some(0), _) | (BinaryOp::Mod, _, Some(1)) => Some(Folded::Const(0)),
+        _ => None
+    }
+}
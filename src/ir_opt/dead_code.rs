@@ -0,0 +1,67 @@
+use koopa::ir::{Function, FunctionData, Value, ValueKind};
+use koopa::opt::FunctionPass;
+
+use super::{layout_insts, remove_instruction};
+
+/// 死代码删除
+/// - 删除没有任何使用者、也没有副作用的指令（二元运算、load、指针计算等）
+/// - 删除只被写入、从未被读取的局部变量（alloc 及其全部 store）
+///
+/// 一条指令被删除后，它的操作数可能也随之失去使用者，因此用工作表反复处理。
+pub struct DeadCodeElimination;
+
+impl FunctionPass for DeadCodeElimination {
+    fn run_on(&mut self, _: Function, data: &mut FunctionData) {
+        // 工作表按布局逆序弹出，尽量先处理后面的使用者
+        let mut worklist = layout_insts(data);
+        while let Some(inst) = worklist.pop() {
+            // 已经被删除的指令
+            if !data.dfg().values().contains_key(&inst) || data.layout().parent_bb(inst).is_none() {
+                continue;
+            }
+            let removed = if self.is_dead(data, inst) {
+                vec![inst]
+            } else if let Some(stores) = self.write_only_alloc(data, inst) {
+                // 先删除 store，再删除 alloc 本身
+                let mut removed = stores;
+                removed.push(inst);
+                removed
+            } else {
+                continue;
+            };
+            for one in removed {
+                // 被删除指令的操作数可能会变成死代码
+                let operands: Vec<Value> = data.dfg().value(one).kind().value_uses().collect();
+                remove_instruction(data, one);
+                worklist.extend(operands.into_iter().filter(|v| !v.is_global()));
+            }
+        }
+    }
+}
+
+impl DeadCodeElimination {
+    /// 判断一条指令是否没有副作用且没有使用者
+    fn is_dead(&self, data: &FunctionData, inst: Value) -> bool {
+        let value = data.dfg().value(inst);
+        value.used_by().is_empty() && matches!(value.kind(),
+            ValueKind::Binary(_) | ValueKind::Load(_) | ValueKind::Alloc(_) | ValueKind::GetPtr(_) | ValueKind::GetElemPtr(_))
+    }
+
+    /// 如果 inst 是一个只被 store 写入的 alloc，返回所有写入它的 store 指令
+    fn write_only_alloc(&self, data: &FunctionData, inst: Value) -> Option<Vec<Value>> {
+        let dfg = data.dfg();
+        let value = dfg.value(inst);
+        if !matches!(value.kind(), ValueKind::Alloc(_)) {
+            return None;
+        }
+        let mut stores = Vec::new();
+        for &user in value.used_by() {
+            match dfg.value(user).kind() {
+                // 被存入别处的是指针本身，不能删除
+                ValueKind::Store(store) if store.dest() == inst && store.value() != inst => stores.push(user),
+                _ => return None
+            }
+        }
+        Some(stores)
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{BasicBlock, FunctionData};
+
+use super::successors;
+
+/// 函数的支配树
+/// 使用 Cooper-Harvey-Kennedy 迭代算法，在逆后序上反复求交直到不动点。
+/// 只包含从入口可达的基本块。
+pub struct DominatorTree {
+    /// 入口可达基本块的逆后序
+    order: Vec<BasicBlock>,
+    /// 直接支配者（入口没有直接支配者）
+    idom: HashMap<BasicBlock, BasicBlock>,
+    /// 支配树上的孩子，按逆后序排列
+    children: HashMap<BasicBlock, Vec<BasicBlock>>,
+    /// 控制流图上的前驱（只统计可达的前驱，去重）
+    preds: HashMap<BasicBlock, Vec<BasicBlock>>
+}
+
+impl DominatorTree {
+    /// 为一个有函数体的函数计算支配树
+    pub fn new(data: &FunctionData) -> Self {
+        let entry = data.layout().entry_bb().expect("函数声明没有支配树");
+        let order = Self::reverse_postorder(data, entry);
+        let index: HashMap<BasicBlock, usize> = order.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+        let mut preds: HashMap<BasicBlock, Vec<BasicBlock>> = order.iter().map(|&bb| (bb, Vec::new())).collect();
+        for &bb in &order {
+            for succ in successors(data, bb) {
+                let list = preds.get_mut(&succ).unwrap();
+                if !list.contains(&bb) {
+                    list.push(bb);
+                }
+            }
+        }
+
+        // 用逆后序编号表示支配者，便于求交
+        let mut doms: Vec<Option<usize>> = vec![None; order.len()];
+        doms[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 1..order.len() {
+                let mut new_idom: Option<usize> = None;
+                for pred in &preds[&order[i]] {
+                    let p = index[pred];
+                    if doms[p].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => p,
+                        Some(current) => Self::intersect(&doms, p, current)
+                    });
+                }
+                if doms[i] != new_idom {
+                    doms[i] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut idom = HashMap::new();
+        let mut children: HashMap<BasicBlock, Vec<BasicBlock>> = order.iter().map(|&bb| (bb, Vec::new())).collect();
+        for (i, &bb) in order.iter().enumerate().skip(1) {
+            let parent = order[doms[i].unwrap()];
+            idom.insert(bb, parent);
+            children.get_mut(&parent).unwrap().push(bb);
+        }
+        Self { order, idom, children, preds }
+    }
+
+    /// 沿着支配树向上走，找到两个节点最近的公共支配者
+    fn intersect(doms: &[Option<usize>], mut a: usize, mut b: usize) -> usize {
+        while a != b {
+            while a > b {
+                a = doms[a].unwrap();
+            }
+            while b > a {
+                b = doms[b].unwrap();
+            }
+        }
+        a
+    }
+
+    /// 从入口开始深度优先遍历，返回可达基本块的逆后序
+    fn reverse_postorder(data: &FunctionData, entry: BasicBlock) -> Vec<BasicBlock> {
+        let mut visited = HashSet::from([entry]);
+        let mut postorder = Vec::new();
+        // 栈中存放（基本块，下一个要访问的后继下标）
+        let mut stack = vec![(entry, 0)];
+        while let Some((bb, next)) = stack.pop() {
+            let succs = successors(data, bb);
+            if next < succs.len() {
+                stack.push((bb, next + 1));
+                if visited.insert(succs[next]) {
+                    stack.push((succs[next], 0));
+                }
+            } else {
+                postorder.push(bb);
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    /// 入口基本块
+    pub fn entry(&self) -> BasicBlock {
+        self.order[0]
+    }
+
+    /// 入口可达的所有基本块，按逆后序排列
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.order
+    }
+
+    /// 判断 a 是否支配 b（每个基本块都支配自身）
+    /// 不可达的基本块不被任何块支配
+    pub fn dominates(&self, a: BasicBlock, b: BasicBlock) -> bool {
+        if !self.idom.contains_key(&b) && b != self.entry() {
+            return false;
+        }
+        let mut current = b;
+        loop {
+            if current == a {
+                return true;
+            }
+            match self.idom.get(&current) {
+                Some(&parent) => current = parent,
+                None => return false
+            }
+        }
+    }
+
+    /// 支配树上的孩子
+    pub fn children(&self, bb: BasicBlock) -> &[BasicBlock] {
+        self.children.get(&bb).map_or(&[], |c| c.as_slice())
+    }
+
+    /// 控制流图上的前驱
+    pub fn preds(&self, bb: BasicBlock) -> &[BasicBlock] {
+        self.preds.get(&bb).map_or(&[], |p| p.as_slice())
+    }
+}
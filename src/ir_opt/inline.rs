@@ -0,0 +1,252 @@
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{builder::{BasicBlockBuilder, LocalInstBuilder, ValueBuilder}, BasicBlock, Function, FunctionData, Program, Value, ValueKind};
+use koopa::ir::entities::ValueData;
+use koopa::opt::ModulePass;
+
+use super::dominator::DominatorTree;
+use super::{layout_insts, map_operands, remove_instruction, replace_all_uses};
+
+/// 函数内联
+/// - 只内联指令数不超过 limit 的非递归函数
+/// - 按调用图自底向上处理，被调用者先完成自身的内联，再被内联进调用者
+/// - 被调用者中的基本块和值被复制到调用者中，基本块及其参数重新命名，
+///   ret 改写为跳转到调用点之后的续块（返回值作为续块的参数）
+pub struct Inliner {
+    limit: usize
+}
+
+/// 从被调用者中复制出来的函数体，复制时不再需要借用被调用者
+struct CalleeBody {
+    name: String,
+    params: Vec<Value>,
+    /// 逆后序排列的基本块：（基本块、名称、参数）
+    blocks: Vec<(BasicBlock, Option<String>, Vec<Value>)>,
+    /// 每个基本块中的指令
+    insts: HashMap<BasicBlock, Vec<Value>>,
+    /// 函数体中用到的所有局部值
+    values: HashMap<Value, ValueData>
+}
+
+impl Inliner {
+    pub fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+
+    /// 返回函数直接调用的所有函数（按出现顺序去重）
+    fn callees(data: &FunctionData) -> Vec<Function> {
+        let mut result = Vec::new();
+        for inst in layout_insts(data) {
+            if let ValueKind::Call(call) = data.dfg().value(inst).kind()
+                && !result.contains(&call.callee()) {
+                result.push(call.callee());
+            }
+        }
+        result
+    }
+
+    /// 找出所有处在调用环上的（直接或间接递归的）函数
+    fn recursive_functions(call_graph: &HashMap<Function, Vec<Function>>) -> HashSet<Function> {
+        let mut result = HashSet::new();
+        for &func in call_graph.keys() {
+            let mut visited = HashSet::new();
+            let mut stack = call_graph[&func].clone();
+            while let Some(callee) = stack.pop() {
+                if callee == func {
+                    result.insert(func);
+                    break;
+                }
+                if visited.insert(callee) {
+                    stack.extend(call_graph.get(&callee).into_iter().flatten().copied());
+                }
+            }
+        }
+        result
+    }
+
+    /// 调用图的后序：被调用者排在调用者之前
+    fn bottom_up_order(program: &Program, call_graph: &HashMap<Function, Vec<Function>>) -> Vec<Function> {
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        for &root in program.func_layout() {
+            if !visited.insert(root) {
+                continue;
+            }
+            // 栈中存放（函数，下一个要访问的被调用者下标）
+            let mut stack = vec![(root, 0)];
+            while let Some((func, next)) = stack.pop() {
+                let callees = call_graph.get(&func).map_or(&[][..], |c| c.as_slice());
+                if next < callees.len() {
+                    stack.push((func, next + 1));
+                    if visited.insert(callees[next]) {
+                        stack.push((callees[next], 0));
+                    }
+                } else {
+                    order.push(func);
+                }
+            }
+        }
+        order
+    }
+
+    /// 复制被调用者的函数体
+    fn copy_body(data: &FunctionData) -> CalleeBody {
+        let dom = DominatorTree::new(data);
+        let blocks = dom.blocks().iter().map(|&bb| {
+            let bb_data = data.dfg().bb(bb);
+            (bb, bb_data.name().clone(), bb_data.params().to_vec())
+        }).collect();
+        let insts = dom.blocks().iter().map(|&bb| {
+            (bb, data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect())
+        }).collect();
+        CalleeBody {
+            name: data.name()[1..].to_string(),
+            params: data.params().to_vec(),
+            blocks,
+            insts,
+            values: data.dfg().values().iter().map(|(&v, d)| (v, d.clone())).collect()
+        }
+    }
+
+    /// 在调用者中展开一处调用
+    fn inline_call(&self, caller: &mut FunctionData, call: Value, body: &CalleeBody) {
+        let call_bb = caller.layout().parent_bb(call).unwrap();
+        let (args, ret_ty) = match caller.dfg().value(call).kind() {
+            ValueKind::Call(c) => (c.args().to_vec(), caller.dfg().value(call).ty().clone()),
+            _ => unreachable!()
+        };
+
+        // 把调用点之后的指令移入续块，返回值成为续块的参数
+        let cont_params = if ret_ty.is_unit() { vec![] } else { vec![(Some(format!("%{}_ret", body.name)), ret_ty)] };
+        let cont = caller.dfg_mut().new_bb().basic_block_with_param_names(Some(format!("%{}_cont", body.name)), cont_params);
+        caller.layout_mut().bbs_mut().cursor_mut(call_bb).insert_key_after(cont).unwrap();
+        let tail: Vec<Value> = caller.layout().bbs().node(&call_bb).unwrap().insts().keys()
+            .skip_while(|&&inst| inst != call)
+            .skip(1)
+            .copied()
+            .collect();
+        // 调用点之后的局部变量不跟着移入续块，和被内联的局部变量一样放在入口块
+        let entry = caller.layout().entry_bb().unwrap();
+        for inst in tail {
+            let is_alloc = matches!(caller.dfg().value(inst).kind(), ValueKind::Alloc(_));
+            if is_alloc && call_bb == entry {
+                continue;
+            }
+            caller.layout_mut().bb_mut(call_bb).insts_mut().remove(&inst);
+            if is_alloc {
+                caller.layout_mut().bb_mut(entry).insts_mut().push_key_front(inst).unwrap();
+            } else {
+                caller.layout_mut().bb_mut(cont).insts_mut().push_key_back(inst).unwrap();
+            }
+        }
+
+        // 创建被复制的基本块，并建立新旧基本块、参数之间的映射
+        let mut value_map: HashMap<Value, Value> = body.params.iter().copied().zip(args).collect();
+        let mut bb_map = HashMap::new();
+        let mut cursor_bb = call_bb;
+        for (bb, name, params) in &body.blocks {
+            let bb_name = name.as_ref().map(|n| format!("%{}_{}", body.name, &n[1..]));
+            let param_info = params.iter().map(|p| {
+                let data = &body.values[p];
+                (data.name().as_ref().map(|n| format!("%{}_{}", body.name, &n[1..])), data.ty().clone())
+            }).collect();
+            let new_bb = caller.dfg_mut().new_bb().basic_block_with_param_names(bb_name, param_info);
+            caller.layout_mut().bbs_mut().cursor_mut(cursor_bb).insert_key_after(new_bb).unwrap();
+            cursor_bb = new_bb;
+            value_map.extend(params.iter().copied().zip(caller.dfg().bb(new_bb).params().to_vec()));
+            bb_map.insert(*bb, new_bb);
+        }
+
+        // 按逆后序复制指令，保证操作数总是先于使用者被创建
+        for (bb, _, _) in &body.blocks {
+            let new_bb = bb_map[bb];
+            for inst in &body.insts[bb] {
+                let mut data = body.values[inst].clone();
+                map_operands(data.kind_mut(), |v| *v = Self::map_value(caller, body, &mut value_map, *v));
+                match data.kind_mut() {
+                    ValueKind::Branch(branch) => {
+                        *branch.true_bb_mut() = bb_map[&branch.true_bb()];
+                        *branch.false_bb_mut() = bb_map[&branch.false_bb()];
+                    },
+                    ValueKind::Jump(jump) => *jump.target_mut() = bb_map[&jump.target()],
+                    _ => {}
+                }
+                let new_inst = match data.kind() {
+                    // ret 改写为跳转到续块
+                    ValueKind::Return(ret) => {
+                        let args = ret.value().into_iter().collect();
+                        caller.dfg_mut().new_value().jump_with_args(cont, args)
+                    },
+                    _ => caller.dfg_mut().new_value().raw(data)
+                };
+                // 和基本块一样加上被调用者的名字作为前缀，同一个函数被内联多次时名字由生成器去重
+                let name = body.values[inst].name().as_ref().map(|n| format!("%{}_{}", body.name, &n[1..]));
+                caller.dfg_mut().set_value_name(new_inst, name);
+                if matches!(caller.dfg().value(new_inst).kind(), ValueKind::Alloc(_)) {
+                    // 局部变量统一放到调用者的入口块
+                    caller.layout_mut().bb_mut(entry).insts_mut().push_key_front(new_inst).unwrap();
+                } else {
+                    caller.layout_mut().bb_mut(new_bb).insts_mut().push_key_back(new_inst).unwrap();
+                }
+                value_map.insert(*inst, new_inst);
+            }
+        }
+
+        // 用续块参数替换调用结果，再把调用换成跳转到被复制的入口块
+        if let Some(&ret) = caller.dfg().bb(cont).params().first() {
+            replace_all_uses(caller.dfg_mut(), call, ret);
+        }
+        remove_instruction(caller, call);
+        let jump = caller.dfg_mut().new_value().jump(bb_map[&body.blocks[0].0]);
+        caller.layout_mut().bb_mut(call_bb).insts_mut().push_key_back(jump).unwrap();
+    }
+
+    /// 将被调用者中的值映射为调用者中的值
+    /// 全局值保持不变；常量在调用者中重新创建
+    fn map_value(caller: &mut FunctionData, body: &CalleeBody, value_map: &mut HashMap<Value, Value>, value: Value) -> Value {
+        if value.is_global() {
+            return value;
+        }
+        if let Some(&mapped) = value_map.get(&value) {
+            return mapped;
+        }
+        let mut data = body.values[&value].clone();
+        map_operands(data.kind_mut(), |v| *v = Self::map_value(caller, body, value_map, *v));
+        let mapped = caller.dfg_mut().new_value().raw(data);
+        value_map.insert(value, mapped);
+        mapped
+    }
+}
+
+impl ModulePass for Inliner {
+    fn run_on(&mut self, program: &mut Program) {
+        if self.limit == 0 {
+            return;
+        }
+        let call_graph: HashMap<Function, Vec<Function>> = program.func_layout().iter()
+            .map(|&f| (f, Self::callees(program.func(f))))
+            .collect();
+        let recursive = Self::recursive_functions(&call_graph);
+
+        for caller in Self::bottom_up_order(program, &call_graph) {
+            let calls: Vec<(Value, Function)> = {
+                let data = program.func(caller);
+                layout_insts(data).into_iter().filter_map(|inst| match data.dfg().value(inst).kind() {
+                    ValueKind::Call(call) => Some((inst, call.callee())),
+                    _ => None
+                }).collect()
+            };
+            for (call, callee) in calls {
+                let callee_data = program.func(callee);
+                if callee == caller || recursive.contains(&callee) || callee_data.layout().entry_bb().is_none() {
+                    continue;
+                }
+                if layout_insts(callee_data).len() > self.limit {
+                    continue;
+                }
+                let body = Self::copy_body(callee_data);
+                self.inline_call(program.func_mut(caller), call, &body);
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+
+use koopa::ir::{builder::{BasicBlockBuilder, LocalInstBuilder, ValueBuilder}, dfg::DataFlowGraph, BasicBlock, BinaryOp, Function, FunctionData, Type, Value, ValueKind};
+use koopa::opt::FunctionPass;
+
+use super::dominator::DominatorTree;
+use super::loops::{find_loops, NaturalLoop};
+use super::{integer_value, is_memory_object, is_private_alloc, may_alias, successors};
+
+/// 循环不变量外提
+/// - 为每个循环准备一个前置块（循环外进入循环头的唯一入口），必要时新建
+/// - 操作数都在循环外定义的二元运算移到前置块中
+/// - 从局部变量或全局变量中 load，且循环中没有可能写入该地址的 store 或函数调用时，同样外提
+/// - 内层循环先处理，外提到内层前置块的指令之后还可以继续外提到外层循环之外
+pub struct Licm;
+
+impl FunctionPass for Licm {
+    fn run_on(&mut self, _: Function, data: &mut FunctionData) {
+        if data.layout().entry_bb().is_none() {
+            return;
+        }
+        let mut done = HashSet::new();
+        // 插入前置块会改变控制流图，所以每处理完一个循环就重新计算支配树和循环
+        loop {
+            let dom = DominatorTree::new(data);
+            let Some(natural_loop) = find_loops(data, &dom).into_iter().find(|l| !done.contains(&l.header)) else {
+                break;
+            };
+            done.insert(natural_loop.header);
+            let preheader = self.preheader(data, &dom, &natural_loop);
+            self.hoist(data, &dom, &natural_loop, preheader);
+        }
+    }
+}
+
+impl Licm {
+    /// 返回循环的前置块
+    /// 如果循环外只有一个前驱，并且它只跳转到循环头，直接使用它；
+    /// 否则新建一个前置块，把所有从循环外进入循环头的边都改到前置块上
+    fn preheader(&self, data: &mut FunctionData, dom: &DominatorTree, natural_loop: &NaturalLoop) -> BasicBlock {
+        let header = natural_loop.header;
+        let outside: Vec<BasicBlock> = dom.preds(header).iter()
+            .copied()
+            .filter(|bb| !natural_loop.blocks.contains(bb))
+            .collect();
+        if let [pred] = outside[..] && successors(data, pred) == [header] {
+            return pred;
+        }
+
+        // 前置块的参数和循环头一致，原样传给循环头
+        let header_data = data.dfg().bb(header);
+        let name = header_data.name().as_ref().map(|n| format!("{n}_preheader"));
+        let params_ty: Vec<Type> = header_data.params().iter().map(|&p| data.dfg().value(p).ty().clone()).collect();
+        let preheader = data.dfg_mut().new_bb().basic_block_with_params(name, params_ty);
+        let params = data.dfg().bb(preheader).params().to_vec();
+        let jump = data.dfg_mut().new_value().jump_with_args(header, params);
+        // 循环头是入口块时，前置块成为新的入口
+        data.layout_mut().bbs_mut().cursor_mut(header).insert_key_before(preheader).unwrap();
+        data.layout_mut().bb_mut(preheader).insts_mut().push_key_back(jump).unwrap();
+
+        for pred in outside {
+            let terminator = *data.layout().bbs().node(&pred).unwrap().insts().back_key().unwrap();
+            let mut terminator_data = data.dfg().value(terminator).clone();
+            match terminator_data.kind_mut() {
+                ValueKind::Branch(branch) => {
+                    if branch.true_bb() == header {
+                        *branch.true_bb_mut() = preheader;
+                    }
+                    if branch.false_bb() == header {
+                        *branch.false_bb_mut() = preheader;
+                    }
+                },
+                ValueKind::Jump(jump) => *jump.target_mut() = preheader,
+                _ => unreachable!()
+            }
+            data.dfg_mut().replace_value_with(terminator).raw(terminator_data);
+        }
+        preheader
+    }
+
+    /// 把循环中的不变量移到前置块末尾的跳转之前
+    fn hoist(&self, data: &mut FunctionData, dom: &DominatorTree, natural_loop: &NaturalLoop, preheader: BasicBlock) {
+        // 按逆后序处理，操作数总是先于使用者被外提
+        let blocks: Vec<BasicBlock> = dom.blocks().iter()
+            .copied()
+            .filter(|bb| natural_loop.blocks.contains(bb))
+            .collect();
+
+        // 循环中定义的值（指令和基本块参数），以及循环中写内存的位置
+        let mut defined: HashSet<Value> = HashSet::new();
+        let mut stores = Vec::new();
+        let mut has_call = false;
+        for &bb in &blocks {
+            defined.extend(data.dfg().bb(bb).params().iter().copied());
+            for &inst in data.layout().bbs().node(&bb).unwrap().insts().keys() {
+                defined.insert(inst);
+                match data.dfg().value(inst).kind() {
+                    ValueKind::Store(store) => stores.push(store.dest()),
+                    ValueKind::Call(_) => has_call = true,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &bb in &blocks {
+                let insts: Vec<Value> = data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+                for inst in insts {
+                    if !is_invariant(data.dfg(), inst, &defined, &stores, has_call) {
+                        continue;
+                    }
+                    data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+                    let terminator = *data.layout().bbs().node(&preheader).unwrap().insts().back_key().unwrap();
+                    data.layout_mut().bb_mut(preheader).insts_mut().cursor_mut(terminator).insert_key_before(inst).unwrap();
+                    defined.remove(&inst);
+                    changed = true;
+                }
+            }
+        }
+    }
+}
+
+/// 判断循环中的一条指令能否外提
+/// defined：仍留在循环中定义的值；stores：循环中所有 store 的目标地址
+fn is_invariant(dfg: &DataFlowGraph, inst: Value, defined: &HashSet<Value>, stores: &[Value], has_call: bool) -> bool {
+    let outside = |v: Value| !defined.contains(&v);
+    match dfg.value(inst).kind() {
+        ValueKind::Binary(binary) => {
+            if !outside(binary.lhs()) || !outside(binary.rhs()) {
+                return false;
+            }
+            // 外提后即使循环一次也不执行，除法也会被执行，所以只外提除数是非零常量的除法
+            match binary.op() {
+                BinaryOp::Div | BinaryOp::Mod => integer_value(dfg, binary.rhs()).is_some_and(|r| r != 0),
+                _ => true
+            }
+        },
+        ValueKind::Load(load) => {
+            let src = load.src();
+            outside(src) && is_memory_object(dfg, src)
+                && stores.iter().all(|&dest| !may_alias(dfg, dest, src))
+                // 被调用的函数可能写入任何逃逸出去的内存
+                && (!has_call || is_private_alloc(dfg, src))
+        },
+        _ => false
+    }
+}
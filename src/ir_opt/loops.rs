@@ -0,0 +1,45 @@
+use std::collections::HashSet;
+
+use koopa::ir::{BasicBlock, FunctionData};
+
+use super::dominator::DominatorTree;
+use super::successors;
+
+/// 控制流图上的一个自然循环
+pub struct NaturalLoop {
+    /// 循环头，支配循环中的所有基本块
+    pub header: BasicBlock,
+    /// 循环中的所有基本块（包括循环头）
+    pub blocks: HashSet<BasicBlock>
+}
+
+/// 找出函数中的所有自然循环
+/// 回边 latch -> header 满足 header 支配 latch；循环体是不经过 header 就能到达 latch 的所有块。
+/// 同一个循环头的多条回边合并为一个循环。返回的循环按大小排序，内层循环在前。
+pub fn find_loops(data: &FunctionData, dom: &DominatorTree) -> Vec<NaturalLoop> {
+    let mut loops: Vec<NaturalLoop> = Vec::new();
+    for &latch in dom.blocks() {
+        for header in successors(data, latch) {
+            if !dom.dominates(header, latch) {
+                continue;
+            }
+            let index = match loops.iter().position(|l| l.header == header) {
+                Some(index) => index,
+                None => {
+                    loops.push(NaturalLoop { header, blocks: HashSet::from([header]) });
+                    loops.len() - 1
+                }
+            };
+            // 从 latch 出发沿前驱反向遍历，遇到 header 停止
+            let blocks = &mut loops[index].blocks;
+            let mut stack = vec![latch];
+            while let Some(bb) = stack.pop() {
+                if blocks.insert(bb) {
+                    stack.extend(dom.preds(bb).iter().copied());
+                }
+            }
+        }
+    }
+    loops.sort_by_key(|l| l.blocks.len());
+    loops
+}
@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+
+use koopa::ir::{builder::{LocalInstBuilder, ValueBuilder}, BasicBlock, Function, FunctionData, Value, ValueKind};
+use koopa::opt::FunctionPass;
+
+use super::constant_fold::evaluate_binary;
+use super::{remove_instruction, replace_all_uses};
+
+/// 稀疏条件常量传播（Wegman-Zadeck SCCP）
+/// 同时在 SSA 值和控制流边上做数据流分析：
+/// - 只有可执行的边才会把参数传递给目标基本块的参数
+/// - 条件已知的 br 只会把一侧的边标记为可执行
+///
+/// 分析结束后，常量值被替换为整数常量，条件已知的 br 改写为 jump。
+/// 永远不会执行的分支随后由 CfgSimplify 删除，无用的指令由 DeadCodeElimination 删除。
+pub struct Sccp;
+
+/// 格上的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lattice {
+    // 尚未确定（可能是任何常量）
+    Top,
+    // 确定是某个常量
+    Const(i32),
+    // 不是常量
+    Bottom
+}
+
+impl Lattice {
+    /// 格上的交运算
+    fn meet(self, other: Lattice) -> Lattice {
+        match (self, other) {
+            (Lattice::Top, x) | (x, Lattice::Top) => x,
+            (Lattice::Const(a), Lattice::Const(b)) if a == b => Lattice::Const(a),
+            _ => Lattice::Bottom
+        }
+    }
+}
+
+/// 一次 SCCP 分析的状态
+struct SccpState<'a> {
+    data: &'a FunctionData,
+    values: HashMap<Value, Lattice>,
+    executable_blocks: HashSet<BasicBlock>,
+    executable_edges: HashSet<(BasicBlock, BasicBlock)>,
+    flow_worklist: Vec<(BasicBlock, BasicBlock)>,
+    ssa_worklist: Vec<Value>
+}
+
+impl FunctionPass for Sccp {
+    fn run_on(&mut self, _: Function, data: &mut FunctionData) {
+        let Some(entry) = data.layout().entry_bb() else {
+            return;
+        };
+        let (values, executable_blocks) = {
+            let mut state = SccpState {
+                data,
+                values: HashMap::new(),
+                executable_blocks: HashSet::new(),
+                executable_edges: HashSet::new(),
+                flow_worklist: Vec::new(),
+                ssa_worklist: Vec::new()
+            };
+            state.run(entry);
+            (state.values, state.executable_blocks)
+        };
+        self.rewrite(data, &values, &executable_blocks);
+    }
+}
+
+impl Sccp {
+    /// 根据分析结果改写函数
+    fn rewrite(&self, data: &mut FunctionData, values: &HashMap<Value, Lattice>, executable_blocks: &HashSet<BasicBlock>) {
+        let bbs: Vec<BasicBlock> = data.layout().bbs().keys().copied().collect();
+        for bb in bbs {
+            if !executable_blocks.contains(&bb) {
+                continue;
+            }
+            // 基本块参数：替换所有使用，参数本身保留
+            for param in data.dfg().bb(bb).params().to_vec() {
+                if let Some(Lattice::Const(i)) = values.get(&param) {
+                    let constant = data.dfg_mut().new_value().integer(*i);
+                    replace_all_uses(data.dfg_mut(), param, constant);
+                }
+            }
+            let insts: Vec<Value> = data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+            for inst in insts {
+                match data.dfg().value(inst).kind() {
+                    ValueKind::Binary(_) => {
+                        if let Some(Lattice::Const(i)) = values.get(&inst) {
+                            let constant = data.dfg_mut().new_value().integer(*i);
+                            replace_all_uses(data.dfg_mut(), inst, constant);
+                            remove_instruction(data, inst);
+                        }
+                    },
+                    ValueKind::Branch(branch) => {
+                        let cond = match values.get(&branch.cond()) {
+                            Some(Lattice::Const(i)) => *i,
+                            _ => match super::integer_value(data.dfg(), branch.cond()) {
+                                Some(i) => i,
+                                None => continue
+                            }
+                        };
+                        let (target, args) = if cond != 0 {
+                            (branch.true_bb(), branch.true_args().to_vec())
+                        } else {
+                            (branch.false_bb(), branch.false_args().to_vec())
+                        };
+                        data.dfg_mut().replace_value_with(inst).jump_with_args(target, args);
+                    },
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl SccpState<'_> {
+    fn run(&mut self, entry: BasicBlock) {
+        self.visit_block(entry);
+        while !self.flow_worklist.is_empty() || !self.ssa_worklist.is_empty() {
+            while let Some((from, to)) = self.flow_worklist.pop() {
+                if !self.executable_edges.insert((from, to)) {
+                    continue;
+                }
+                self.update_params(to);
+                if self.executable_blocks.insert(to) {
+                    self.visit_block(to);
+                }
+            }
+            while let Some(value) = self.ssa_worklist.pop() {
+                let users: Vec<Value> = self.data.dfg().value(value).used_by().iter().copied().collect();
+                for user in users {
+                    if let Some(bb) = self.data.layout().parent_bb(user)
+                        && self.executable_blocks.contains(&bb) {
+                        self.visit_inst(bb, user);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 第一次访问一个基本块，求值其中的所有指令
+    fn visit_block(&mut self, bb: BasicBlock) {
+        self.executable_blocks.insert(bb);
+        let insts: Vec<Value> = self.data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        for inst in insts {
+            self.visit_inst(bb, inst);
+        }
+    }
+
+    /// 取得某个值当前在格上的位置
+    fn lattice(&self, value: Value) -> Lattice {
+        if value.is_global() {
+            return Lattice::Bottom;
+        }
+        match self.data.dfg().value(value).kind() {
+            ValueKind::Integer(i) => Lattice::Const(i.value()),
+            ValueKind::FuncArgRef(_) | ValueKind::Undef(_) | ValueKind::ZeroInit(_) | ValueKind::Aggregate(_) => Lattice::Bottom,
+            _ => self.values.get(&value).copied().unwrap_or(Lattice::Top)
+        }
+    }
+
+    /// 更新某个值在格上的位置，发生变化时加入工作表
+    fn set_lattice(&mut self, value: Value, new: Lattice) {
+        let old = self.lattice(value);
+        let new = old.meet(new);
+        if new != old {
+            self.values.insert(value, new);
+            self.ssa_worklist.push(value);
+        }
+    }
+
+    /// 求值一条指令
+    fn visit_inst(&mut self, bb: BasicBlock, inst: Value) {
+        match self.data.dfg().value(inst).kind() {
+            ValueKind::Binary(binary) => {
+                let result = match (self.lattice(binary.lhs()), self.lattice(binary.rhs())) {
+                    (Lattice::Const(l), Lattice::Const(r)) => match evaluate_binary(binary.op(), l, r) {
+                        Some(i) => Lattice::Const(i),
+                        None => Lattice::Bottom
+                    },
+                    (Lattice::Bottom, _) | (_, Lattice::Bottom) => Lattice::Bottom,
+                    _ => Lattice::Top
+                };
+                self.set_lattice(inst, result);
+            },
+            ValueKind::Branch(branch) => {
+                let (true_bb, false_bb) = (branch.true_bb(), branch.false_bb());
+                match self.lattice(branch.cond()) {
+                    Lattice::Top => {},
+                    Lattice::Const(i) => self.flow_worklist.push((bb, if i != 0 { true_bb } else { false_bb })),
+                    Lattice::Bottom => {
+                        self.flow_worklist.push((bb, true_bb));
+                        self.flow_worklist.push((bb, false_bb));
+                    }
+                }
+                // 参数可能发生了变化
+                for target in [true_bb, false_bb] {
+                    if self.executable_edges.contains(&(bb, target)) {
+                        self.update_params(target);
+                    }
+                }
+            },
+            ValueKind::Jump(jump) => {
+                let target = jump.target();
+                self.flow_worklist.push((bb, target));
+                if self.executable_edges.contains(&(bb, target)) {
+                    self.update_params(target);
+                }
+            },
+            ValueKind::Load(_) | ValueKind::Call(_) | ValueKind::Alloc(_) | ValueKind::GetPtr(_) | ValueKind::GetElemPtr(_) => {
+                self.set_lattice(inst, Lattice::Bottom);
+            },
+            _ => {}
+        }
+    }
+
+    /// 重新计算基本块参数：对所有可执行入边传入的参数取交
+    fn update_params(&mut self, bb: BasicBlock) {
+        let params = self.data.dfg().bb(bb).params().to_vec();
+        if params.is_empty() {
+            return;
+        }
+        let mut incoming = vec![Lattice::Top; params.len()];
+        for &user in self.data.dfg().bb(bb).used_by() {
+            let Some(from) = self.data.layout().parent_bb(user) else {
+                continue;
+            };
+            if !self.executable_edges.contains(&(from, bb)) {
+                continue;
+            }
+            let mut arg_lists = Vec::new();
+            match self.data.dfg().value(user).kind() {
+                ValueKind::Branch(branch) => {
+                    if branch.true_bb() == bb {
+                        arg_lists.push(branch.true_args().to_vec());
+                    }
+                    if branch.false_bb() == bb {
+                        arg_lists.push(branch.false_args().to_vec());
+                    }
+                },
+                ValueKind::Jump(jump) => arg_lists.push(jump.args().to_vec()),
+                _ => {}
+            }
+            for args in arg_lists {
+                for (i, arg) in args.into_iter().enumerate() {
+                    incoming[i] = incoming[i].meet(self.lattice(arg));
+                }
+            }
+        }
+        for (param, lattice) in params.into_iter().zip(incoming) {
+            self.set_lattice(param, lattice);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use koopa::ir::{builder::{BasicBlockBuilder, LocalInstBuilder}, Function, FunctionData, Type, Value, ValueKind};
+use koopa::opt::FunctionPass;
+
+use super::{is_private_alloc, layout_insts, remove_instruction, replace_all_uses};
+
+/// 自递归尾调用消除
+/// 形如 `%r = call @f(...)` 之后紧跟 `ret %r` 的自调用，改写为跳回函数开头：
+/// - 新建一个带参数的循环头，参数对应函数的形参，原入口块中除 alloc 以外的指令都移入循环头
+/// - 原入口块只保留 alloc，然后以函数实参跳转到循环头
+/// - 尾调用改写为带新实参的 jump
+///
+/// 局部变量在每次“递归”之间会被复用，所以只有当所有局部变量的地址都没有逃逸时才做变换，
+/// 否则被调用者可能通过参数读到调用者的局部变量。
+pub struct TailRecursion;
+
+impl FunctionPass for TailRecursion {
+    fn run_on(&mut self, func: Function, data: &mut FunctionData) {
+        if data.layout().entry_bb().is_none() {
+            return;
+        }
+        let tail_calls: Vec<(Value, Value)> = self.self_tail_calls(func, data);
+        if tail_calls.is_empty() {
+            return;
+        }
+        let insts = layout_insts(data);
+        let escaped = insts.iter().any(|&inst| {
+            matches!(data.dfg().value(inst).kind(), ValueKind::Alloc(_)) && !is_private_alloc(data.dfg(), inst)
+        });
+        if escaped {
+            return;
+        }
+
+        // 新建循环头，形参的所有使用改为循环头的参数
+        let entry = data.layout().entry_bb().unwrap();
+        let params = data.params().to_vec();
+        let params_ty: Vec<Type> = params.iter().map(|&p| data.dfg().value(p).ty().clone()).collect();
+        let name = data.name()[1..].to_string();
+        let header = data.dfg_mut().new_bb().basic_block_with_params(Some(format!("%{name}_tail")), params_ty);
+        let header_params = data.dfg().bb(header).params().to_vec();
+        for (&param, &header_param) in params.iter().zip(&header_params) {
+            replace_all_uses(data.dfg_mut(), param, header_param);
+        }
+        data.layout_mut().bbs_mut().cursor_mut(entry).insert_key_after(header).unwrap();
+
+        // 入口块中除 alloc 以外的指令移入循环头
+        let entry_insts: Vec<Value> = data.layout().bbs().node(&entry).unwrap().insts().keys().copied().collect();
+        for inst in entry_insts {
+            if !matches!(data.dfg().value(inst).kind(), ValueKind::Alloc(_)) {
+                data.layout_mut().bb_mut(entry).insts_mut().remove(&inst);
+                data.layout_mut().bb_mut(header).insts_mut().push_key_back(inst).unwrap();
+            }
+        }
+        let jump = data.dfg_mut().new_value().jump_with_args(header, params);
+        data.layout_mut().bb_mut(entry).insts_mut().push_key_back(jump).unwrap();
+
+        // 尾调用改写为跳转到循环头
+        for (call, ret) in tail_calls {
+            let bb = data.layout().parent_bb(call).unwrap();
+            let args = match data.dfg().value(call).kind() {
+                ValueKind::Call(c) => c.args().to_vec(),
+                _ => unreachable!()
+            };
+            remove_instruction(data, ret);
+            remove_instruction(data, call);
+            let jump = data.dfg_mut().new_value().jump_with_args(header, args);
+            data.layout_mut().bb_mut(bb).insts_mut().push_key_back(jump).unwrap();
+        }
+    }
+}
+
+impl TailRecursion {
+    /// 找出函数中所有的自递归尾调用，返回（call 指令，紧随其后的 ret 指令）
+    fn self_tail_calls(&self, func: Function, data: &FunctionData) -> Vec<(Value, Value)> {
+        let mut result = Vec::new();
+        for node in data.layout().bbs().nodes() {
+            let insts: Vec<Value> = node.insts().keys().copied().collect();
+            for pair in insts.windows(2) {
+                let (call, ret) = (pair[0], pair[1]);
+                let ValueKind::Call(c) = data.dfg().value(call).kind() else {
+                    continue;
+                };
+                let ValueKind::Return(r) = data.dfg().value(ret).kind() else {
+                    continue;
+                };
+                // 返回值必须正好是调用的结果（无返回值的函数则 ret 不带值）
+                let returns_result = match r.value() {
+                    Some(v) => v == call,
+                    None => data.dfg().value(call).ty().is_unit()
+                };
+                if c.callee() == func && returns_result {
+                    result.push((call, ret));
+                }
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use koopa::ir::{dfg::DataFlowGraph, BasicBlock, BinaryOp, Function, FunctionData, Value, ValueKind};
+use koopa::opt::FunctionPass;
+
+use super::dominator::DominatorTree;
+use super::{integer_value, may_alias, is_private_alloc, remove_instruction, replace_all_uses};
+
+/// 公共子表达式删除（基于支配树的全局值编号）
+/// - 二元运算：沿支配树向下传递表达式表，被支配的块可以复用支配者中已经算过的结果
+/// - load：只在基本块内（以及唯一前驱就是支配者的直线块之间）复用，
+///   中间遇到可能写入同一地址的 store 或者函数调用时失效
+/// - store 之后紧跟的 load 直接使用被存入的值
+pub struct ValueNumbering;
+
+/// 值编号时的操作数：整数常量按数值比较，其他值按 Value 比较
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operand {
+    Const(i32),
+    Value(Value)
+}
+
+/// 二元表达式 -> 已经计算出该表达式的值
+type ExprTable = HashMap<(BinaryOp, Operand, Operand), Value>;
+/// 地址 -> 该地址中当前存放的值
+type LoadTable = HashMap<Value, Value>;
+
+impl FunctionPass for ValueNumbering {
+    fn run_on(&mut self, _: Function, data: &mut FunctionData) {
+        if data.layout().entry_bb().is_none() {
+            return;
+        }
+        let dom = DominatorTree::new(data);
+        // 显式栈模拟支配树的先序遍历，每个孩子拿到父节点处理完后的表的副本
+        let mut stack = vec![(dom.entry(), ExprTable::new(), LoadTable::new())];
+        while let Some((bb, mut exprs, mut loads)) = stack.pop() {
+            self.number_block(data, bb, &mut exprs, &mut loads);
+            for &child in dom.children(bb).iter().rev() {
+                // 只有唯一前驱就是当前块时，块末尾的内存状态才对孩子成立
+                let child_loads = if dom.preds(child) == [bb] { loads.clone() } else { LoadTable::new() };
+                stack.push((child, exprs.clone(), child_loads));
+            }
+        }
+    }
+}
+
+impl ValueNumbering {
+    /// 对一个基本块中的指令做值编号
+    fn number_block(&self, data: &mut FunctionData, bb: BasicBlock, exprs: &mut ExprTable, loads: &mut LoadTable) {
+        let insts: Vec<Value> = data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        for inst in insts {
+            let existing = match data.dfg().value(inst).kind() {
+                ValueKind::Binary(binary) => {
+                    let dfg = data.dfg();
+                    let key = (binary.op(), operand(dfg, binary.lhs()), operand(dfg, binary.rhs()));
+                    let found = equivalent_keys(key).into_iter().find_map(|k| exprs.get(&k).copied());
+                    if found.is_none() {
+                        exprs.insert(key, inst);
+                    }
+                    found
+                },
+                ValueKind::Load(load) => {
+                    let found = loads.get(&load.src()).copied();
+                    if found.is_none() {
+                        loads.insert(load.src(), inst);
+                    }
+                    found
+                },
+                ValueKind::Store(store) => {
+                    let dfg = data.dfg();
+                    loads.retain(|&ptr, _| !may_alias(dfg, ptr, store.dest()));
+                    loads.insert(store.dest(), store.value());
+                    None
+                },
+                ValueKind::Call(_) => {
+                    // 被调用的函数可能写入任何逃逸出去的内存
+                    let dfg = data.dfg();
+                    loads.retain(|&ptr, _| is_private_alloc(dfg, ptr));
+                    None
+                },
+                _ => None
+            };
+            if let Some(existing) = existing {
+                replace_all_uses(data.dfg_mut(), inst, existing);
+                remove_instruction(data, inst);
+            }
+        }
+    }
+}
+
+fn operand(dfg: &DataFlowGraph, value: Value) -> Operand {
+    match integer_value(dfg, value) {
+        Some(i) => Operand::Const(i),
+        None => Operand::Value(value)
+    }
+}
+
+/// 返回与 key 等价的所有表达式写法（交换律、比较运算的镜像）
+fn equivalent_keys(key: (BinaryOp, Operand, Operand)) -> Vec<(BinaryOp, Operand, Operand)> {
+    let (op, l, r) = key;
+    let swapped = match op {
+        BinaryOp::Add | BinaryOp::Mul | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor
+        | BinaryOp::Eq | BinaryOp::NotEq => Some(op),
+        BinaryOp::Lt => Some(BinaryOp::Gt),
+        BinaryOp::Gt => Some(BinaryOp::Lt),
+        BinaryOp::Le => Some(BinaryOp::Ge),
+        BinaryOp::Ge => Some(BinaryOp::Le),
+        _ => None
+    };
+    match swapped {
+        Some(swapped) => vec![key, (swapped, r, l)],
+        None => vec![key]
+    }
+}
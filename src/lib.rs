@@ -0,0 +1,23 @@
+//! SysY 到 Koopa IR / RISC-V 的编译器
+//!
+//! 命令行程序（main.rs）和 fuzz/ 中的模糊测试目标都通过这个库使用编译器的各个部分
+//! 只需要编译程序时使用 driver 中的 Compiler 和 Session，它们的接口保持稳定；
+//! 其余模块是编译器的内部结构，可能随实现变化
+
+pub mod driver;
+pub mod function_ast;
+pub mod ass_gen;
+pub mod ir_gen;
+pub mod ir_opt;
+pub mod ir_interp;
+pub mod riscv_sim;
+pub mod sysy_runtime;
+pub mod diff_test;
+pub mod sysy_fuzz;
+pub mod error_report;
+
+use lalrpop_util::lalrpop_mod;
+
+lalrpop_mod!(pub sysy);
+
+pub use driver::{Ast, Compiler, Options, Session, Target};
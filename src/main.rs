@@ -1,84 +1,194 @@
-use std::io::Result;
+use std::io::{Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 
-mod function_ast;
-mod ass_gen;
-mod ir_gen;
-mod error_report;
+use codespan_reporting::{files::SimpleFiles, term::{self, termcolor::{ColorChoice, StandardStream}}};
+use compiler::function_ast::dump;
+use compiler::{diff_test, error_report, ir_interp, riscv_sim, sysy_fuzz, Compiler, Session, Target};
 
+// 命令行参数的解析只属于命令行程序，不放在库中
+mod cli;
 
-use codespan_reporting::{files::SimpleFiles, term::{self, termcolor::{ColorChoice, StandardStream}}};
-use lalrpop_util::lalrpop_mod;
+use cli::{Args, Command, Emit, EmitKind, InputFormat, Mode, EXIT_FAILURE, EXIT_INTERNAL, EXIT_SUCCESS, EXIT_USAGE};
 
-lalrpop_mod!(pub sysy);
+/// 错误汇报使用的输入文件
+type Files<'a> = SimpleFiles<&'a str, &'a str>;
 
 
-fn show_help_and_exit() -> ! {
-    eprintln!("Usage: cargo run -- [-koopa|-riscv] <input_path> -o <output_path>");
-    std::process::exit(-1);
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = match cli::parse_args(&args) {
+        Ok(Command::Compile(args)) => args,
+        Ok(Command::Help) => {
+            print!("{}", cli::HELP);
+            std::process::exit(EXIT_SUCCESS);
+        },
+        Ok(Command::Version) => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            std::process::exit(EXIT_SUCCESS);
+        },
+        Err(e) => {
+            eprintln!("error: {}\nRun with --help to see all options.", e);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    // 编译器的 panic 是内部错误：默认的 panic 信息打印之后，以单独的退出码退出
+    let code = panic::catch_unwind(AssertUnwindSafe(|| run(&args))).unwrap_or_else(|_| {
+        eprintln!("error: internal compiler error; please report this as a bug");
+        EXIT_INTERNAL
+    });
+    std::process::exit(code);
 }
 
+/// 按照命令行参数工作，返回退出码
+fn run(args: &Args) -> i32 {
+    let compiler = Compiler::new(args.options.clone());
+    let options = compiler.options();
 
-fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    
-    if args.len() < 5 || args[1] != "-koopa" && args[1] != "-riscv" || args[3] != "-o" {
-        show_help_and_exit();
+    // 差分测试和随机程序测试不读取单个输入文件
+    if args.mode == Mode::DiffTest {
+        return match diff_test::run(Path::new(&args.input), &options.opt, &options.asm) {
+            Ok(passed) => exit_code(passed),
+            Err(e) => report_io_error("cannot read", &args.input, e)
+        };
+    }
+    if args.mode == Mode::SysyFuzz {
+        let count = args.input.parse().expect("the count is checked by parse_args");
+        return exit_code(sysy_fuzz::run(args.fuzz_seed, count, &options.opt, &options.asm));
     }
 
-    let input  = &args[2];
-    let output = &args[4];
-
-    let parser = sysy::CompUnitParser::new();
-
-    let input_string = std::fs::read_to_string(input)?;
-    let input_string = unindent::unindent(&input_string);
-    let ast = parser.parse(&input_string);
+    let (name, input_string) = match read_input(&args.input) {
+        Ok(input) => input,
+        Err(e) => return report_io_error("cannot read", &args.input, e)
+    };
+    // SysY 源程序去掉公共的缩进，Koopa IR 原样交给 koopa 的前端
+    let input_string = match args.input_format {
+        InputFormat::Sysy => unindent::unindent(&input_string),
+        InputFormat::Koopa => input_string
+    };
     // 错误汇报使用的内容
-    let mut files = SimpleFiles::new();
-    let file_id = files.add(input, &input_string);
-
-    let ir_program = match ast {
-        Ok(ast) => {
-            let mut koopa_ir_generator = ir_gen::IrGen::new();
-            let result = koopa_ir_generator.generate_koopa_ir(ast);
-            let writer = StandardStream::stderr(ColorChoice::Always);
-            let config = codespan_reporting::term::Config::default();
-            if let Some(result) = result {
-                let problems = koopa_ir_generator.get_problems();
-                for one in &problems {
-                    term::emit(&mut writer.lock(), &config, &files, &one.generate(file_id)).unwrap();
-                }
-                if !problems.is_empty() {
-                    eprintln!("{} warning{} generated.", problems.len(), if problems.len() > 1 { "s" } else { "" });
-                }
-                if args[1] == "-koopa" {
-                    let mut koopa_ir_text_generator = koopa::back::KoopaGenerator::new(Vec::new());
-                    koopa_ir_text_generator.generate_on(&result).unwrap();
-                    let text = String::from_utf8(koopa_ir_text_generator.writer()).unwrap();
-                    std::fs::write(output, text)?;
+    let mut files = Files::new();
+    let file_id = files.add(&name, &input_string);
+
+    let mut session = compiler.session();
+    let ir_program = match args.input_format {
+        InputFormat::Sysy => {
+            let Some(ast) = report_diagnostics(session.parse(&input_string), &mut session, &files, file_id) else {
+                return EXIT_FAILURE;
+            };
+            for emit in args.emits.iter().filter(|emit| emit.kind.is_ast()) {
+                let text = match emit.kind {
+                    EmitKind::AstJson => dump::dump_json(&ast, &input_string),
+                    _ => dump::dump_tree(&ast, &input_string)
+                };
+                if let Err(code) = write_output(args, emit, &text) {
+                    return code;
                 }
-                result
-            } else {
-                let problems = koopa_ir_generator.get_problems();
-                for one in &problems {
-                    term::emit(&mut writer.lock(), &config, &files, &one.generate(file_id)).unwrap();
+            }
+            // 只输出语法树时不生成 IR，有语义错误的程序也可以查看语法树
+            if args.mode == Mode::Compile && args.emits.iter().all(|emit| emit.kind.is_ast()) {
+                return EXIT_SUCCESS;
+            }
+            session.lower(ast)
+        },
+        InputFormat::Koopa => session.lower_koopa(&input_string)
+    };
+    let Some(ir_program) = report_diagnostics(ir_program, &mut session, &files, file_id) else {
+        return EXIT_FAILURE;
+    };
+
+    match args.mode {
+        Mode::Compile => {
+            for emit in &args.emits {
+                let target = match emit.kind {
+                    EmitKind::Ast | EmitKind::AstJson => continue,
+                    EmitKind::Koopa => Target::Koopa,
+                    EmitKind::Asm => Target::Riscv
+                };
+                let Some(text) = report_diagnostics(session.codegen(&ir_program, target), &mut session, &files, file_id) else {
+                    return EXIT_FAILURE;
+                };
+                if let Err(code) = write_output(args, emit, &text) {
+                    return code;
                 }
-                eprintln!("{} error{} generated.", problems.len(), if problems.len() > 1 { "s" } else { "" });
-                std::process::exit(-1);
             }
+            EXIT_SUCCESS
         },
-        Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(-1)
-        }
+        Mode::RunKoopa => {
+            let stdout = std::io::stdout();
+            let mut interpreter = ir_interp::Interpreter::new(&ir_program, std::io::stdin().lock(), std::io::BufWriter::new(stdout.lock()));
+            match interpreter.run() {
+                Ok(result) => result,
+                Err(e) => report_problem(&files, file_id, e.to_problem())
+            }
+        },
+        Mode::RunRiscv => {
+            let Some(asm) = report_diagnostics(session.codegen(&ir_program, Target::Riscv), &mut session, &files, file_id) else {
+                return EXIT_FAILURE;
+            };
+            let stdout = std::io::stdout();
+            match riscv_sim::run(&asm, std::io::stdin().lock(), std::io::BufWriter::new(stdout.lock())) {
+                Ok(result) => result,
+                Err(e) => report_problem(&files, file_id, e.to_problem())
+            }
+        },
+        // 在前面已经处理过了
+        Mode::DiffTest | Mode::SysyFuzz => unreachable!()
+    }
+}
+
+fn exit_code(passed: bool) -> i32 {
+    if passed { EXIT_SUCCESS } else { EXIT_FAILURE }
+}
+
+/// 读取输入，返回错误汇报使用的文件名和内容，路径为 `-` 时读取标准输入
+fn read_input(path: &str) -> std::io::Result<(String, String)> {
+    if path == "-" {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        Ok((String::from("<stdin>"), input))
+    } else {
+        Ok((path.to_string(), std::fs::read_to_string(path)?))
+    }
+}
+
+/// 把一个输出写到它的路径或者标准输出，失败时汇报错误并返回退出码
+fn write_output(args: &Args, emit: &Emit, text: &str) -> Result<(), i32> {
+    let result = match args.output_path(emit) {
+        Some(path) => std::fs::write(&path, text).map_err(|e| (path, e)),
+        None => std::io::stdout().write_all(text.as_bytes()).map_err(|e| (String::from("<stdout>"), e))
     };
+    result.map_err(|(path, e)| report_io_error("cannot write", &path, e))
+}
 
-    if args[1] == "-riscv" {
-        let mut compiler = ass_gen::AssGen::new(&ir_program);
-        // 如果生成失败，程序会直接崩溃的，不用担心
-        compiler.generate_program();
-        std::fs::write(output, compiler.finish())?;
+fn report_io_error(action: &str, path: &str, error: std::io::Error) -> i32 {
+    eprintln!("error: {} '{}': {}", action, path, error);
+    EXIT_FAILURE
+}
+
+/// 汇报 session 中新产生的错误和警告以及它们的数量，原样返回这一步的结果
+fn report_diagnostics<T>(result: Option<T>, session: &mut Session, files: &Files, file_id: usize) -> Option<T> {
+    let has_errors = session.has_errors();
+    let diagnostics = session.take_diagnostics();
+    let writer = StandardStream::stderr(ColorChoice::Always);
+    let config = codespan_reporting::term::Config::default();
+    for one in &diagnostics {
+        term::emit(&mut writer.lock(), &config, files, &one.generate(file_id)).unwrap();
+    }
+    let plural = |count: usize| if count > 1 { "s" } else { "" };
+    if has_errors {
+        let count = diagnostics.iter().filter(|problem| problem.level == error_report::ProblemLevel::Error).count();
+        eprintln!("{} error{} generated.", count, plural(count));
+    } else if !diagnostics.is_empty() {
+        eprintln!("{} warning{} generated.", diagnostics.len(), plural(diagnostics.len()));
     }
+    result
+}
 
-    Ok(())
-}
\ No newline at end of file
+/// 汇报一个执行时的错误，返回退出码
+fn report_problem(files: &Files, file_id: usize, problem: error_report::ProblemInfo) -> i32 {
+    let writer = StandardStream::stderr(ColorChoice::Always);
+    let config = codespan_reporting::term::Config::default();
+    term::emit(&mut writer.lock(), &config, files, &problem.generate(file_id)).unwrap();
+    EXIT_FAILURE
+}
@@ -0,0 +1,24 @@
+use std::io::{BufRead, Write};
+
+// RV32IM 汇编模拟器：翻译并执行 AssGen 生成的汇编，用于在没有 RISC-V 工具链的机器上测试后端
+// 只支持 AssGen 用到的指令，SysY 运行时库由宿主实现
+mod assembler;
+mod machine;
+pub mod error;
+
+pub use error::SimError;
+
+/// 代码段的起始地址，ra 中保存的返回地址使用它计算
+const TEXT_BASE: u32 = 0x0001_0000;
+/// 数据段的起始地址
+const DATA_BASE: u32 = 0x1000_0000;
+/// 栈顶地址，也就是 main 开始时 sp 的值
+const STACK_TOP: u32 = 0x8000_0000;
+/// 栈的大小
+const STACK_SIZE: u32 = 8 << 20;
+
+/// 翻译并执行汇编文本，返回 main 的返回值
+pub fn run<R: BufRead, W: Write>(asm: &str, input: R, output: W) -> Result<i32, SimError> {
+    let assembly = assembler::assemble(asm)?;
+    machine::Machine::new(&assembly, input, output).run()
+}
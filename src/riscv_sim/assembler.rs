@@ -0,0 +1,358 @@
+/// 此文件把 AssGen 生成的汇编文本翻译为模拟器执行的指令
+/// - 第一遍记录所有标签的位置：.text 中的标签对应指令下标，.data 中的标签对应数据段地址
+/// - 第二遍解析指令的操作数，检查立即数和跳转距离是否在真实指令的编码范围内
+/// - 跳转到未定义的标签时，如果是 SysY 运行时库函数，就交给宿主实现
+use std::collections::HashMap;
+
+use koopa::ir::BinaryOp;
+
+use crate::sysy_runtime::RuntimeFunction;
+
+use super::error::{SimError, SimErrorReason};
+use super::DATA_BASE;
+
+/// 寄存器编号，0 到 31
+pub type Reg = usize;
+
+/// 条件跳转的比较方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    Eq,
+    Ne,
+    Lt,
+    Ge
+}
+
+/// call/tail 的目标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// 程序中的标签，记录指令下标
+    Code(usize),
+    /// 由宿主实现的运行时库函数
+    Runtime(RuntimeFunction)
+}
+
+/// 一条（伪）指令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Li(Reg, i32),
+    La(Reg, u32),
+    Mv(Reg, Reg),
+    /// 寄存器之间的运算，运算的含义和 Koopa IR 中的二元运算相同
+    Alu(BinaryOp, Reg, Reg, Reg),
+    /// 寄存器和立即数的运算
+    AluImm(BinaryOp, Reg, Reg, i32),
+    MulH(Reg, Reg, Reg),
+    Seqz(Reg, Reg),
+    Snez(Reg, Reg),
+    /// lw rd, offset(base)
+    Lw(Reg, i32, Reg),
+    /// sw src, offset(base)
+    Sw(Reg, i32, Reg),
+    Branch(Cond, Reg, Reg, usize),
+    Jump(usize),
+    Call(Target),
+    Tail(Target),
+    Ret
+}
+
+/// 一条指令以及它在汇编文本中的位置
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub op: Op,
+    /// 从 1 开始的行号
+    pub line: usize,
+    pub text: String
+}
+
+/// 翻译后的程序
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    pub instructions: Vec<Instruction>,
+    /// 数据段的初始内容，从 DATA_BASE 开始
+    pub data: Vec<u8>,
+    /// main 的指令下标
+    pub entry: usize
+}
+
+/// 标签的位置
+#[derive(Debug, Clone, Copy)]
+enum Label {
+    Text(usize),
+    Data(u32)
+}
+
+/// 翻译汇编文本
+pub fn assemble(text: &str) -> Result<Assembly, SimError> {
+    let mut labels = HashMap::new();
+    let mut pending = Vec::new();
+    let mut data = Vec::new();
+    let mut in_data = false;
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let error = |message: String| SimError {
+            line,
+            text: raw.trim().to_string(),
+            reason: SimErrorReason::Syntax(message)
+        };
+        let mut body = raw.split('#').next().unwrap().trim();
+        if let Some((label, rest)) = body.split_once(':') {
+            let label = label.trim();
+            let position = if in_data { Label::Data(DATA_BASE + data.len() as u32) } else { Label::Text(pending.len()) };
+            if labels.insert(label.to_string(), position).is_some() {
+                return Err(error(format!("duplicate label '{}'", label)));
+            }
+            body = rest.trim();
+        }
+        if body.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = match body.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.split(',').map(str::trim).collect()),
+            None => (body, Vec::new())
+        };
+        match mnemonic {
+            ".text" => in_data = false,
+            ".data" => in_data = true,
+            ".globl" | ".global" => {},
+            ".word" => {
+                for operand in operands {
+                    let value = parse_imm(operand).map_err(error)?;
+                    data.extend_from_slice(&(value as u32).to_le_bytes());
+                }
+            },
+            ".zero" => {
+                let size = operands.first().ok_or_else(|| error(String::from("expected a size")))?;
+                let size = usize::try_from(parse_imm(size).map_err(error)?)
+                    .map_err(|_| error(String::from("size must not be negative")))?;
+                data.resize(data.len() + size, 0);
+            },
+            ".align" => {
+                let align = operands.first().ok_or_else(|| error(String::from("expected an alignment")))?;
+                let align = 1usize << parse_imm(align).map_err(error)?.clamp(0, 12);
+                data.resize(data.len().next_multiple_of(align), 0);
+            },
+            _ if mnemonic.starts_with('.') => return Err(error(format!("unsupported directive '{}'", mnemonic))),
+            _ if in_data => return Err(error(String::from("instructions are not allowed in the data section"))),
+            _ => pending.push((line, raw.trim().to_string(), mnemonic.to_string(), operands.iter().map(|o| o.to_string()).collect::<Vec<_>>()))
+        }
+    }
+
+    let mut instructions = Vec::new();
+    for (index, (line, text, mnemonic, operands)) in pending.into_iter().enumerate() {
+        let op = parse_instruction(&labels, index, &mnemonic, &operands).map_err(|message| SimError {
+            line,
+            text: text.clone(),
+            reason: SimErrorReason::Syntax(message)
+        })?;
+        instructions.push(Instruction { op, line, text });
+    }
+    let entry = match labels.get("main") {
+        Some(Label::Text(index)) => *index,
+        _ => return Err(SimError { line: 0, text: String::new(), reason: SimErrorReason::MissingMain })
+    };
+    Ok(Assembly { instructions, data, entry })
+}
+
+/// 解析一条指令，index 为它的指令下标，用于检查跳转距离
+fn parse_instruction(labels: &HashMap<String, Label>, index: usize, mnemonic: &str, operands: &[String]) -> Result<Op, String> {
+    let count = |n: usize| if operands.len() == n {
+        Ok(())
+    } else {
+        Err(format!("'{}' expects {} operand{}, found {}", mnemonic, n, if n == 1 { "" } else { "s" }, operands.len()))
+    };
+    let reg = |i: usize| parse_reg(&operands[i]);
+    let code_label = |i: usize| match labels.get(&operands[i]) {
+        Some(Label::Text(target)) => Ok(*target),
+        Some(Label::Data(_)) => Err(format!("'{}' is not a code label", operands[i])),
+        None => Err(format!("undefined label '{}'", operands[i]))
+    };
+    // 跳转距离按字节计算，必须能用 bits 位有符号数表示
+    let in_reach = |target: usize, bits: u32| {
+        let distance = (target as i64 - index as i64) * 4;
+        let limit = 1i64 << (bits - 1);
+        if (-limit..limit).contains(&distance) {
+            Ok(target)
+        } else {
+            Err(format!("jump target '{}' is out of range for '{}'", operands.last().unwrap(), mnemonic))
+        }
+    };
+    let call_target = |i: usize| match labels.get(&operands[i]) {
+        Some(Label::Text(target)) => Ok(Target::Code(*target)),
+        Some(Label::Data(_)) => Err(format!("'{}' is not a code label", operands[i])),
+        None => RuntimeFunction::from_name(&operands[i])
+            .map(Target::Runtime)
+            .ok_or_else(|| format!("undefined function '{}'", operands[i]))
+    };
+
+    if let Some(op) = alu_op(mnemonic) {
+        count(3)?;
+        return Ok(Op::Alu(op, reg(0)?, reg(1)?, reg(2)?));
+    }
+    if let Some(op) = alu_imm_op(mnemonic) {
+        count(3)?;
+        let imm = parse_imm(&operands[2])?;
+        let range = if matches!(op, BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Sar) { 0..=31 } else { -2048..=2047 };
+        if !range.contains(&imm) {
+            return Err(format!("immediate {} is out of range for '{}'", imm, mnemonic));
+        }
+        return Ok(Op::AluImm(op, reg(0)?, reg(1)?, imm as i32));
+    }
+    let op = match mnemonic {
+        "li" => {
+            count(2)?;
+            let imm = parse_imm(&operands[1])?;
+            if !(i32::MIN as i64..=u32::MAX as i64).contains(&imm) {
+                return Err(format!("immediate {} does not fit in 32 bits", imm));
+            }
+            Op::Li(reg(0)?, imm as i32)
+        },
+        "la" => {
+            count(2)?;
+            match labels.get(&operands[1]) {
+                Some(Label::Data(address)) => Op::La(reg(0)?, *address),
+                Some(Label::Text(_)) => return Err(format!("'{}' is not a data label", operands[1])),
+                None => return Err(format!("undefined label '{}'", operands[1]))
+            }
+        },
+        "mv" => {
+            count(2)?;
+            Op::Mv(reg(0)?, reg(1)?)
+        },
+        "mulh" => {
+            count(3)?;
+            Op::MulH(reg(0)?, reg(1)?, reg(2)?)
+        },
+        "seqz" => {
+            count(2)?;
+            Op::Seqz(reg(0)?, reg(1)?)
+        },
+        "snez" => {
+            count(2)?;
+            Op::Snez(reg(0)?, reg(1)?)
+        },
+        "lw" => {
+            count(2)?;
+            let (offset, base) = parse_address(&operands[1])?;
+            Op::Lw(reg(0)?, offset, base)
+        },
+        "sw" => {
+            count(2)?;
+            let (offset, base) = parse_address(&operands[1])?;
+            Op::Sw(reg(0)?, offset, base)
+        },
+        "beqz" | "bnez" => {
+            count(2)?;
+            let cond = if mnemonic == "beqz" { Cond::Eq } else { Cond::Ne };
+            Op::Branch(cond, reg(0)?, 0, in_reach(code_label(1)?, 13)?)
+        },
+        "beq" | "bne" | "blt" | "bge" => {
+            count(3)?;
+            let cond = match mnemonic {
+                "beq" => Cond::Eq,
+                "bne" => Cond::Ne,
+                "blt" => Cond::Lt,
+                _ => Cond::Ge
+            };
+            Op::Branch(cond, reg(0)?, reg(1)?, in_reach(code_label(2)?, 13)?)
+        },
+        "j" => {
+            count(1)?;
+            Op::Jump(in_reach(code_label(0)?, 21)?)
+        },
+        "call" => {
+            count(1)?;
+            Op::Call(call_target(0)?)
+        },
+        "tail" => {
+            count(1)?;
+            Op::Tail(call_target(0)?)
+        },
+        "ret" => {
+            count(0)?;
+            Op::Ret
+        },
+        _ => return Err(format!("unknown instruction '{}'", mnemonic))
+    };
+    Ok(op)
+}
+
+/// 寄存器之间运算的指令
+fn alu_op(mnemonic: &str) -> Option<BinaryOp> {
+    Some(match mnemonic {
+        "add" => BinaryOp::Add,
+        "sub" => BinaryOp::Sub,
+        "mul" => BinaryOp::Mul,
+        "div" => BinaryOp::Div,
+        "rem" => BinaryOp::Mod,
+        "and" => BinaryOp::And,
+        "or" => BinaryOp::Or,
+        "xor" => BinaryOp::Xor,
+        "sll" => BinaryOp::Shl,
+        "srl" => BinaryOp::Shr,
+        "sra" => BinaryOp::Sar,
+        "slt" => BinaryOp::Lt,
+        "sgt" => BinaryOp::Gt,
+        _ => return None
+    })
+}
+
+/// 寄存器和立即数运算的指令
+fn alu_imm_op(mnemonic: &str) -> Option<BinaryOp> {
+    Some(match mnemonic {
+        "addi" => BinaryOp::Add,
+        "andi" => BinaryOp::And,
+        "ori" => BinaryOp::Or,
+        "xori" => BinaryOp::Xor,
+        "slli" => BinaryOp::Shl,
+        "srli" => BinaryOp::Shr,
+        "srai" => BinaryOp::Sar,
+        "slti" => BinaryOp::Lt,
+        _ => return None
+    })
+}
+
+/// 解析寄存器名，支持 ABI 名称和 x0~x31
+fn parse_reg(name: &str) -> Result<Reg, String> {
+    const NAMES: [&str; 32] = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+        "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+        "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+        "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6"
+    ];
+    if name == "fp" {
+        return Ok(8);
+    }
+    if let Some(index) = NAMES.iter().position(|&n| n == name) {
+        return Ok(index);
+    }
+    name.strip_prefix('x')
+        .and_then(|n| n.parse::<usize>().ok())
+        .filter(|&n| n < 32)
+        .ok_or_else(|| format!("unknown register '{}'", name))
+}
+
+/// 解析十进制或者 0x 开头的十六进制立即数
+fn parse_imm(text: &str) -> Result<i64, String> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text)
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse::<i64>()
+    }.map_err(|_| format!("invalid immediate '{}'", text))?;
+    Ok(if negative { -value } else { value })
+}
+
+/// 解析 offset(base) 形式的地址
+fn parse_address(text: &str) -> Result<(i32, Reg), String> {
+    let (offset, base) = text.strip_suffix(')')
+        .and_then(|t| t.split_once('('))
+        .ok_or_else(|| format!("expected an address like 'offset(base)', found '{}'", text))?;
+    let offset = if offset.is_empty() { 0 } else { parse_imm(offset)? };
+    if !(-2048..=2047).contains(&offset) {
+        return Err(format!("offset {} is out of range", offset));
+    }
+    Ok((offset as i32, parse_reg(base)?))
+}
@@ -0,0 +1,76 @@
+/// 此文件存放模拟器的错误，包括翻译汇编时的错误和运行时的错误
+/// 错误都带有出错指令所在的行，方便对照生成的汇编查找问题
+use std::fmt;
+
+use crate::error_report::ProblemInfo;
+
+/// 模拟器出错的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimErrorReason {
+    /// 汇编文本有误，附带说明
+    Syntax(String),
+    /// 程序中没有 main 标签
+    MissingMain,
+    /// 访问的地址不在数据段或栈中
+    InvalidAddress(u32),
+    /// lw/sw 的地址不是 4 的倍数
+    MisalignedAccess(u32),
+    /// 跳转到了不是指令的地址，例如 ra 被破坏之后的 ret
+    InvalidJump(u32),
+    /// 运行时库函数读取输入失败
+    InvalidInput(&'static str),
+    /// 写入输出失败
+    Output(String),
+    /// main 返回时被调用者保存寄存器（或者 sp）没有恢复
+    RegisterNotRestored(&'static str)
+}
+
+/// 模拟器错误：在汇编的哪一行，因为什么原因出错
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimError {
+    /// 从 1 开始的行号，和整个程序有关的错误为 0
+    pub line: usize,
+    pub text: String,
+    pub reason: SimErrorReason
+}
+
+impl SimErrorReason {
+    /// 是否是翻译汇编时发现的错误
+    pub fn is_assembly_error(&self) -> bool {
+        matches!(self, SimErrorReason::Syntax(_) | SimErrorReason::MissingMain)
+    }
+}
+
+impl fmt::Display for SimErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimErrorReason::Syntax(message) => write!(f, "{}", message),
+            SimErrorReason::MissingMain => write!(f, "the program has no 'main' label"),
+            SimErrorReason::InvalidAddress(address) => write!(f, "access to unmapped address {:#010x}", address),
+            SimErrorReason::MisalignedAccess(address) => write!(f, "misaligned word access at address {:#010x}", address),
+            SimErrorReason::InvalidJump(address) => write!(f, "jump to invalid code address {:#010x}", address),
+            SimErrorReason::InvalidInput(function) => write!(f, "{} could not read an integer from the input", function),
+            SimErrorReason::Output(message) => write!(f, "failed to write output: {}", message),
+            SimErrorReason::RegisterNotRestored(register) => write!(f, "callee-saved register {} was not restored when main returned", register)
+        }
+    }
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.reason)
+        } else {
+            write!(f, "{} (at line {}: {})", self.reason, self.line, self.text)
+        }
+    }
+}
+
+impl SimError {
+    /// 转换为和编译错误同样格式的诊断信息
+    pub fn to_problem(&self) -> ProblemInfo {
+        let kind = if self.reason.is_assembly_error() { "assembly error" } else { "runtime error" };
+        let notes = (self.line != 0).then(|| vec![format!("at line {} of the generated assembly: {}", self.line, self.text)]);
+        ProblemInfo::error(format!("{}: {}", kind, self.reason), Vec::new(), notes)
+    }
+}
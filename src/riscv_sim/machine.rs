@@ -0,0 +1,246 @@
+/// 此文件存放模拟器的执行部分
+/// - 内存只有数据段和栈两块，访问其他地址或者不对齐的地址都会报错
+/// - main 返回到一个特殊的地址时程序结束，此时检查 sp 和 s0~s11 是否已经恢复
+/// - 运行时库函数由宿主实现，调用之后和真实的库函数一样破坏所有调用者保存寄存器
+use std::io::{BufRead, Write};
+
+use koopa::ir::BinaryOp;
+
+use crate::ir_opt::evaluate_binary;
+use crate::sysy_runtime::{RuntimeFunction, SysyIo};
+
+use super::assembler::{Assembly, Cond, Op, Reg, Target};
+use super::error::{SimError, SimErrorReason};
+use super::{DATA_BASE, STACK_SIZE, STACK_TOP, TEXT_BASE};
+
+/// main 的返回地址，返回到这里表示程序结束
+const EXIT_ADDRESS: u32 = 0;
+/// 运行时库函数调用之后，调用者保存寄存器被改写成的值
+const CLOBBERED: i32 = 0x0bad_cafe;
+/// 调用者保存寄存器：t0~t6 和 a0~a7
+const CALLER_SAVED: [Reg; 15] = [5, 6, 7, 28, 29, 30, 31, 10, 11, 12, 13, 14, 15, 16, 17];
+/// 被调用者保存寄存器 s0~s11 的编号和名称
+const CALLEE_SAVED: [(Reg, &str); 12] = [
+    (8, "s0"), (9, "s1"), (18, "s2"), (19, "s3"), (20, "s4"), (21, "s5"),
+    (22, "s6"), (23, "s7"), (24, "s8"), (25, "s9"), (26, "s10"), (27, "s11")
+];
+const SP: Reg = 2;
+const RA: Reg = 1;
+const A0: Reg = 10;
+const A1: Reg = 11;
+
+/// 被调用者保存寄存器的初始值，main 返回时应该和它相同
+fn initial_value(reg: Reg) -> i32 {
+    0x5eed_0000 + reg as i32
+}
+
+/// 模拟器的状态
+pub struct Machine<'a, R: BufRead, W: Write> {
+    assembly: &'a Assembly,
+    regs: [i32; 32],
+    /// 下一条指令的下标
+    pc: usize,
+    data: Vec<u8>,
+    stack: Vec<u8>,
+    io: SysyIo<R, W>
+}
+
+impl<'a, R: BufRead, W: Write> Machine<'a, R, W> {
+    pub fn new(assembly: &'a Assembly, input: R, output: W) -> Self {
+        let mut regs = [0; 32];
+        for (reg, _) in CALLEE_SAVED {
+            regs[reg] = initial_value(reg);
+        }
+        regs[SP] = STACK_TOP as i32;
+        regs[RA] = EXIT_ADDRESS as i32;
+        Self {
+            assembly,
+            regs,
+            pc: assembly.entry,
+            data: assembly.data.clone(),
+            stack: vec![0; STACK_SIZE as usize],
+            io: SysyIo::new(input, output)
+        }
+    }
+
+    /// 从 main 开始执行，返回 main 的返回值
+    pub fn run(&mut self) -> Result<i32, SimError> {
+        loop {
+            let Some(instruction) = self.assembly.instructions.get(self.pc) else {
+                // 执行到了最后一条指令之后
+                let address = TEXT_BASE + 4 * self.pc as u32;
+                return Err(SimError { line: 0, text: String::new(), reason: SimErrorReason::InvalidJump(address) });
+            };
+            match self.step(instruction.op) {
+                Ok(Some(result)) => {
+                    self.io.flush().map_err(|e| SimError {
+                        line: 0,
+                        text: String::new(),
+                        reason: SimErrorReason::Output(e.to_string())
+                    })?;
+                    return Ok(result);
+                },
+                Ok(None) => {},
+                Err(reason) => {
+                    // 尽量把出错之前的输出写出去，写不出去也不影响报告原来的错误
+                    let _ = self.io.flush();
+                    return Err(SimError { line: instruction.line, text: instruction.text.clone(), reason });
+                }
+            }
+        }
+    }
+
+    /// 执行一条指令，main 返回时得到它的返回值
+    fn step(&mut self, op: Op) -> Result<Option<i32>, SimErrorReason> {
+        let next = self.pc + 1;
+        match op {
+            Op::Li(rd, imm) => self.set(rd, imm),
+            Op::La(rd, address) => self.set(rd, address as i32),
+            Op::Mv(rd, rs) => self.set(rd, self.regs[rs]),
+            Op::Alu(op, rd, rs1, rs2) => self.set(rd, alu(op, self.regs[rs1], self.regs[rs2])),
+            Op::AluImm(op, rd, rs, imm) => self.set(rd, alu(op, self.regs[rs], imm)),
+            Op::MulH(rd, rs1, rs2) => self.set(rd, ((self.regs[rs1] as i64 * self.regs[rs2] as i64) >> 32) as i32),
+            Op::Seqz(rd, rs) => self.set(rd, (self.regs[rs] == 0) as i32),
+            Op::Snez(rd, rs) => self.set(rd, (self.regs[rs] != 0) as i32),
+            Op::Lw(rd, offset, base) => {
+                let value = self.load(self.regs[base].wrapping_add(offset) as u32)?;
+                self.set(rd, value);
+            },
+            Op::Sw(rs, offset, base) => self.store(self.regs[base].wrapping_add(offset) as u32, self.regs[rs])?,
+            Op::Branch(cond, rs1, rs2, target) => {
+                let (l, r) = (self.regs[rs1], self.regs[rs2]);
+                let taken = match cond {
+                    Cond::Eq => l == r,
+                    Cond::Ne => l != r,
+                    Cond::Lt => l < r,
+                    Cond::Ge => l >= r
+                };
+                if taken {
+                    self.pc = target;
+                    return Ok(None);
+                }
+            },
+            Op::Jump(target) => {
+                self.pc = target;
+                return Ok(None);
+            },
+            Op::Call(Target::Code(target)) => {
+                self.set(RA, (TEXT_BASE + 4 * next as u32) as i32);
+                self.pc = target;
+                return Ok(None);
+            },
+            Op::Call(Target::Runtime(function)) => self.call_runtime(function)?,
+            Op::Tail(Target::Code(target)) => {
+                self.pc = target;
+                return Ok(None);
+            },
+            Op::Tail(Target::Runtime(function)) => {
+                self.call_runtime(function)?;
+                return self.return_to(self.regs[RA] as u32);
+            },
+            Op::Ret => return self.return_to(self.regs[RA] as u32)
+        }
+        self.pc = next;
+        Ok(None)
+    }
+
+    fn set(&mut self, rd: Reg, value: i32) {
+        if rd != 0 {
+            self.regs[rd] = value;
+        }
+    }
+
+    /// 跳转到 address，返回到 EXIT_ADDRESS 时程序结束
+    fn return_to(&mut self, address: u32) -> Result<Option<i32>, SimErrorReason> {
+        if address == EXIT_ADDRESS {
+            if self.regs[SP] != STACK_TOP as i32 {
+                return Err(SimErrorReason::RegisterNotRestored("sp"));
+            }
+            if let Some((_, name)) = CALLEE_SAVED.iter().find(|&&(reg, _)| self.regs[reg] != initial_value(reg)) {
+                return Err(SimErrorReason::RegisterNotRestored(name));
+            }
+            return Ok(Some(self.regs[A0]));
+        }
+        let index = address.wrapping_sub(TEXT_BASE) / 4;
+        if address < TEXT_BASE || !address.is_multiple_of(4) || index as usize >= self.assembly.instructions.len() {
+            return Err(SimErrorReason::InvalidJump(address));
+        }
+        self.pc = index as usize;
+        Ok(None)
+    }
+
+    /// 调用由宿主实现的运行时库函数，参数和返回值遵循调用约定
+    fn call_runtime(&mut self, function: RuntimeFunction) -> Result<(), SimErrorReason> {
+        let output_error = |e: std::io::Error| SimErrorReason::Output(e.to_string());
+        let (a0, a1) = (self.regs[A0], self.regs[A1]);
+        let result = match function {
+            RuntimeFunction::GetInt => Some(self.io.read_int().ok_or(SimErrorReason::InvalidInput("getint"))?),
+            RuntimeFunction::GetCh => Some(self.io.read_char()),
+            RuntimeFunction::GetArray => {
+                let len = self.io.read_int().ok_or(SimErrorReason::InvalidInput("getarray"))?;
+                for i in 0..len {
+                    let value = self.io.read_int().ok_or(SimErrorReason::InvalidInput("getarray"))?;
+                    self.store((a0 as u32).wrapping_add(4 * i as u32), value)?;
+                }
+                Some(len)
+            },
+            RuntimeFunction::PutInt => {
+                self.io.write_int(a0).map_err(output_error)?;
+                None
+            },
+            RuntimeFunction::PutCh => {
+                self.io.write_char(a0).map_err(output_error)?;
+                None
+            },
+            RuntimeFunction::PutArray => {
+                let values = (0..a0.max(0))
+                    .map(|i| self.load((a1 as u32).wrapping_add(4 * i as u32)))
+                    .collect::<Result<Vec<i32>, SimErrorReason>>()?;
+                self.io.write_array(&values).map_err(output_error)?;
+                None
+            },
+            RuntimeFunction::StartTime | RuntimeFunction::StopTime => None
+        };
+        for reg in CALLER_SAVED {
+            self.regs[reg] = CLOBBERED;
+        }
+        if let Some(result) = result {
+            self.regs[A0] = result;
+        }
+        Ok(())
+    }
+
+    /// 找到地址对应的内存以及在其中的下标
+    fn locate(&mut self, address: u32) -> Result<(&mut Vec<u8>, usize), SimErrorReason> {
+        if !address.is_multiple_of(4) {
+            return Err(SimErrorReason::MisalignedAccess(address));
+        }
+        let in_data = address.checked_sub(DATA_BASE).map(|i| i as usize).filter(|&i| i + 4 <= self.data.len());
+        let in_stack = address.checked_sub(STACK_TOP - STACK_SIZE).map(|i| i as usize).filter(|&i| i + 4 <= self.stack.len());
+        match (in_data, in_stack) {
+            (Some(index), _) => Ok((&mut self.data, index)),
+            (_, Some(index)) => Ok((&mut self.stack, index)),
+            _ => Err(SimErrorReason::InvalidAddress(address))
+        }
+    }
+
+    fn load(&mut self, address: u32) -> Result<i32, SimErrorReason> {
+        let (memory, index) = self.locate(address)?;
+        Ok(i32::from_le_bytes(memory[index..index + 4].try_into().unwrap()))
+    }
+
+    fn store(&mut self, address: u32, value: i32) -> Result<(), SimErrorReason> {
+        let (memory, index) = self.locate(address)?;
+        memory[index..index + 4].copy_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+}
+
+/// 计算一条运算指令的结果
+/// 除以 0 不会出错：div 得到 -1，rem 得到被除数，和 RV32M 的规定一致
+fn alu(op: BinaryOp, l: i32, r: i32) -> i32 {
+    evaluate_binary(op, l, r).unwrap_or(match op {
+        BinaryOp::Div => -1,
+        _ => l
+    })
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::ass_gen::{AsmConfig, AssGen};
+use crate::function_ast::CompUnit;
+use crate::ir_gen::IrGen;
+use crate::ir_interp::Interpreter;
+use crate::ir_opt::{optimize_program, OptConfig};
+use crate::riscv_sim;
+use crate::sysy::CompUnitParser;
+
+// 随机 SysY 程序测试（类似 Csmith）：生成没有未定义行为的随机程序，打印为源程序之后在同一个进程中编译，
+// 分别解释 -koopa 输出的 IR 和模拟 -riscv 输出的汇编，和语法树上的参考求值比较
+// 出错的程序会被自动缩减之后打印出来
+// 前端目前只支持 main 中的声明、赋值和 return，因此生成的程序中没有循环和分支
+mod generate;
+mod evaluate;
+mod shrink;
+
+use evaluate::evaluate_program;
+
+/// 生成程序使用的伪随机数生成器（splitmix64），同一个种子总是生成同一个程序
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// [0, n) 中的随机数
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// 以 percent% 的概率返回 true
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// 编译或者执行随机程序时发现的问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Failure {
+    /// 打印出的源程序不能重新解析
+    Reparse(String),
+    /// 编译器拒绝了合法的程序
+    Rejected { stage: &'static str, message: String },
+    /// 编译器的某个阶段 panic
+    Panic { stage: &'static str, message: String },
+    /// 执行时出错
+    Trap { path: &'static str, message: String },
+    /// 执行结果和参考求值不同
+    Mismatch { path: &'static str, expected: i32, actual: i32 }
+}
+
+impl Failure {
+    /// 缩减时要求候选以同样的方式出错：同一种问题，出现在同一个阶段
+    fn same_kind(&self, other: &Failure) -> bool {
+        match (self, other) {
+            (Failure::Reparse(_), Failure::Reparse(_)) => true,
+            (Failure::Rejected { stage: a, .. }, Failure::Rejected { stage: b, .. })
+            | (Failure::Panic { stage: a, .. }, Failure::Panic { stage: b, .. })
+            | (Failure::Trap { path: a, .. }, Failure::Trap { path: b, .. })
+            | (Failure::Mismatch { path: a, .. }, Failure::Mismatch { path: b, .. }) => a == b,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Reparse(message) => write!(f, "the printed program does not parse: {}", message),
+            Failure::Rejected { stage, message } => write!(f, "{} rejected a valid program: {}", stage, message),
+            Failure::Panic { stage, message } => write!(f, "{} panicked: {}", stage, message),
+            Failure::Trap { path, message } => write!(f, "running the {} output failed: {}", path, message),
+            Failure::Mismatch { path, expected, actual } => {
+                write!(f, "the {} output returned {}, but the reference evaluation returned {}", path, actual, expected)
+            }
+        }
+    }
+}
+
+/// 用 first_seed 开始的 count 个种子生成程序并检查，打印发现的问题，没有问题时返回 true
+pub fn run(first_seed: u64, count: u64, opt_config: &OptConfig, asm_config: &AsmConfig) -> bool {
+    // 编译器的 panic 作为测试结果汇报，不打印默认的 panic 信息
+    panic::set_hook(Box::new(|_| {}));
+    let mut failed = 0;
+    for seed in first_seed..first_seed + count {
+        let program = generate::generate_program(&mut Rng::new(seed));
+        let Err(failure) = check(&program, opt_config, asm_config) else {
+            continue;
+        };
+        failed += 1;
+        let reduced = shrink::shrink_program(program, |candidate| {
+            evaluate_program(candidate).is_ok()
+                && check(candidate, opt_config, asm_config).is_err_and(|other| failure.same_kind(&other))
+        });
+        // 缩减之后的问题描述中的数值可能变化，重新检查一次
+        let reduced_failure = check(&reduced, opt_config, asm_config).err().unwrap_or(failure);
+        println!("seed {}: {}\nreduced program:\n{}", seed, reduced_failure, reduced);
+    }
+    let _ = panic::take_hook();
+    println!("{} programs checked; {} failed", count, failed);
+    failed == 0
+}
+
+/// 编译并执行一个合法的程序，和参考求值比较
+fn check(program: &CompUnit, opt_config: &OptConfig, asm_config: &AsmConfig) -> Result<(), Failure> {
+    let expected = evaluate_program(program).expect("generated programs are valid");
+    let source = program.to_string();
+
+    let ast = catch_panic("the parser", || CompUnitParser::new().parse(&source).map_err(|e| e.to_string()))?
+        .map_err(Failure::Reparse)?;
+    let mut program = catch_panic("IrGen", || {
+        let mut generator = IrGen::new();
+        generator.generate_koopa_ir(ast).ok_or_else(|| {
+            let messages: Vec<String> = generator.get_problems().into_iter().map(|problem| problem.message).collect();
+            messages.join("; ")
+        })
+    })?.map_err(|message| Failure::Rejected { stage: "IrGen", message })?;
+    catch_panic("the IR optimizer", || optimize_program(&mut program, opt_config))?;
+
+    // -koopa 的路径：输出 IR 文本，重新解析之后解释执行
+    let text = catch_panic("the Koopa IR printer", || {
+        let mut generator = koopa::back::KoopaGenerator::new(Vec::new());
+        generator.generate_on(&program).unwrap();
+        String::from_utf8(generator.writer()).unwrap()
+    })?;
+    let reparsed = koopa::front::Driver::from(text.as_str()).generate_program()
+        .map_err(|e| Failure::Rejected { stage: "the Koopa IR parser", message: format!("{:?}", e) })?;
+    let result = catch_panic("the Koopa IR interpreter", || {
+        Interpreter::new(&reparsed, std::io::empty(), std::io::sink()).run().map_err(|e| e.to_string())
+    })?;
+    compare("Koopa IR", result, expected)?;
+
+    // -riscv 的路径：生成汇编并在模拟器中执行
+    let asm = catch_panic("AssGen", || AssGen::new(&program, asm_config).generate_program())?
+        .map_err(|e| Failure::Rejected { stage: "AssGen", message: e.to_string() })?;
+    let result = catch_panic("the RISC-V simulator", || {
+        riscv_sim::run(&asm, std::io::empty(), std::io::sink()).map_err(|e| e.to_string())
+    })?;
+    compare("RISC-V", result, expected)
+}
+
+fn compare(path: &'static str, result: Result<i32, String>, expected: i32) -> Result<(), Failure> {
+    match result {
+        Ok(actual) if actual == expected => Ok(()),
+        Ok(actual) => Err(Failure::Mismatch { path, expected, actual }),
+        Err(message) => Err(Failure::Trap { path, message })
+    }
+}
+
+/// 执行 f，把其中的 panic 转换为 Failure::Panic
+fn catch_panic<T>(stage: &'static str, f: impl FnOnce() -> T) -> Result<T, Failure> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| String::from("unknown panic payload"));
+        Failure::Panic { stage, message }
+    })
+}
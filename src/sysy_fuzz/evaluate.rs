@@ -0,0 +1,195 @@
+/// 此文件存放语法树上的参考求值，和编译器完全独立，用来检查编译结果
+/// - 运算按 32 位补码回绕，逻辑运算短路，和编译器的语义一致
+/// - 求值同时检查程序是否合法，缩减程序时用它排除删掉声明或者改坏除数之后的程序
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::function_ast::{
+    AddExp, AddOp, BlockItem, CompUnit, Decl, EqExp, EqOp, Exp, LAndExp, LOrExp, LVal, MulExp, MulOp, PrimaryExp,
+    RelExp, RelOp, Stmt, UnaryExp, UnaryOp
+};
+
+/// 程序不合法的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    Undeclared(String),
+    Uninitialized(String),
+    Duplicate(String),
+    AssignToConst(String),
+    /// 常量的初始值中用到了变量
+    NotConstant(String),
+    DivisionByZero
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::Undeclared(name) => write!(f, "use of undeclared identifier '{}'", name),
+            EvalError::Uninitialized(name) => write!(f, "use of uninitialized variable '{}'", name),
+            EvalError::Duplicate(name) => write!(f, "duplicate symbol '{}'", name),
+            EvalError::AssignToConst(name) => write!(f, "assignment to const '{}'", name),
+            EvalError::NotConstant(name) => write!(f, "variable '{}' used in a const definition", name),
+            EvalError::DivisionByZero => write!(f, "division by zero")
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Symbol {
+    Const(i32),
+    /// 没有赋值的变量为 None
+    Var(Option<i32>)
+}
+
+/// 求出 main 的返回值，没有 return 时和编译器一样返回 0
+pub fn evaluate_program(program: &CompUnit) -> Result<i32, EvalError> {
+    let mut evaluator = Evaluator { symbols: HashMap::new(), constant: false };
+    for item in &program.func_def.block.block_items {
+        match item {
+            BlockItem::Decl(Decl::ConstDecl(const_decl)) => {
+                for def in &const_decl.const_def {
+                    evaluator.constant = true;
+                    let value = evaluator.exp(&def.const_init_val.const_exp.exp)?;
+                    evaluator.constant = false;
+                    evaluator.declare(&def.ident, Symbol::Const(value))?;
+                }
+            },
+            BlockItem::Decl(Decl::VarDecl(var_decl)) => {
+                for def in &var_decl.var_def {
+                    let value = match &def.init_val {
+                        Some(init_val) => Some(evaluator.exp(&init_val.exp)?),
+                        None => None
+                    };
+                    evaluator.declare(&def.ident, Symbol::Var(value))?;
+                }
+            },
+            BlockItem::Stmt(Stmt::LValExp(LVal::Ident(name, _), exp, _)) => {
+                let value = evaluator.exp(exp)?;
+                match evaluator.symbols.get_mut(name) {
+                    Some(Symbol::Var(var)) => *var = Some(value),
+                    Some(Symbol::Const(_)) => return Err(EvalError::AssignToConst(name.clone())),
+                    None => return Err(EvalError::Undeclared(name.clone()))
+                }
+            },
+            BlockItem::Stmt(Stmt::Exp(exp, _)) => return evaluator.exp(exp)
+        }
+    }
+    Ok(0)
+}
+
+struct Evaluator {
+    symbols: HashMap<String, Symbol>,
+    /// 正在计算常量的初始值
+    constant: bool
+}
+
+impl Evaluator {
+    fn declare(&mut self, name: &str, symbol: Symbol) -> Result<(), EvalError> {
+        if self.symbols.insert(name.to_string(), symbol).is_some() {
+            return Err(EvalError::Duplicate(name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn exp(&self, exp: &Exp) -> Result<i32, EvalError> {
+        self.l_or_exp(&exp.l_or_exp)
+    }
+
+    fn l_or_exp(&self, exp: &LOrExp) -> Result<i32, EvalError> {
+        match exp {
+            LOrExp::LAndExp(exp) => self.l_and_exp(exp),
+            LOrExp::CompoundLOrExp(lhs, rhs, _) => Ok((self.l_or_exp(lhs)? != 0 || self.l_and_exp(rhs)? != 0) as i32)
+        }
+    }
+
+    fn l_and_exp(&self, exp: &LAndExp) -> Result<i32, EvalError> {
+        match exp {
+            LAndExp::EqExp(exp) => self.eq_exp(exp),
+            LAndExp::CompoundLAndExp(lhs, rhs, _) => Ok((self.l_and_exp(lhs)? != 0 && self.eq_exp(rhs)? != 0) as i32)
+        }
+    }
+
+    fn eq_exp(&self, exp: &EqExp) -> Result<i32, EvalError> {
+        match exp {
+            EqExp::RelExp(exp) => self.rel_exp(exp),
+            EqExp::CompoundEqExp(lhs, rhs, op, _) => {
+                let (l, r) = (self.eq_exp(lhs)?, self.rel_exp(rhs)?);
+                Ok(match op {
+                    EqOp::Eq => l == r,
+                    EqOp::Ne => l != r
+                } as i32)
+            }
+        }
+    }
+
+    fn rel_exp(&self, exp: &RelExp) -> Result<i32, EvalError> {
+        match exp {
+            RelExp::AddExp(exp) => self.add_exp(exp),
+            RelExp::CompoundRelExp(lhs, rhs, op, _) => {
+                let (l, r) = (self.rel_exp(lhs)?, self.add_exp(rhs)?);
+                Ok(match op {
+                    RelOp::Lt => l < r,
+                    RelOp::Gt => l > r,
+                    RelOp::Le => l <= r,
+                    RelOp::Ge => l >= r
+                } as i32)
+            }
+        }
+    }
+
+    fn add_exp(&self, exp: &AddExp) -> Result<i32, EvalError> {
+        match exp {
+            AddExp::MulExp(exp) => self.mul_exp(exp),
+            AddExp::CompoundAddExp(lhs, rhs, op, _) => {
+                let (l, r) = (self.add_exp(lhs)?, self.mul_exp(rhs)?);
+                Ok(match op {
+                    AddOp::Plus => l.wrapping_add(r),
+                    AddOp::Minus => l.wrapping_sub(r)
+                })
+            }
+        }
+    }
+
+    fn mul_exp(&self, exp: &MulExp) -> Result<i32, EvalError> {
+        match exp {
+            MulExp::UnaryExp(exp) => self.unary_exp(exp),
+            MulExp::CompoundMulExp(lhs, rhs, op, _) => {
+                let (l, r) = (self.mul_exp(lhs)?, self.unary_exp(rhs)?);
+                match op {
+                    MulOp::Mul => Ok(l.wrapping_mul(r)),
+                    MulOp::Div | MulOp::Mod if r == 0 => Err(EvalError::DivisionByZero),
+                    MulOp::Div => Ok(l.wrapping_div(r)),
+                    MulOp::Mod => Ok(l.wrapping_rem(r))
+                }
+            }
+        }
+    }
+
+    fn unary_exp(&self, exp: &UnaryExp) -> Result<i32, EvalError> {
+        match exp {
+            UnaryExp::PrimaryExp(exp) => self.primary_exp(exp),
+            UnaryExp::CompoundUnaryExp(op, exp, _) => {
+                let value = self.unary_exp(exp)?;
+                Ok(match op {
+                    UnaryOp::Plus => value,
+                    UnaryOp::Minus => value.wrapping_neg(),
+                    UnaryOp::Not => (value == 0) as i32
+                })
+            }
+        }
+    }
+
+    fn primary_exp(&self, exp: &PrimaryExp) -> Result<i32, EvalError> {
+        match exp {
+            PrimaryExp::Exp(exp) => self.exp(exp),
+            PrimaryExp::Number(value, _) => Ok(*value),
+            PrimaryExp::LVal(LVal::Ident(name, _)) => match self.symbols.get(name) {
+                Some(Symbol::Const(value)) => Ok(*value),
+                Some(Symbol::Var(_)) if self.constant => Err(EvalError::NotConstant(name.clone())),
+                Some(Symbol::Var(Some(value))) => Ok(*value),
+                Some(Symbol::Var(None)) => Err(EvalError::Uninitialized(name.clone())),
+                None => Err(EvalError::Undeclared(name.clone()))
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+/// 此文件存放随机程序的生成
+/// - 只生成前端支持的语法：main 函数中的常量声明、变量声明、赋值和最后的 return
+/// - 变量在赋值之前不会被读取，常量的初始值只用到字面量和之前的常量
+/// - 除法和取模的除数写成 `(e % k + m)` 的形式，其中 m = k + 1，取值范围是 [2, 2k]，不会出现除以 0 或者 INT_MIN / -1
+use crate::function_ast::{
+    AddExp, AddOp, BType, Block, BlockItem, CompUnit, ConstDecl, ConstDef, ConstExp, ConstInitVal, Decl, EqExp, EqOp,
+    Exp, FuncDef, FuncType, InitVal, LAndExp, LOrExp, LVal, MulExp, MulOp, PrimaryExp, RelExp, RelOp, Span, Stmt,
+    UnaryExp, UnaryOp, VarDecl, VarDef
+};
+
+use super::Rng;
+
+/// 生成的程序中没有源码位置
+const NO_SPAN: Span = Span { start: 0, end: 0 };
+/// 表达式中运算的最大嵌套层数
+const MAX_DEPTH: u32 = 4;
+
+/// 生成一个随机程序
+pub(super) fn generate_program(rng: &mut Rng) -> CompUnit {
+    let mut generator = Generator { rng, consts: Vec::new(), vars: Vec::new(), next_id: 0 };
+    let mut block_items = Vec::new();
+    for _ in 0..generator.rng.below(12) + 3 {
+        block_items.push(generator.block_item());
+    }
+    block_items.push(BlockItem::Stmt(Stmt::Exp(generator.exp(MAX_DEPTH, false), NO_SPAN)));
+    CompUnit {
+        func_def: FuncDef {
+            func_type: FuncType::Int,
+            ident: String::from("main"),
+            block: Block { block_items },
+            span: NO_SPAN
+        }
+    }
+}
+
+struct Generator<'r> {
+    rng: &'r mut Rng,
+    /// 已经声明的常量
+    consts: Vec<String>,
+    /// 已经声明的变量，以及它是否已经被赋值
+    vars: Vec<(String, bool)>,
+    /// 下一个标识符的编号
+    next_id: usize
+}
+
+impl Generator<'_> {
+    fn block_item(&mut self) -> BlockItem {
+        match self.rng.below(100) {
+            0..25 => BlockItem::Decl(Decl::ConstDecl(self.const_decl())),
+            25..60 => BlockItem::Decl(Decl::VarDecl(self.var_decl())),
+            _ if self.vars.is_empty() => BlockItem::Decl(Decl::VarDecl(self.var_decl())),
+            _ => {
+                let index = self.rng.below(self.vars.len());
+                let exp = self.exp(MAX_DEPTH, false);
+                self.vars[index].1 = true;
+                BlockItem::Stmt(Stmt::LValExp(LVal::Ident(self.vars[index].0.clone(), NO_SPAN), exp, NO_SPAN))
+            }
+        }
+    }
+
+    fn const_decl(&mut self) -> ConstDecl {
+        let mut const_def = Vec::new();
+        for _ in 0..self.rng.below(3) + 1 {
+            let exp = self.exp(MAX_DEPTH, true);
+            let ident = self.new_ident("c");
+            self.consts.push(ident.clone());
+            const_def.push(ConstDef { ident, const_init_val: ConstInitVal { const_exp: ConstExp { exp } }, span: NO_SPAN });
+        }
+        ConstDecl { b_type: BType::Int, const_def }
+    }
+
+    fn var_decl(&mut self) -> VarDecl {
+        let mut var_def = Vec::new();
+        for _ in 0..self.rng.below(3) + 1 {
+            let init_val = self.rng.chance(80).then(|| InitVal { exp: self.exp(MAX_DEPTH, false) });
+            let ident = self.new_ident("v");
+            self.vars.push((ident.clone(), init_val.is_some()));
+            var_def.push(VarDef { ident, init_val, span: NO_SPAN });
+        }
+        VarDecl { b_type: BType::Int, var_def }
+    }
+
+    fn new_ident(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}{}", prefix, self.next_id)
+    }
+
+    // 以下函数按语法的层次生成表达式，只有生成二元运算时嵌套层数减一
+    // constant 为 true 时只使用字面量和常量
+
+    fn exp(&mut self, depth: u32, constant: bool) -> Exp {
+        Exp { l_or_exp: self.l_or_exp(depth, constant) }
+    }
+
+    fn l_or_exp(&mut self, depth: u32, constant: bool) -> LOrExp {
+        if depth > 0 && self.rng.chance(5) {
+            LOrExp::CompoundLOrExp(Box::new(self.l_or_exp(depth - 1, constant)), self.l_and_exp(depth - 1, constant), NO_SPAN)
+        } else {
+            LOrExp::LAndExp(self.l_and_exp(depth, constant))
+        }
+    }
+
+    fn l_and_exp(&mut self, depth: u32, constant: bool) -> LAndExp {
+        if depth > 0 && self.rng.chance(5) {
+            LAndExp::CompoundLAndExp(Box::new(self.l_and_exp(depth - 1, constant)), self.eq_exp(depth - 1, constant), NO_SPAN)
+        } else {
+            LAndExp::EqExp(self.eq_exp(depth, constant))
+        }
+    }
+
+    fn eq_exp(&mut self, depth: u32, constant: bool) -> EqExp {
+        if depth > 0 && self.rng.chance(8) {
+            let op = if self.rng.chance(50) { EqOp::Eq } else { EqOp::Ne };
+            EqExp::CompoundEqExp(Box::new(self.eq_exp(depth - 1, constant)), self.rel_exp(depth - 1, constant), op, NO_SPAN)
+        } else {
+            EqExp::RelExp(self.rel_exp(depth, constant))
+        }
+    }
+
+    fn rel_exp(&mut self, depth: u32, constant: bool) -> RelExp {
+        if depth > 0 && self.rng.chance(10) {
+            let op = [RelOp::Lt, RelOp::Gt, RelOp::Le, RelOp::Ge][self.rng.below(4)].clone();
+            RelExp::CompoundRelExp(Box::new(self.rel_exp(depth - 1, constant)), self.add_exp(depth - 1, constant), op, NO_SPAN)
+        } else {
+            RelExp::AddExp(self.add_exp(depth, constant))
+        }
+    }
+
+    fn add_exp(&mut self, depth: u32, constant: bool) -> AddExp {
+        if depth > 0 && self.rng.chance(40) {
+            let op = if self.rng.chance(50) { AddOp::Plus } else { AddOp::Minus };
+            AddExp::CompoundAddExp(Box::new(self.add_exp(depth - 1, constant)), self.mul_exp(depth - 1, constant), op, NO_SPAN)
+        } else {
+            AddExp::MulExp(self.mul_exp(depth, constant))
+        }
+    }
+
+    fn mul_exp(&mut self, depth: u32, constant: bool) -> MulExp {
+        if depth > 0 && self.rng.chance(35) {
+            let lhs = Box::new(self.mul_exp(depth - 1, constant));
+            match self.rng.below(3) {
+                0 => MulExp::CompoundMulExp(lhs, self.unary_exp(depth - 1, constant), MulOp::Mul, NO_SPAN),
+                1 => MulExp::CompoundMulExp(lhs, self.divisor(depth - 1, constant), MulOp::Div, NO_SPAN),
+                _ => MulExp::CompoundMulExp(lhs, self.divisor(depth - 1, constant), MulOp::Mod, NO_SPAN)
+            }
+        } else {
+            MulExp::UnaryExp(self.unary_exp(depth, constant))
+        }
+    }
+
+    /// 生成 `(e % k + m)`，作为除法和取模的除数
+    fn divisor(&mut self, depth: u32, constant: bool) -> UnaryExp {
+        let k = self.rng.below(19) as i32 + 2;
+        let e = self.unary_exp(depth, constant);
+        let rem = MulExp::CompoundMulExp(Box::new(MulExp::UnaryExp(e)), number(k), MulOp::Mod, NO_SPAN);
+        let sum = AddExp::CompoundAddExp(Box::new(AddExp::MulExp(rem)), MulExp::UnaryExp(number(k + 1)), AddOp::Plus, NO_SPAN);
+        paren(exp_of_add(sum))
+    }
+
+    fn unary_exp(&mut self, depth: u32, constant: bool) -> UnaryExp {
+        if depth > 0 && self.rng.chance(20) {
+            let op = [UnaryOp::Plus, UnaryOp::Minus, UnaryOp::Not][self.rng.below(3)].clone();
+            UnaryExp::CompoundUnaryExp(op, Box::new(self.unary_exp(depth - 1, constant)), NO_SPAN)
+        } else {
+            UnaryExp::PrimaryExp(self.primary_exp(depth, constant))
+        }
+    }
+
+    fn primary_exp(&mut self, depth: u32, constant: bool) -> PrimaryExp {
+        if depth > 0 && self.rng.chance(20) {
+            return PrimaryExp::Exp(Box::new(self.exp(depth - 1, constant)));
+        }
+        let mut names: Vec<&String> = self.consts.iter().collect();
+        if !constant {
+            names.extend(self.vars.iter().filter(|(_, initialized)| *initialized).map(|(name, _)| name));
+        }
+        if !names.is_empty() && self.rng.chance(50) {
+            let name = names[self.rng.below(names.len())].clone();
+            return PrimaryExp::LVal(LVal::Ident(name, NO_SPAN));
+        }
+        // 大多数是小的整数，偶尔出现接近 INT_MAX 的数，检查溢出的处理
+        let value = match self.rng.below(100) {
+            0..60 => self.rng.below(11),
+            60..90 => self.rng.below(1000),
+            90..95 => i32::MAX as usize,
+            _ => self.rng.below(i32::MAX as usize)
+        };
+        PrimaryExp::Number(value as i32, NO_SPAN)
+    }
+}
+
+/// 整数字面量
+pub(super) fn number(value: i32) -> UnaryExp {
+    UnaryExp::PrimaryExp(PrimaryExp::Number(value, NO_SPAN))
+}
+
+/// 给表达式加上括号
+pub(super) fn paren(exp: Exp) -> UnaryExp {
+    UnaryExp::PrimaryExp(PrimaryExp::Exp(Box::new(exp)))
+}
+
+/// 把加减表达式逐层包装为完整的表达式
+pub(super) fn exp_of_add(add_exp: AddExp) -> Exp {
+    Exp { l_or_exp: LOrExp::LAndExp(LAndExp::EqExp(EqExp::RelExp(RelExp::AddExp(add_exp)))) }
+}
@@ -0,0 +1,235 @@
+/// 此文件存放出错程序的缩减
+/// - 每一步尝试删掉一条语句或者一个定义，或者把某个表达式换成更简单的形式
+/// - 只接受仍然合法（参考求值成功）并且仍然以同样方式出错的候选，直到没有候选可以接受
+use crate::function_ast::{
+    AddExp, BlockItem, CompUnit, Decl, EqExp, Exp, LAndExp, LOrExp, MulExp, PrimaryExp, RelExp, Stmt, UnaryExp
+};
+
+use super::generate::number;
+
+/// 最多尝试的候选个数，避免缩减很大的程序时花费太多时间
+const MAX_ATTEMPTS: usize = 5000;
+
+/// 缩减程序，still_fails 判断候选是否仍然以同样的方式出错
+pub(super) fn shrink_program(program: CompUnit, mut still_fails: impl FnMut(&CompUnit) -> bool) -> CompUnit {
+    let mut current = program;
+    let mut attempts = 0;
+    'outer: loop {
+        for candidate in candidates(&current) {
+            attempts += 1;
+            if attempts > MAX_ATTEMPTS {
+                break 'outer;
+            }
+            if still_fails(&candidate) {
+                current = candidate;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+    current
+}
+
+/// 程序的所有一步缩减，删除语句的候选在前
+fn candidates(program: &CompUnit) -> Vec<CompUnit> {
+    let items = &program.func_def.block.block_items;
+    let with_items = |block_items: Vec<BlockItem>| {
+        let mut candidate = program.clone();
+        candidate.func_def.block.block_items = block_items;
+        candidate
+    };
+    let mut result = Vec::new();
+    // 最后的 return 不删除
+    for index in 0..items.len().saturating_sub(1) {
+        let mut block_items = items.clone();
+        block_items.remove(index);
+        result.push(with_items(block_items));
+    }
+    for (index, item) in items.iter().enumerate() {
+        for simpler in item.shrink() {
+            let mut block_items = items.clone();
+            block_items[index] = simpler;
+            result.push(with_items(block_items));
+        }
+    }
+    result
+}
+
+/// 一步缩减，返回所有比自己简单的候选
+trait Shrink: Sized {
+    fn shrink(&self) -> Vec<Self>;
+}
+
+impl Shrink for BlockItem {
+    fn shrink(&self) -> Vec<Self> {
+        let mut result = Vec::new();
+        match self {
+            BlockItem::Decl(Decl::ConstDecl(const_decl)) => {
+                for index in 0..const_decl.const_def.len() {
+                    if const_decl.const_def.len() > 1 {
+                        let mut decl = const_decl.clone();
+                        decl.const_def.remove(index);
+                        result.push(BlockItem::Decl(Decl::ConstDecl(decl)));
+                    }
+                    for exp in const_decl.const_def[index].const_init_val.const_exp.exp.shrink() {
+                        let mut decl = const_decl.clone();
+                        decl.const_def[index].const_init_val.const_exp.exp = exp;
+                        result.push(BlockItem::Decl(Decl::ConstDecl(decl)));
+                    }
+                }
+            },
+            BlockItem::Decl(Decl::VarDecl(var_decl)) => {
+                for index in 0..var_decl.var_def.len() {
+                    if var_decl.var_def.len() > 1 {
+                        let mut decl = var_decl.clone();
+                        decl.var_def.remove(index);
+                        result.push(BlockItem::Decl(Decl::VarDecl(decl)));
+                    }
+                    let Some(init_val) = &var_decl.var_def[index].init_val else {
+                        continue;
+                    };
+                    let mut decl = var_decl.clone();
+                    decl.var_def[index].init_val = None;
+                    result.push(BlockItem::Decl(Decl::VarDecl(decl)));
+                    for exp in init_val.exp.shrink() {
+                        let mut decl = var_decl.clone();
+                        decl.var_def[index].init_val.as_mut().unwrap().exp = exp;
+                        result.push(BlockItem::Decl(Decl::VarDecl(decl)));
+                    }
+                }
+            },
+            BlockItem::Stmt(Stmt::Exp(exp, span)) => {
+                result.extend(exp.shrink().into_iter().map(|exp| BlockItem::Stmt(Stmt::Exp(exp, *span))));
+            },
+            BlockItem::Stmt(Stmt::LValExp(l_val, exp, span)) => {
+                result.extend(exp.shrink().into_iter().map(|exp| BlockItem::Stmt(Stmt::LValExp(l_val.clone(), exp, *span))));
+            }
+        }
+        result
+    }
+}
+
+// 以下二元运算的缩减方式相同：换成左边或者右边的操作数，或者缩减其中一个操作数
+
+impl Shrink for Exp {
+    fn shrink(&self) -> Vec<Self> {
+        self.l_or_exp.shrink().into_iter().map(|l_or_exp| Exp { l_or_exp }).collect()
+    }
+}
+
+impl Shrink for LOrExp {
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            LOrExp::LAndExp(exp) => exp.shrink().into_iter().map(LOrExp::LAndExp).collect(),
+            LOrExp::CompoundLOrExp(lhs, rhs, span) => {
+                let mut result = vec![(**lhs).clone(), LOrExp::LAndExp(rhs.clone())];
+                result.extend(lhs.shrink().into_iter().map(|lhs| LOrExp::CompoundLOrExp(Box::new(lhs), rhs.clone(), *span)));
+                result.extend(rhs.shrink().into_iter().map(|rhs| LOrExp::CompoundLOrExp(lhs.clone(), rhs, *span)));
+                result
+            }
+        }
+    }
+}
+
+impl Shrink for LAndExp {
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            LAndExp::EqExp(exp) => exp.shrink().into_iter().map(LAndExp::EqExp).collect(),
+            LAndExp::CompoundLAndExp(lhs, rhs, span) => {
+                let mut result = vec![(**lhs).clone(), LAndExp::EqExp(rhs.clone())];
+                result.extend(lhs.shrink().into_iter().map(|lhs| LAndExp::CompoundLAndExp(Box::new(lhs), rhs.clone(), *span)));
+                result.extend(rhs.shrink().into_iter().map(|rhs| LAndExp::CompoundLAndExp(lhs.clone(), rhs, *span)));
+                result
+            }
+        }
+    }
+}
+
+impl Shrink for EqExp {
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            EqExp::RelExp(exp) => exp.shrink().into_iter().map(EqExp::RelExp).collect(),
+            EqExp::CompoundEqExp(lhs, rhs, op, span) => {
+                let mut result = vec![(**lhs).clone(), EqExp::RelExp(rhs.clone())];
+                result.extend(lhs.shrink().into_iter().map(|lhs| EqExp::CompoundEqExp(Box::new(lhs), rhs.clone(), op.clone(), *span)));
+                result.extend(rhs.shrink().into_iter().map(|rhs| EqExp::CompoundEqExp(lhs.clone(), rhs, op.clone(), *span)));
+                result
+            }
+        }
+    }
+}
+
+impl Shrink for RelExp {
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            RelExp::AddExp(exp) => exp.shrink().into_iter().map(RelExp::AddExp).collect(),
+            RelExp::CompoundRelExp(lhs, rhs, op, span) => {
+                let mut result = vec![(**lhs).clone(), RelExp::AddExp(rhs.clone())];
+                result.extend(lhs.shrink().into_iter().map(|lhs| RelExp::CompoundRelExp(Box::new(lhs), rhs.clone(), op.clone(), *span)));
+                result.extend(rhs.shrink().into_iter().map(|rhs| RelExp::CompoundRelExp(lhs.clone(), rhs, op.clone(), *span)));
+                result
+            }
+        }
+    }
+}
+
+impl Shrink for AddExp {
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            AddExp::MulExp(exp) => exp.shrink().into_iter().map(AddExp::MulExp).collect(),
+            AddExp::CompoundAddExp(lhs, rhs, op, span) => {
+                let mut result = vec![(**lhs).clone(), AddExp::MulExp(rhs.clone())];
+                result.extend(lhs.shrink().into_iter().map(|lhs| AddExp::CompoundAddExp(Box::new(lhs), rhs.clone(), op.clone(), *span)));
+                result.extend(rhs.shrink().into_iter().map(|rhs| AddExp::CompoundAddExp(lhs.clone(), rhs, op.clone(), *span)));
+                result
+            }
+        }
+    }
+}
+
+impl Shrink for MulExp {
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            MulExp::UnaryExp(exp) => exp.shrink().into_iter().map(MulExp::UnaryExp).collect(),
+            MulExp::CompoundMulExp(lhs, rhs, op, span) => {
+                let mut result = vec![(**lhs).clone(), MulExp::UnaryExp(rhs.clone())];
+                result.extend(lhs.shrink().into_iter().map(|lhs| MulExp::CompoundMulExp(Box::new(lhs), rhs.clone(), op.clone(), *span)));
+                result.extend(rhs.shrink().into_iter().map(|rhs| MulExp::CompoundMulExp(lhs.clone(), rhs, op.clone(), *span)));
+                result
+            }
+        }
+    }
+}
+
+impl Shrink for UnaryExp {
+    fn shrink(&self) -> Vec<Self> {
+        match self {
+            UnaryExp::CompoundUnaryExp(op, exp, span) => {
+                let mut result = vec![(**exp).clone()];
+                result.extend(exp.shrink().into_iter().map(|exp| UnaryExp::CompoundUnaryExp(op.clone(), Box::new(exp), *span)));
+                result
+            },
+            UnaryExp::PrimaryExp(PrimaryExp::Exp(exp)) => {
+                // 括号中只有一个一元表达式时去掉括号
+                let mut result: Vec<UnaryExp> = single_unary(exp).into_iter().cloned().collect();
+                result.extend(exp.shrink().into_iter().map(|exp| UnaryExp::PrimaryExp(PrimaryExp::Exp(Box::new(exp)))));
+                result
+            },
+            UnaryExp::PrimaryExp(PrimaryExp::Number(value, _)) => {
+                let mut values = vec![0, 1, value / 2];
+                values.retain(|v| v.unsigned_abs() < value.unsigned_abs());
+                values.dedup();
+                values.into_iter().map(number).collect()
+            },
+            UnaryExp::PrimaryExp(PrimaryExp::LVal(_)) => vec![number(0)]
+        }
+    }
+}
+
+/// 如果表达式只是一个一元表达式，返回它
+fn single_unary(exp: &Exp) -> Option<&UnaryExp> {
+    let LOrExp::LAndExp(LAndExp::EqExp(EqExp::RelExp(RelExp::AddExp(AddExp::MulExp(MulExp::UnaryExp(unary)))))) = &exp.l_or_exp else {
+        return None;
+    };
+    Some(unary)
+}
@@ -0,0 +1,105 @@
+use std::io::{self, BufRead, Bytes, Write};
+
+// SysY 运行时库（libsysy）的输入输出部分，Koopa IR 解释器和 RISC-V 模拟器共用
+// 数组参数在两者中的表示不同，由调用方负责读写
+
+/// SysY 运行时库中的函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeFunction {
+    GetInt,
+    GetCh,
+    GetArray,
+    PutInt,
+    PutCh,
+    PutArray,
+    /// starttime/stoptime 只用于计时，什么也不做
+    StartTime,
+    StopTime
+}
+
+impl RuntimeFunction {
+    /// 根据函数名（不含 @）查找运行时库函数
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "getint" => Self::GetInt,
+            "getch" => Self::GetCh,
+            "getarray" => Self::GetArray,
+            "putint" => Self::PutInt,
+            "putch" => Self::PutCh,
+            "putarray" => Self::PutArray,
+            "starttime" | "_sysy_starttime" => Self::StartTime,
+            "stoptime" | "_sysy_stoptime" => Self::StopTime,
+            _ => return None
+        })
+    }
+}
+
+/// 运行时库使用的输入和输出，行为和 libsysy 保持一致
+pub struct SysyIo<R: BufRead, W: Write> {
+    input: Bytes<R>,
+    /// 读取整数时多读的一个字节
+    peeked: Option<u8>,
+    output: W
+}
+
+impl<R: BufRead, W: Write> SysyIo<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input: input.bytes(), peeked: None, output }
+    }
+
+    /// 把缓冲的输出全部写出
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    /// getch：读取一个字节，输入结束时返回 -1
+    pub fn read_char(&mut self) -> i32 {
+        self.next_byte().map_or(-1, |b| b as i32)
+    }
+
+    /// getint：和 scanf("%d") 一样，跳过空白，读取可选的符号和若干数字，超出 i32 的部分回绕
+    /// 读不到整数时返回 None
+    pub fn read_int(&mut self) -> Option<i32> {
+        let mut byte = self.next_byte();
+        while byte.is_some_and(|b| b.is_ascii_whitespace()) {
+            byte = self.next_byte();
+        }
+        let negative = byte == Some(b'-');
+        if matches!(byte, Some(b'-' | b'+')) {
+            byte = self.next_byte();
+        }
+        if !byte.is_some_and(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut value: i32 = 0;
+        while let Some(digit) = byte.filter(u8::is_ascii_digit) {
+            value = value.wrapping_mul(10).wrapping_add((digit - b'0') as i32);
+            byte = self.next_byte();
+        }
+        self.peeked = byte;
+        Some(if negative { value.wrapping_neg() } else { value })
+    }
+
+    /// putint
+    pub fn write_int(&mut self, value: i32) -> io::Result<()> {
+        write!(self.output, "{}", value)
+    }
+
+    /// putch：只输出低 8 位
+    pub fn write_char(&mut self, value: i32) -> io::Result<()> {
+        self.output.write_all(&[value as u8])
+    }
+
+    /// putarray：输出 "n: a0 a1 ..." 并换行
+    pub fn write_array(&mut self, values: &[i32]) -> io::Result<()> {
+        write!(self.output, "{}:", values.len())?;
+        for value in values {
+            write!(self.output, " {}", value)?;
+        }
+        writeln!(self.output)
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        self.peeked.take().or_else(|| self.input.next().and_then(Result::ok))
+    }
+}
@@ -0,0 +1,98 @@
+//! 语法树的打印：缩进的文本和 JSON 两种格式
+
+mod common;
+
+use compiler::function_ast::dump::{dump_json, dump_tree};
+use compiler::Compiler;
+
+const SOURCE: &str = "int main() {\n    int x = 0x10;\n    x = -x;\n    return x % 3;\n}\n";
+
+#[test]
+fn tree_shows_kinds_values_and_spans() {
+    let ast = Compiler::default().session().parse(SOURCE).unwrap();
+    let expected = "\
+CompUnit > FuncDef func_type=\"int\" ident=\"main\" @1:1-5:2
+  Block
+    BlockItem::Decl > Decl::VarDecl > VarDecl b_type=\"int\"
+      VarDef ident=\"x\" @2:9-2:17
+        Exp > LOrExp::LAndExp > LAndExp::EqExp > EqExp::RelExp > RelExp::AddExp > AddExp::MulExp > MulExp::UnaryExp > UnaryExp::PrimaryExp > PrimaryExp::Number value=16 @2:13-2:17
+    BlockItem::Stmt > Stmt::LValExp @3:5-3:12
+      LVal::Ident ident=\"x\" @3:5-3:6
+      Exp > LOrExp::LAndExp > LAndExp::EqExp > EqExp::RelExp > RelExp::AddExp > AddExp::MulExp > MulExp::UnaryExp > UnaryExp::CompoundUnaryExp op=\"-\" @3:9-3:11
+        UnaryExp::PrimaryExp > PrimaryExp::LVal > LVal::Ident ident=\"x\" @3:10-3:11
+    BlockItem::Stmt > Stmt::Exp @4:5-4:18
+      Exp > LOrExp::LAndExp > LAndExp::EqExp > EqExp::RelExp > RelExp::AddExp > AddExp::MulExp > MulExp::CompoundMulExp op=\"%\" @4:12-4:17
+        MulExp::UnaryExp > UnaryExp::PrimaryExp > PrimaryExp::LVal > LVal::Ident ident=\"x\" @4:12-4:13
+        UnaryExp::PrimaryExp > PrimaryExp::Number value=3 @4:16-4:17
+";
+    assert_eq!(dump_tree(&ast, SOURCE), expected);
+}
+
+#[test]
+fn operators_and_literals_have_spans() {
+    let source = "int main() {\n    return 1 + 2 * 3 < 4 == 5 && !6 || 7 - 8 / (9 >= 10) != 0;\n}\n";
+    let ast = Compiler::default().session().parse(source).unwrap();
+    let tree = dump_tree(&ast, source);
+    // 节点按照先序出现，运算的位置覆盖它所有的操作数
+    let expected = [
+        "Stmt::Exp @2:5-2:63",
+        "LOrExp::CompoundLOrExp op=\"||\" @2:12-2:62",
+        "LAndExp::CompoundLAndExp op=\"&&\" @2:12-2:36",
+        "EqExp::CompoundEqExp op=\"==\" @2:12-2:30",
+        "RelExp::CompoundRelExp op=\"<\" @2:12-2:25",
+        "AddExp::CompoundAddExp op=\"+\" @2:12-2:21",
+        "PrimaryExp::Number value=1 @2:12-2:13",
+        "MulExp::CompoundMulExp op=\"*\" @2:16-2:21",
+        "PrimaryExp::Number value=4 @2:24-2:25",
+        "UnaryExp::CompoundUnaryExp op=\"!\" @2:34-2:36",
+        "EqExp::CompoundEqExp op=\"!=\" @2:40-2:62",
+        "AddExp::CompoundAddExp op=\"-\" @2:40-2:57",
+        "MulExp::CompoundMulExp op=\"/\" @2:44-2:57",
+        "RelExp::CompoundRelExp op=\">=\" @2:49-2:56",
+        "PrimaryExp::Number value=10 @2:54-2:56"
+    ];
+    let mut rest = tree.as_str();
+    for node in expected {
+        let index = rest.find(node).unwrap_or_else(|| panic!("缺少 {}：\n{}", node, tree));
+        rest = &rest[index + node.len()..];
+    }
+}
+
+#[test]
+fn json_has_the_same_nodes() {
+    let ast = Compiler::default().session().parse(SOURCE).unwrap();
+    let json = dump_json(&ast, SOURCE);
+    assert!(json.starts_with("{\n  \"kind\": \"CompUnit\",\n  \"children\": [\n"), "{}", json);
+    assert!(json.contains("\"kind\": \"PrimaryExp::Number\",\n"), "{}", json);
+    assert!(json.contains("\"value\": 16,\n"), "{}", json);
+    assert!(json.contains("\"op\": \"%\",\n"), "{}", json);
+    assert!(json.contains(
+        "\"span\": {\"start\": {\"line\": 3, \"column\": 5, \"offset\": 35}, \"end\": {\"line\": 3, \"column\": 6, \"offset\": 36}}"
+    ), "{}", json);
+    // 语句 x = -x; 和其中的取负运算
+    assert!(json.contains(
+        "\"span\": {\"start\": {\"line\": 3, \"column\": 5, \"offset\": 35}, \"end\": {\"line\": 3, \"column\": 12, \"offset\": 42}}"
+    ), "{}", json);
+    assert!(json.contains(
+        "\"span\": {\"start\": {\"line\": 3, \"column\": 9, \"offset\": 39}, \"end\": {\"line\": 3, \"column\": 11, \"offset\": 41}}"
+    ), "{}", json);
+    // 每个节点都是一个对象，和文本格式中的节点一一对应
+    let tree = dump_tree(&ast, SOURCE);
+    let tree_nodes = tree.lines().map(|line| line.split(" > ").count()).sum::<usize>();
+    assert_eq!(json.matches("\"kind\"").count(), tree_nodes);
+    assert_eq!(json.matches('{').count(), json.matches('}').count());
+    assert_eq!(json.matches('[').count(), json.matches(']').count());
+}
+
+#[test]
+fn dump_from_the_command_line() {
+    let ast = Compiler::default().session().parse(SOURCE).unwrap();
+    // 有语义错误的程序也可以打印语法树
+    let source = format!("{}\nint y = z;", SOURCE.trim_end().trim_end_matches('}'));
+    let (output, stderr) = common::compile_with_diagnostics("dump.c", SOURCE, "-dump-ast", &[]);
+    assert_eq!(output.unwrap(), dump_tree(&ast, SOURCE), "{}", stderr);
+    let (output, stderr) = common::compile_with_diagnostics("dump_json.c", SOURCE, "--emit=ast-json", &[]);
+    assert_eq!(output.unwrap(), dump_json(&ast, SOURCE), "{}", stderr);
+    let (output, stderr) = common::compile_with_diagnostics("dump_error.c", &format!("{}\n}}\n", source), "-dump-ast", &[]);
+    assert!(output.unwrap().contains("LVal::Ident ident=\"z\""), "{}", stderr);
+}
@@ -0,0 +1,8 @@
+int main() {
+    int a = 1;
+    int b;
+    b = a + 2;
+    a = b * b;
+    b = a - b;
+    return a + b;
+}
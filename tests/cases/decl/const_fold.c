@@ -0,0 +1,6 @@
+int main() {
+    const int n = 10, m = n * 2 + 1;
+    const int k = (m - n) % 4;
+    int x = n + m + k;
+    return x;
+}
@@ -0,0 +1,6 @@
+int main() {
+    const int max = 2147483647;
+    const int wrapped = max + 1, product = 4879009 * 841;
+    const int negated = -wrapped, quotient = wrapped / -1;
+    return (wrapped == -max - 1) + (product == -191720727) * 2 + (negated == wrapped) * 4 + (quotient == wrapped) * 8;
+}
@@ -0,0 +1,40 @@
+int main() {
+    int v0 = 1;
+    int v1 = v0 * 3 % 97 + 1;
+    int v2 = v1 * 4 % 97 + 2;
+    int v3 = v2 * 5 % 97 + 3;
+    int v4 = v3 * 6 % 97 + 4;
+    int v5 = v4 * 2 % 97 + 5;
+    int v6 = v5 * 3 % 97 + 6;
+    int v7 = v6 * 4 % 97 + 7;
+    int v8 = v7 * 5 % 97 + 8;
+    int v9 = v8 * 6 % 97 + 9;
+    int v10 = v9 * 2 % 97 + 10;
+    int v11 = v10 * 3 % 97 + 11;
+    int v12 = v11 * 4 % 97 + 12;
+    int v13 = v12 * 5 % 97 + 13;
+    int v14 = v13 * 6 % 97 + 14;
+    int v15 = v14 * 2 % 97 + 15;
+    int v16 = v15 * 3 % 97 + 16;
+    int v17 = v16 * 4 % 97 + 17;
+    int v18 = v17 * 5 % 97 + 18;
+    int v19 = v18 * 6 % 97 + 19;
+    int v20 = v19 * 2 % 97 + 20;
+    int v21 = v20 * 3 % 97 + 21;
+    int v22 = v21 * 4 % 97 + 22;
+    int v23 = v22 * 5 % 97 + 23;
+    int v24 = v23 * 6 % 97 + 24;
+    int v25 = v24 * 2 % 97 + 25;
+    int v26 = v25 * 3 % 97 + 26;
+    int v27 = v26 * 4 % 97 + 27;
+    int v28 = v27 * 5 % 97 + 28;
+    int v29 = v28 * 6 % 97 + 29;
+    v0 = v0 + v29;
+    int sum = 0;
+    sum = sum + v0 - v1 - v2 - v3 - v4 - v5;
+    sum = sum + v6 - v7 - v8 - v9 - v10 - v11;
+    sum = sum + v12 - v13 - v14 - v15 - v16 - v17;
+    sum = sum + v18 - v19 - v20 - v21 - v22 - v23;
+    sum = sum + v24 - v25 - v26 - v27 - v28 - v29;
+    return sum + v0 * 1 + v1 * 2 + v2 * 3 + v3 * 1 + v4 * 2 + v5 * 3 + v6 * 1 + v7 * 2 + v8 * 3 + v9 * 1 + v10 * 2 + v11 * 3 + v12 * 1 + v13 * 2 + v14 * 3 + v15 * 1 + v16 * 2 + v17 * 3 + v18 * 1 + v19 * 2 + v20 * 3 + v21 * 1 + v22 * 2 + v23 * 3 + v24 * 1 + v25 * 2 + v26 * 3 + v27 * 1 + v28 * 2 + v29 * 3;
+}
@@ -0,0 +1,6 @@
+int main() {
+    const int a = 1;
+    int b = a;
+    a = 2; // ERROR: cannot assign to variable 'a'
+    return b;
+}
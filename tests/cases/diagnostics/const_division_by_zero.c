@@ -0,0 +1,5 @@
+int main() {
+    const int a = 1;
+    const int b = a / (a - 1); // ERROR: division by zero in constant expression
+    return b;
+}
@@ -0,0 +1,5 @@
+int main() {
+    int a = 1;
+    const int b = a; // ERROR: variable 'a' found in const value definition
+    return b;
+}
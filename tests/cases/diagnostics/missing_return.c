@@ -0,0 +1,4 @@
+int main() { // WARNING: non-void function doesn't return a value.
+    int a = 1;
+    a = a + 1;
+}
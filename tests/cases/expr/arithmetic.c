@@ -0,0 +1,5 @@
+int main() {
+    int a = 7, b = -3;
+    int c = a * b + a / b - a % b;
+    return (c + 100) * 2 - -a;
+}
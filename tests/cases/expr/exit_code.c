@@ -0,0 +1,4 @@
+int main() {
+    int a = 1000;
+    return a - -a % 7 + 1;
+}
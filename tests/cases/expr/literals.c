@@ -0,0 +1,6 @@
+int main() {
+    int min = -2147483648;
+    int all_ones = 0xFFFFFFFF;
+    int octal = 037777777777;
+    return (min == -2147483647 - 1) + (all_ones == -1) * 2 + (octal == -1) * 4 + (0x7fffffff == 2147483647) * 8;
+}
@@ -0,0 +1,5 @@
+int main() {
+    int a = 3, b = 0;
+    int c = a > b && b <= 0 || !a;
+    return (a != b) + (a == 3) * 2 + c * 4 + (b >= a) * 8 + (a < b) * 16;
+}
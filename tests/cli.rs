@@ -0,0 +1,101 @@
+//! 命令行接口：参数顺序、标准输入输出、多个输出和退出码
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const SOURCE: &str = "int main() {\n    int a = 6;\n    return a * 7;\n}\n";
+
+/// 以给定的参数运行编译器，stdin 作为标准输入，返回退出码、标准输出和标准错误
+fn compiler(args: &[&str], stdin: &str) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .args(args)
+        .current_dir(env!("CARGO_TARGET_TMPDIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let result = child.wait_with_output().unwrap();
+    (
+        result.status.code().unwrap(),
+        String::from_utf8_lossy(&result.stdout).into_owned(),
+        String::from_utf8_lossy(&result.stderr).into_owned()
+    )
+}
+
+fn tmp(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+#[test]
+fn options_in_any_order() {
+    std::fs::write(tmp("cli_order.c"), SOURCE).unwrap();
+    let (code, _, stderr) = compiler(&["-O0", "-o", "cli_order.S", "cli_order.c", "--regalloc=greedy", "-riscv"], "");
+    assert_eq!(code, 0, "{}", stderr);
+    let (code, expected, stderr) = compiler(&["-riscv", "cli_order.c", "-o", "-", "-O0", "--regalloc=greedy"], "");
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(std::fs::read_to_string(tmp("cli_order.S")).unwrap(), expected);
+}
+
+#[test]
+fn stdin_and_stdout() {
+    let (code, stdout, stderr) = compiler(&["-koopa", "-"], SOURCE);
+    assert_eq!(code, 0, "{}", stderr);
+    assert!(stdout.contains("fun @main(): i32"), "{}", stdout);
+
+    // Koopa IR 从标准输入读取时需要指定输入格式
+    let (code, stdout, stderr) = compiler(&["--emit=asm", "--input-format=koopa", "-"], &stdout);
+    assert_eq!(code, 0, "{}", stderr);
+    assert!(stdout.contains("main:"), "{}", stdout);
+}
+
+#[test]
+fn several_outputs_at_once() {
+    std::fs::write(tmp("cli_multi.c"), SOURCE).unwrap();
+    let (code, _, stderr) = compiler(&["--emit=koopa,asm", "cli_multi.c", "-o", "cli_multi.out"], "");
+    assert_eq!(code, 0, "{}", stderr);
+    let koopa = std::fs::read_to_string(tmp("cli_multi.koopa")).unwrap();
+    let asm = std::fs::read_to_string(tmp("cli_multi.S")).unwrap();
+    assert_eq!(compiler(&["-koopa", "cli_multi.c"], "").1, koopa);
+    assert_eq!(compiler(&["-riscv", "cli_multi.c"], "").1, asm);
+
+    // 每个输出也可以有自己的路径
+    let (code, stdout, stderr) = compiler(&["--emit=asm=cli_multi_own.S", "--emit=koopa", "cli_multi.c"], "");
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(stdout, koopa);
+    assert_eq!(std::fs::read_to_string(tmp("cli_multi_own.S")).unwrap(), asm);
+}
+
+#[test]
+fn help_and_version() {
+    let (code, help, _) = compiler(&["--help"], "");
+    assert_eq!(code, 0);
+    for option in ["-koopa", "-riscv", "-dump-ast", "-run-koopa", "-run-riscv", "-diff-test", "-sysy-fuzz", "--emit", "-o ",
+                   "--input-format", "-O0", "-finline-limit", "--regalloc", "-fomit-frame-pointer",
+                   "-fno-omit-frame-pointer", "--seed", "--help", "--version"] {
+        assert!(help.contains(option), "{} is not documented", option);
+    }
+    let (code, version, _) = compiler(&["-V"], "");
+    assert_eq!(code, 0);
+    assert_eq!(version.trim(), format!("compiler {}", env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn exit_codes() {
+    // 命令行参数有误
+    for args in [&[][..], &["-koopa"], &["-koopa", "a.c", "b.c"], &["-koopa", "a.c", "-O9"], &["-koopa", "a.c", "--bogus"],
+                 &["-run-koopa", "a.c", "-o", "x"], &["--emit=ir", "a.c"], &["-sysy-fuzz", "many"]] {
+        let (code, _, stderr) = compiler(args, "");
+        assert_eq!(code, 2, "{:?}\n{}", args, stderr);
+        assert!(stderr.contains("--help"), "{}", stderr);
+    }
+    // 编译错误和读取失败
+    let (code, _, stderr) = compiler(&["-koopa", "-"], "int main() { return x; }");
+    assert_eq!(code, 1, "{}", stderr);
+    assert!(stderr.contains("1 error generated."), "{}", stderr);
+    let (code, _, stderr) = compiler(&["-koopa", "cli_missing.c"], "");
+    assert_eq!(code, 1, "{}", stderr);
+    assert!(stderr.contains("cannot read 'cli_missing.c'"), "{}", stderr);
+}
@@ -0,0 +1,89 @@
+//! 集成测试共用的辅助函数
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// 把 source 写入临时文件，以 mode（-koopa 或 -riscv）和给定的参数编译，返回输出文件的内容
+#[allow(dead_code)]
+pub fn compile(name: &str, source: &str, mode: &str, options: &[&str]) -> String {
+    try_compile(&format!("{}.c", name), source, mode, options)
+        .unwrap_or_else(|stderr| panic!("编译失败：{} {:?}\n{}", mode, options, stderr))
+}
+
+/// 把 source 写入名为 file_name 的临时文件并编译
+/// 成功时返回输出文件的内容，失败时返回编译器的错误输出
+#[allow(dead_code)]
+pub fn try_compile(file_name: &str, source: &str, mode: &str, options: &[&str]) -> Result<String, String> {
+    match compile_with_diagnostics(file_name, source, mode, options) {
+        (Some(output), _) => Ok(output),
+        (None, stderr) => Err(stderr)
+    }
+}
+
+/// 把 source 写入名为 file_name 的临时文件并编译
+/// 返回输出文件的内容（编译失败时为 None）和编译器的错误输出，成功编译时错误输出中可能有警告
+pub fn compile_with_diagnostics(file_name: &str, source: &str, mode: &str, options: &[&str]) -> (Option<String>, String) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let input = dir.join(file_name);
+    let output = dir.join(format!("{}.out", file_name));
+    std::fs::write(&input, source).unwrap();
+    let result = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg(mode)
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(options)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&result.stderr).into_owned();
+    if result.status.success() {
+        (Some(std::fs::read_to_string(&output).unwrap()), stderr)
+    } else {
+        (None, stderr)
+    }
+}
+
+/// 以给定的参数把 source 编译为 RISC-V 汇编
+/// 不是每个测试文件都会用到
+#[allow(dead_code)]
+pub fn compile_riscv(name: &str, source: &str, options: &[&str]) -> String {
+    compile(name, source, "-riscv", options)
+}
+
+/// 把 source 写入名为 file_name 的临时文件，以 mode（-run-koopa 或 -run-riscv）执行，input 作为标准输入
+/// 返回退出码、标准输出和标准错误
+#[allow(dead_code)]
+pub fn run(mode: &str, file_name: &str, source: &str, input: &str, options: &[&str]) -> (i32, String, String) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let path = dir.join(file_name);
+    std::fs::write(&path, source).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg(mode)
+        .arg(&path)
+        .args(options)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let result = child.wait_with_output().unwrap();
+    (
+        result.status.code().unwrap(),
+        String::from_utf8_lossy(&result.stdout).into_owned(),
+        String::from_utf8_lossy(&result.stderr).into_owned()
+    )
+}
+
+/// 用 -run-koopa 解释执行 Koopa IR
+#[allow(dead_code)]
+pub fn run_koopa(file_name: &str, source: &str, input: &str, options: &[&str]) -> (i32, String, String) {
+    run("-run-koopa", file_name, source, input, options)
+}
+
+/// 生成 RISC-V 汇编并用 -run-riscv 在模拟器中执行
+#[allow(dead_code)]
+pub fn run_riscv(file_name: &str, source: &str, input: &str, options: &[&str]) -> (i32, String, String) {
+    run("-run-riscv", file_name, source, input, options)
+}
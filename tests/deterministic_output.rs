@@ -0,0 +1,49 @@
+//! 确定性测试：同样的输入和参数，多次编译的输出必须逐字节相同，
+//! 否则黄金文件测试和编译缓存都无法工作。
+
+mod common;
+
+use common::compile;
+
+/// 寄存器压力较大的程序：多个变量同时活跃，二元运算需要保留寄存器，部分值会溢出到栈上
+const SOURCE: &str = "
+int main() {
+    int a = 3, b = 4, c = 5, d = 6, e = 7, f = 8, g = 9, h = 10;
+    int i = a * b + c, j = d - e * f, k = g / 2 + h % 3, l = a + b + c + d;
+    int m = (e + f) * (g - h), n = i * j - k * l, o = m / 7 + n % 5;
+    const int p = 12;
+    a = a + p;
+    b = b * a - c;
+    int q = !a + -b + (c == d) + (e != f) + (g < h) + (h >= g) + (i <= j) + (k > l);
+    int r = (a && b) + (c || 0) + (0 && d);
+    return a + b + c + d + e + f + g + h + i + j + k + l + m + n + o + q + r;
+}
+";
+
+const RUNS: usize = 10;
+
+fn check_deterministic(mode: &str, options: &[&str]) {
+    let name = format!("deterministic{}{}", mode, options.concat().replace(['-', '='], "_"));
+    let first = compile(&name, SOURCE, mode, options);
+    for _ in 1..RUNS {
+        let output = compile(&name, SOURCE, mode, options);
+        assert!(output == first, "{} {:?} 的多次编译结果不同", mode, options);
+    }
+}
+
+#[test]
+fn koopa_output_is_deterministic() {
+    for level in ["-O0", "-O1", "-O2"] {
+        check_deterministic("-koopa", &[level]);
+    }
+}
+
+#[test]
+fn riscv_output_is_deterministic() {
+    for regalloc in ["--regalloc=greedy", "--regalloc=linear", "--regalloc=graph"] {
+        for level in ["-O0", "-O1"] {
+            check_deterministic("-riscv", &[level, regalloc]);
+        }
+    }
+    check_deterministic("-riscv", &["-O2", "-fno-omit-frame-pointer"]);
+}
@@ -0,0 +1,70 @@
+//! 差分测试：-diff-test 在同一个进程中用解释器和模拟器执行 tests/cases 下带有 .out 文件的用例
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 以给定的参数对 dir 运行差分测试，返回是否全部通过和标准输出
+fn diff_test(dir: &Path, options: &[&str]) -> (bool, String) {
+    let result = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("-diff-test")
+        .arg(dir)
+        .args(options)
+        .output()
+        .unwrap();
+    (result.status.success(), String::from_utf8_lossy(&result.stdout).into_owned())
+}
+
+/// 在临时目录中创建只有一个用例的测试目录
+fn single_case(name: &str, source: &str, expected: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("case.c"), source).unwrap();
+    std::fs::write(dir.join("case.out"), expected).unwrap();
+    dir
+}
+
+#[test]
+fn test_cases_pass() {
+    let cases = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("cases");
+    for options in [&["-O0"][..], &["-O0", "--regalloc=greedy"], &["-O1", "--regalloc=graph"], &["-O2"]] {
+        let (passed, stdout) = diff_test(&cases, options);
+        assert!(passed, "{:?}\n{}", options, stdout);
+        assert!(stdout.contains(" 0 failed"), "{}", stdout);
+    }
+}
+
+#[test]
+fn wrong_result_is_blamed_on_ir_gen() {
+    let dir = single_case("diff_wrong_result", "int main() { int a = 1; return a + 1; }", "3\n");
+    let (passed, stdout) = diff_test(&dir, &["-O0"]);
+    assert!(!passed);
+    assert!(stdout.contains("case case.c ... FAILED in IrGen"), "{}", stdout);
+    assert!(stdout.contains("expected: 3"), "{}", stdout);
+    assert!(stdout.contains("actual:   2"), "{}", stdout);
+}
+
+#[test]
+fn compile_errors_fail_the_case() {
+    let dir = single_case("diff_compile_error", "int main() { return b; }", "0\n");
+    let (passed, stdout) = diff_test(&dir, &[]);
+    assert!(!passed);
+    assert!(stdout.contains("FAILED in IrGen"), "{}", stdout);
+    assert!(stdout.contains("use of undeclared identifier 'b'"), "{}", stdout);
+}
+
+#[test]
+fn missing_or_empty_directory_fails() {
+    // 无法读取的目录是 I/O 错误
+    let missing = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("diff_missing_dir");
+    let result = Command::new(env!("CARGO_BIN_EXE_compiler")).arg("-diff-test").arg(&missing).output().unwrap();
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert_eq!(result.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("error: cannot read"), "{}", stderr);
+    // 没有任何用例的目录也算作失败，而不是 0 个用例全部通过
+    let empty = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("diff_empty_dir");
+    std::fs::create_dir_all(&empty).unwrap();
+    let result = Command::new(env!("CARGO_BIN_EXE_compiler")).arg("-diff-test").arg(&empty).output().unwrap();
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert_eq!(result.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("no differential test cases found"), "{}", stderr);
+}
@@ -0,0 +1,91 @@
+//! 库的驱动接口：不经过命令行程序编译，错误和警告作为数据返回
+
+use compiler::error_report::ProblemLevel;
+use compiler::{Compiler, Options, Target};
+
+#[test]
+fn session_runs_each_stage() {
+    let source = "int main() {\n    const int a = 3;\n    int b = a * 4;\n    return b + 1;\n}\n";
+    let mut session = Compiler::default().session();
+    let ast = session.parse(source).unwrap();
+    let program = session.lower(ast).unwrap();
+    let koopa = session.codegen(&program, Target::Koopa).unwrap();
+    let asm = session.codegen(&program, Target::Riscv).unwrap();
+    assert!(koopa.contains("fun @main(): i32"), "{}", koopa);
+    assert!(asm.contains("main:"), "{}", asm);
+    assert!(session.diagnostics().is_empty());
+
+    // 同样的 IR 文本可以作为 Koopa IR 输入
+    let mut session = Compiler::new(Options::default()).session();
+    let reparsed = session.lower_koopa(&koopa).unwrap();
+    assert_eq!(session.codegen(&reparsed, Target::Riscv).unwrap(), asm);
+}
+
+#[test]
+fn errors_and_warnings_are_returned_as_data() {
+    let source = "int main() {\n    int a = 1;\n    return a + c;\n}\n";
+    let (output, diagnostics) = Compiler::default().compile(source, Target::Koopa);
+    assert!(output.is_none());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].level, ProblemLevel::Error);
+    assert_eq!(diagnostics[0].message, "use of undeclared identifier 'c'");
+    let span = diagnostics[0].labels[0].span;
+    assert_eq!(&source[span.start..span.end], "c");
+
+    // 有警告时仍然得到输出
+    let source = "int main() {\n    int a = 1;\n}\n";
+    let (output, diagnostics) = Compiler::default().compile(source, Target::Riscv);
+    assert!(output.is_some());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].level, ProblemLevel::Warning);
+}
+
+#[test]
+fn syntax_errors_have_locations() {
+    let source = "int main() {\n    int a = 1\n    return a;\n}\n";
+    let mut session = Compiler::default().session();
+    assert!(session.parse(source).is_none());
+    assert!(session.has_errors());
+    let problem = &session.diagnostics()[0];
+    assert_eq!(problem.message, "unexpected token 'return'");
+    let span = problem.labels[0].span;
+    assert_eq!(&source[span.start..span.end], "return");
+
+    let source = "int main() {\n    return 1 $ 2;\n}\n";
+    let (_, diagnostics) = Compiler::default().compile(source, Target::Koopa);
+    assert_eq!(diagnostics[0].message, "invalid token");
+    let span = diagnostics[0].labels[0].span;
+    assert_eq!(&source[span.start..span.end], "$");
+}
+
+#[test]
+fn koopa_errors_are_returned_as_data() {
+    let text = "fun @main(): i32 {\n%entry:\n  %0 = add 1 2\n  ret %0\n}\n";
+    let mut session = Compiler::default().session();
+    assert!(session.lower_koopa(text).is_none());
+    let problem = &session.diagnostics()[0];
+    assert_eq!(problem.message, "invalid Koopa IR syntax");
+    let span = problem.labels[0].span;
+    assert!(text[span.start..].starts_with("%0 = add"), "{:?}", span);
+
+    let text = "decl @f(i32)\n\nfun @main(): i32 {\n%entry:\n  %0 = call @g()\n  ret %0\n}\n";
+    let mut session = Compiler::default().session();
+    assert!(session.lower_koopa(text).is_none());
+    let problem = &session.diagnostics()[0];
+    assert_eq!(problem.message, "invalid Koopa IR in function '@main'");
+    let span = problem.labels[0].span;
+    assert_eq!(&text[span.start..span.end], &text[14..text.len() - 1]);
+}
+
+#[test]
+fn parse_keeps_indentation() {
+    // 命令行程序会先去掉公共的缩进，库原样解析，错误的位置相对于传入的源程序
+    let source = "    int main() {\n        return 1 $ 2;\n    }\n";
+    let mut session = Compiler::default().session();
+    assert!(session.parse(source).is_none());
+    let span = session.diagnostics()[0].labels[0].span;
+    assert_eq!(span.start, source.find('$').unwrap());
+    assert_eq!(&source[span.start..span.end], "$");
+    // 缩进不影响语法
+    assert!(Compiler::default().session().parse("    int main() {\n        return 1;\n    }\n").is_some());
+}
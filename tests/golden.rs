@@ -0,0 +1,239 @@
+//! 黄金文件测试：用 -koopa 和 -riscv 编译 tests/cases 下的每个 .c 文件，和旁边同名的 .koopa/.S 文件比较
+//!
+//! - 源程序中的 `// ERROR: ...` 和 `// WARNING: ...` 注释表示这一行应该产生的诊断信息，
+//!   注释中的文字需要是诊断信息描述的一部分，没有注明的诊断信息也会导致测试失败
+//! - 有 ERROR 注释的程序应该编译失败，不和黄金文件比较
+//! - `cargo test --test golden -- --bless` 用当前的输出创建或者更新黄金文件
+//! - 其他不以 `-` 开头的参数作为过滤条件，只运行路径中包含它的用例
+
+mod common;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 诊断信息的等级
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Error,
+    Warning
+}
+
+/// 一条诊断信息：等级、所在的行和描述
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Diagnostic {
+    level: Level,
+    /// 从 1 开始的行号，没有源码位置的诊断信息（例如后端错误）为 None
+    line: Option<usize>,
+    message: String
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning"
+        };
+        match self.line {
+            Some(line) => write!(f, "line {}: {}: {}", line, level, self.message),
+            None => write!(f, "{}: {}", level, self.message)
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let bless = args.iter().any(|arg| arg == "--bless");
+    let filters: Vec<&String> = args.iter().filter(|arg| !arg.starts_with('-')).collect();
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("cases");
+    let mut cases = Vec::new();
+    collect_cases(&root, &mut cases);
+    cases.sort();
+    let cases: Vec<(PathBuf, String)> = cases.into_iter()
+        .map(|path| {
+            let name = path.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/");
+            (path, name)
+        })
+        .filter(|(_, name)| filters.is_empty() || filters.iter().any(|filter| name.contains(filter.as_str())))
+        .collect();
+
+    println!("\nrunning {} golden cases", cases.len());
+    let mut failures = Vec::new();
+    for (path, name) in &cases {
+        match check_case(path, name, bless) {
+            Ok(()) => println!("case {} ... ok", name),
+            Err(message) => {
+                println!("case {} ... FAILED", name);
+                failures.push((name, message));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, message) in &failures {
+            println!("\n---- {} ----\n{}", name, message);
+        }
+        println!("\ntest result: FAILED. {} passed; {} failed", cases.len() - failures.len(), failures.len());
+        std::process::exit(1);
+    }
+    println!("\ntest result: ok. {} passed; 0 failed", cases.len());
+}
+
+/// 递归地找出目录下所有的 .c 文件
+fn collect_cases(dir: &Path, cases: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_cases(&path, cases);
+        } else if path.extension().is_some_and(|extension| extension == "c") {
+            cases.push(path);
+        }
+    }
+}
+
+/// 检查一个用例，失败时返回原因
+fn check_case(path: &Path, name: &str, bless: bool) -> Result<(), String> {
+    let source = std::fs::read_to_string(path).unwrap();
+    let expected = expected_diagnostics(&source);
+    let expect_error = expected.iter().any(|diagnostic| diagnostic.level == Level::Error);
+    // 所有用例的临时文件都放在同一个目录下，用路径区分
+    let file_name = name.replace('/', "_");
+    for (mode, extension) in [("-koopa", "koopa"), ("-riscv", "S")] {
+        let (output, stderr) = common::compile_with_diagnostics(&file_name, &source, mode, &[]);
+        check_diagnostics(&expected, &parse_diagnostics(&stderr))
+            .map_err(|message| format!("{}: {}\nstderr:\n{}", mode, message, strip_colors(&stderr)))?;
+        match output {
+            Some(_) if expect_error => return Err(format!("{}: compilation succeeded but errors were expected", mode)),
+            Some(output) => check_golden(&path.with_extension(extension), &output, bless)?,
+            None if expect_error => {},
+            None => return Err(format!("{}: compilation failed\nstderr:\n{}", mode, strip_colors(&stderr)))
+        }
+    }
+    Ok(())
+}
+
+/// 从源程序的 `// ERROR:` 和 `// WARNING:` 注释中读出期望的诊断信息
+fn expected_diagnostics(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        for (marker, level) in [("// ERROR:", Level::Error), ("// WARNING:", Level::Warning)] {
+            if let Some(position) = line.find(marker) {
+                diagnostics.push(Diagnostic {
+                    level,
+                    line: Some(index + 1),
+                    message: line[position + marker.len()..].trim().to_string()
+                });
+            }
+        }
+    }
+    diagnostics
+}
+
+/// 去掉错误输出中的颜色控制字符
+fn strip_colors(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // 控制序列形如 ESC [ ... m
+            for c in chars.by_ref() {
+                if c == 'm' {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// 从编译器的错误输出中解析出 ProblemInfo 生成的诊断信息
+/// 每条诊断信息以 `error: ` 或者 `warning: ` 开头，随后 `┌─ path:line:column` 给出首要标签的位置
+fn parse_diagnostics(stderr: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in strip_colors(stderr).lines() {
+        if let Some(message) = line.strip_prefix("error: ") {
+            diagnostics.push(Diagnostic { level: Level::Error, line: None, message: message.to_string() });
+        } else if let Some(message) = line.strip_prefix("warning: ") {
+            diagnostics.push(Diagnostic { level: Level::Warning, line: None, message: message.to_string() });
+        } else if let Some(location) = line.trim_start().strip_prefix("┌─ ") {
+            let Some(last) = diagnostics.last_mut().filter(|diagnostic| diagnostic.line.is_none()) else {
+                continue;
+            };
+            // 路径中可能有冒号，从右边解析行号和列号
+            let mut parts = location.rsplitn(3, ':');
+            let _column = parts.next();
+            last.line = parts.next().and_then(|line| line.parse().ok());
+        }
+    }
+    diagnostics
+}
+
+/// 检查实际的诊断信息和注释一一对应
+fn check_diagnostics(expected: &[Diagnostic], actual: &[Diagnostic]) -> Result<(), String> {
+    let mut unmatched: Vec<&Diagnostic> = expected.iter().collect();
+    let mut unexpected = Vec::new();
+    for diagnostic in actual {
+        let found = unmatched.iter().position(|e| {
+            e.level == diagnostic.level && e.line == diagnostic.line && diagnostic.message.contains(&e.message)
+        });
+        match found {
+            Some(index) => {
+                unmatched.remove(index);
+            },
+            None => unexpected.push(diagnostic)
+        }
+    }
+    if unmatched.is_empty() && unexpected.is_empty() {
+        return Ok(());
+    }
+    let mut message = String::from("diagnostics do not match the annotations");
+    for diagnostic in unmatched {
+        message += &format!("\n  missing:    {}", diagnostic);
+    }
+    for diagnostic in unexpected {
+        message += &format!("\n  unexpected: {}", diagnostic);
+    }
+    Err(message)
+}
+
+/// 把输出和黄金文件比较，bless 时用输出更新黄金文件
+fn check_golden(path: &Path, output: &str, bless: bool) -> Result<(), String> {
+    let expected = std::fs::read_to_string(path).ok();
+    if bless {
+        if expected.as_deref() != Some(output) {
+            std::fs::write(path, output).unwrap();
+        }
+        return Ok(());
+    }
+    let Some(expected) = expected else {
+        return Err(format!(
+            "missing golden file {}\nrun `cargo test --test golden -- --bless` to create it",
+            path.display()
+        ));
+    };
+    if expected == output {
+        return Ok(());
+    }
+    // 只显示第一处不同附近的几行
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let output_lines: Vec<&str> = output.lines().collect();
+    let first = expected_lines.iter().zip(&output_lines).take_while(|(e, o)| e == o).count();
+    let mut message = format!(
+        "output differs from {} starting at line {}\nrun `cargo test --test golden -- --bless` to update it",
+        path.display(),
+        first + 1
+    );
+    for line in expected_lines.iter().skip(first).take(5) {
+        message += &format!("\n- {}", line);
+    }
+    for line in output_lines.iter().skip(first).take(5) {
+        message += &format!("\n+ {}", line);
+    }
+    Err(message)
+}
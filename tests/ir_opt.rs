@@ -0,0 +1,572 @@
+//! 优化 pass 的测试：编译 SysY 程序，或者输入 Koopa IR，检查优化之后的 IR 或汇编，
+//! 再用 -run-koopa 和 -run-riscv 执行优化的结果
+
+mod common;
+
+use common::{compile, run_koopa, run_riscv, try_compile};
+
+/// 以 -O1 优化 Koopa IR，返回优化之后的 IR
+fn optimize(name: &str, source: &str) -> String {
+    try_compile(&format!("{}.koopa", name), source, "-koopa", &["-O1"]).unwrap_or_else(|stderr| panic!("优化失败：\n{}", stderr))
+}
+
+/// 不再优化，直接执行优化之后的 IR 和由它生成的汇编，检查每组输入的退出码
+fn check_runs(name: &str, ir: &str, cases: &[(&str, i32)]) {
+    let file_name = format!("{}_opt.koopa", name);
+    for &(input, expected) in cases {
+        let (code, _, stderr) = run_koopa(&file_name, ir, input, &["-O0"]);
+        assert_eq!(code, expected, "-run-koopa，输入 {:?}\n{}\n{}", input, stderr, ir);
+        let (code, _, stderr) = run_riscv(&file_name, ir, input, &["-O0"]);
+        assert_eq!(code, expected, "-run-riscv，输入 {:?}\n{}\n{}", input, stderr, ir);
+    }
+}
+
+#[test]
+fn constant_fold_identities_in_sysy() {
+    let source = "int main() {\n    int x = 7;\n    int y = x * 1 + 0 - 0;\n    return y / 1 + 6 * 7;\n}\n";
+    let unoptimized = compile("fold_o0", source, "-koopa", &["-O0"]);
+    assert!(unoptimized.contains("mul 6, 7"), "{}", unoptimized);
+    // 乘以 1、加减 0、除以 1 都被消去，常量运算直接算出结果
+    let ir = compile("fold_o1", source, "-koopa", &["-O1"]);
+    for op in ["mul", "sub", "div"] {
+        assert!(!ir.contains(op), "不应该出现 {}：\n{}", op, ir);
+    }
+    assert!(!ir.contains("6, 7"), "{}", ir);
+    // 默认的优化级别是 -O1
+    assert_eq!(compile("fold_default", source, "-koopa", &[]), ir);
+}
+
+#[test]
+fn dead_code_is_removed_in_sysy() {
+    let source = "int main() {\n    int a = 3;\n    int b = a * 5;\n    return a;\n}\n";
+    assert!(compile("dce_o0", source, "-koopa", &["-O0"]).contains("mul"));
+    // b 没有被使用，它的计算、alloc 和 store 都被删除
+    let ir = compile("dce_o1", source, "-koopa", &["-O1"]);
+    assert!(!ir.contains("mul"), "{}", ir);
+    assert!(!ir.contains("@b"), "{}", ir);
+}
+
+#[test]
+fn value_numbering_reuses_loads_in_sysy() {
+    let source = "int main() {\n    int a = 3;\n    return a * 2 + a * 2;\n}\n";
+    let unoptimized = compile("vn_o0", source, "-koopa", &["-O0"]);
+    assert_eq!(unoptimized.matches("load @a").count(), 2, "{}", unoptimized);
+    // 两次 load 都取到刚刚 store 的值，之后整个表达式都能折叠成常量
+    let ir = compile("vn_o1", source, "-koopa", &["-O1"]);
+    assert!(!ir.contains("load"), "{}", ir);
+    assert!(ir.contains("ret 12"), "{}", ir);
+}
+
+#[test]
+fn strength_reduction_in_sysy() {
+    let source = "int main() {\n    int x = 100;\n    return x / 7 + x * 8;\n}\n";
+    // 后端的强度削弱不受优化等级影响
+    let asm = compile("strength_o0", source, "-riscv", &["-O0"]);
+    assert!(asm.contains("mulh"), "{}", asm);
+    assert!(asm.contains("slli"), "{}", asm);
+    assert!(!asm.contains("div"), "{}", asm);
+    assert!(!asm.contains("mul\t"), "{}", asm);
+}
+
+#[test]
+fn inline_limit_option_is_accepted() {
+    let source = "int main() {\n    return 1 + 2;\n}\n";
+    // 只有 main 一个函数，内联阈值不影响结果
+    let ir = compile("inline_limit_0", source, "-koopa", &["-O1", "-finline-limit=0"]);
+    assert_eq!(compile("inline_limit_default", source, "-koopa", &["-O1"]), ir);
+}
+
+/// 有很多局部变量同时活跃的 SysY 程序，寄存器不够用
+fn register_pressure_source() -> String {
+    let mut source = String::from("int main() {\n");
+    for i in 0..16 {
+        source.push_str(&format!("    int v{} = {};\n", i, i + 1));
+    }
+    source.push_str("    int s = 0;\n");
+    for i in 0..16 {
+        source.push_str(&format!("    s = s + v{} * v{};\n", i, (i + 1) % 16));
+    }
+    source.push_str("    return s;\n}\n");
+    source
+}
+
+#[test]
+fn linear_scan_spills_less_than_greedy_in_sysy() {
+    let source = register_pressure_source();
+    let stack_accesses = |asm: &str| asm.matches("(sp)").count();
+    let greedy = compile("pressure_greedy", &source, "-riscv", &["-O0", "--regalloc=greedy"]);
+    let linear = compile("pressure_linear", &source, "-riscv", &["-O0", "--regalloc=linear"]);
+    // 线性扫描在值不再活跃后复用寄存器，读写栈的次数少得多
+    assert!(stack_accesses(&linear) * 2 < stack_accesses(&greedy), "{}\n{}", linear, greedy);
+    // 默认使用线性扫描
+    let default = compile("pressure_default", &source, "-riscv", &["-O0"]);
+    assert_eq!(stack_accesses(&default), stack_accesses(&linear), "{}", default);
+}
+
+#[test]
+fn graph_coloring_spills_less_than_greedy_in_sysy() {
+    let source = register_pressure_source();
+    let stack_accesses = |asm: &str| asm.matches("(sp)").count();
+    let greedy = compile("pressure_greedy_graph", &source, "-riscv", &["-O0", "--regalloc=greedy"]);
+    let graph = compile("pressure_graph", &source, "-riscv", &["-O0", "--regalloc=graph"]);
+    // 图着色按照冲突图分配寄存器，不冲突的值共用同一个寄存器
+    assert!(stack_accesses(&graph) * 2 < stack_accesses(&greedy), "{}\n{}", graph, greedy);
+}
+
+#[test]
+fn callee_saved_registers_and_frame_pointer_in_sysy() {
+    let source = register_pressure_source();
+    // 调用者保存的寄存器不够用时使用 s 寄存器，在函数开头保存、出口处恢复
+    let asm = compile("callee_saved", &source, "-riscv", &["-O0"]);
+    assert!(asm.contains("\tsw\ts1,"), "{}", asm);
+    assert!(asm.contains("\tlw\ts1,"), "{}", asm);
+    assert!(!asm.contains("s0"), "{}", asm);
+    // 保留帧指针时 s0 不参与分配，指向调用者的栈顶
+    let asm = compile("frame_pointer", &source, "-riscv", &["-O0", "-fno-omit-frame-pointer"]);
+    assert!(asm.contains("\tsw\ts0,"), "{}", asm);
+    assert!(asm.contains("\taddi\ts0,sp,"), "{}", asm);
+    assert!(asm.contains("\tlw\ts0,"), "{}", asm);
+}
+
+/// 同一个函数在一个调用者中被内联两次
+const INLINE_TWICE: &str = "\
+decl @getint(): i32
+
+fun @clamp(%p: i32): i32 {
+%entry:
+  %x = add %p, 1
+  %big = gt %x, 10
+  br %big, %keep, %drop
+%keep:
+  ret %x
+%drop:
+  ret 0
+}
+
+fun @main(): i32 {
+%entry:
+  %g = call @getint()
+  %a = call @clamp(%g)
+  %v = alloc i32
+  store %a, %v
+  %b = call @clamp(%a)
+  %c = call @getint()
+  br %c, %then, %end
+%then:
+  store %b, %v
+  jump %end
+%end:
+  %y = load %v
+  ret %y
+}
+";
+
+#[test]
+fn inline_twice_with_fresh_names() {
+    let ir = optimize("inline_twice", INLINE_TWICE);
+    let main = &ir[ir.find("fun @main").unwrap()..];
+    assert!(!main.contains("call @clamp"), "{}", ir);
+    // 复制出来的值和基本块带有被调用者的名字，两份复制的名字互不相同
+    for name in ["%clamp_x =", "%clamp_x_0 =", "%clamp_keep:", "%clamp_keep_0:", "%clamp_cont(%clamp_ret: i32):"] {
+        assert!(main.contains(name), "缺少 {}：\n{}", name, ir);
+    }
+    let defined: Vec<&str> = main.lines().filter_map(|line| line.trim().split_once(" = ")).map(|(name, _)| name).collect();
+    let mut unique = defined.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(defined.len(), unique.len(), "{}", ir);
+    // 调用点之后的局部变量留在入口块，不跟着移入续块
+    let entry = &main[..main.find("\n\n").unwrap()];
+    assert!(entry.contains("%v = alloc i32"), "{}", ir);
+    check_runs("inline_twice", &ir, &[("11 0", 12), ("11 1", 13), ("3 1", 0)]);
+}
+
+/// 代数恒等式和常量运算，以及不能折叠的除以 0
+const FOLD_IDENTITIES: &str = "\
+decl @getint(): i32
+
+fun @main(): i32 {
+%entry:
+  %x = call @getint()
+  %a = add %x, 0
+  %b = mul %a, 1
+  %c = sub %b, 0
+  %d = div %c, 1
+  %e = mul %d, 0
+  %f = add %c, %e
+  %g = mul 6, 7
+  %z = eq %x, 0
+  br %z, %bad, %good
+%bad:
+  %q = div %g, 0
+  ret %q
+%good:
+  %r = add %f, %g
+  ret %r
+}
+";
+
+#[test]
+fn constant_fold_identities() {
+    let ir = optimize("fold_identities", FOLD_IDENTITIES);
+    assert!(ir.contains("%r = add %x, 42"), "{}", ir);
+    assert!(!ir.contains("mul") && !ir.contains("sub"), "{}", ir);
+    // 除以 0 在运行时才出错，折叠时原样保留
+    assert!(ir.contains("%q = div 42, 0"), "{}", ir);
+    check_runs("fold_identities", &ir, &[("1", 43), ("5", 47)]);
+}
+
+/// 没有用到的值、条件恒为假的分支之后不可达的基本块（包括一个死循环），以及可以合并的跳转链
+const DEAD_BLOCKS: &str = "\
+decl @getint(): i32
+decl @putint(i32)
+
+fun @main(): i32 {
+%entry:
+  %x = call @getint()
+  %unused = mul %x, %x
+  %never = lt 2, 1
+  br %never, %dead, %live
+%dead:
+  %y = add %x, 100
+  call @putint(%y)
+  jump %dead_loop(%y)
+%dead_loop(%w: i32):
+  %t = add %w, 1
+  jump %dead_loop(%t)
+%live:
+  %k = add %x, 1
+  jump %exit
+%exit:
+  %r = mul %k, 2
+  ret %r
+}
+";
+
+#[test]
+fn dead_code_and_unreachable_blocks() {
+    let ir = optimize("dead_blocks", DEAD_BLOCKS);
+    assert!(!ir.contains("%unused"), "{}", ir);
+    assert!(!ir.contains("%dead") && !ir.contains("call @putint"), "{}", ir);
+    // 剩下的基本块合并成了一个
+    let main = &ir[ir.find("fun @main").unwrap()..];
+    assert_eq!(main.matches(":\n").count(), 1, "{}", ir);
+    check_runs("dead_blocks", &ir, &[("3", 8), ("20", 42)]);
+}
+
+/// 支配块中已经算过的乘法在两个分支中重复出现，其中一处交换了操作数
+const DUPLICATE_EXPRESSIONS: &str = "\
+decl @getint(): i32
+
+fun @main(): i32 {
+%entry:
+  %x = call @getint()
+  %y = call @getint()
+  %a = mul %x, %y
+  %c = gt %x, %y
+  br %c, %then, %else
+%then:
+  %b = mul %y, %x
+  %s = add %a, %b
+  ret %s
+%else:
+  %d = mul %x, %y
+  %e = sub %d, %a
+  %f = add %e, %d
+  ret %f
+}
+";
+
+#[test]
+fn value_numbering_removes_duplicates() {
+    let ir = optimize("duplicates", DUPLICATE_EXPRESSIONS);
+    assert_eq!(ir.matches("mul").count(), 1, "{}", ir);
+    assert!(ir.contains("%s = add %a, %a"), "{}", ir);
+    check_runs("duplicates", &ir, &[("5 3", 30), ("3 5", 15)]);
+}
+
+/// 循环中的 %flag 沿每条边传入的都是 0，只有乐观地传播常量才能证明 %strange 永远不会执行
+const LOOP_CONSTANT_BRANCH: &str = "\
+decl @getint(): i32
+decl @putint(i32)
+
+fun @main(): i32 {
+%entry:
+  %n = call @getint()
+  jump %loop(0, 0, 0)
+%loop(%i: i32, %flag: i32, %acc: i32):
+  %more = lt %i, %n
+  br %more, %body, %exit
+%body:
+  br %flag, %strange, %normal
+%strange:
+  call @putint(%acc)
+  jump %next(1, %acc)
+%normal:
+  %acc2 = add %acc, %i
+  jump %next(0, %acc2)
+%next(%f: i32, %a: i32):
+  %i2 = add %i, 1
+  jump %loop(%i2, %f, %a)
+%exit:
+  ret %acc
+}
+";
+
+#[test]
+fn sccp_turns_constant_branch_into_jump() {
+    let ir = optimize("loop_constant_branch", LOOP_CONSTANT_BRANCH);
+    assert!(!ir.contains("br %flag"), "{}", ir);
+    assert!(!ir.contains("%strange") && !ir.contains("call @putint"), "{}", ir);
+    // 只剩下循环条件一个分支
+    assert_eq!(ir.matches("br ").count(), 1, "{}", ir);
+    check_runs("loop_constant_branch", &ir, &[("0", 0), ("5", 10), ("10", 45)]);
+}
+
+/// 除以、模以和乘以常量，输出每个结果
+const CONSTANT_DIVISORS: &str = "\
+decl @getint(): i32
+decl @putint(i32)
+decl @putch(i32)
+
+fun @main(): i32 {
+%entry:
+  %x = call @getint()
+  %a = div %x, 7
+  call @putint(%a)
+  call @putch(32)
+  %b = mod %x, -8
+  call @putint(%b)
+  call @putch(32)
+  %c = mul %x, -1
+  call @putint(%c)
+  call @putch(32)
+  %d = div %x, -1
+  call @putint(%d)
+  ret 0
+}
+";
+
+#[test]
+fn strength_reduction_of_constant_operands() {
+    let asm = try_compile("constant_divisors.koopa", CONSTANT_DIVISORS, "-riscv", &["-O1"]).unwrap();
+    // 除以 7 用乘法取高位代替，模以 2 的幂和乘以 -1 用移位和取反代替
+    assert!(asm.contains("\tmulh\t") && asm.contains("\tsrai\t") && asm.contains("\tsub\t"), "{}", asm);
+    for inst in ["\tdiv\t", "\trem\t", "\tmul\t"] {
+        assert!(!asm.contains(inst), "不应该出现 {}：\n{}", inst.trim(), asm);
+    }
+    // i32::MIN / -1 和 i32::MIN * -1 都按补码回绕
+    for (input, expected) in [("100", "14 4 -100 -100"), ("-100", "-14 -4 100 100"), ("7", "1 7 -7 -7"), ("-9", "-1 -1 9 9"),
+                              ("-2147483648", "-306783378 0 -2147483648 -2147483648")] {
+        let (_, stdout, stderr) = run_koopa("constant_divisors.koopa", CONSTANT_DIVISORS, input, &["-O0"]);
+        assert_eq!(stdout, expected, "{}", stderr);
+        let (code, stdout, stderr) = run_riscv("constant_divisors.koopa", CONSTANT_DIVISORS, input, &["-O1"]);
+        assert_eq!((code, stdout.as_str()), (0, expected), "输入 {}\n{}", input, stderr);
+    }
+}
+
+/// 循环体中的乘法和除法都不依赖循环变量；除数可能为 0，除法只能在循环真正执行时计算
+const LOOP_INVARIANT: &str = "\
+decl @getint(): i32
+
+fun @main(): i32 {
+%entry:
+  %n = call @getint()
+  %a = call @getint()
+  %b = call @getint()
+  jump %loop(0, 0)
+%loop(%i: i32, %acc: i32):
+  %more = lt %i, %n
+  br %more, %body, %exit
+%body:
+  %inv = mul %a, %b
+  %q = div %a, %b
+  %inv2 = add %inv, %q
+  %acc2 = add %acc, %inv2
+  %i2 = add %i, 1
+  jump %loop(%i2, %acc2)
+%exit:
+  ret %acc
+}
+";
+
+#[test]
+fn licm_hoists_invariant_code() {
+    let ir = optimize("loop_invariant", LOOP_INVARIANT);
+    let body = &ir[ir.find("%body:").unwrap()..ir.find("%exit:").unwrap()];
+    let entry = &ir[ir.find("%entry:").unwrap()..ir.find("%loop(").unwrap()];
+    assert!(entry.contains("%inv = mul %a, %b") && !body.contains("mul"), "{}", ir);
+    assert!(body.contains("%q = div %a, %b"), "{}", ir);
+    check_runs("loop_invariant", &ir, &[("0 1 0", 0), ("3 6 2", 45), ("4 5 5", 104)]);
+}
+
+/// 累加器形式的自递归，递归调用之后紧跟着返回它的结果
+const TAIL_RECURSION: &str = "\
+decl @getint(): i32
+
+fun @sum(%n: i32, %acc: i32): i32 {
+%entry:
+  %zero = eq %n, 0
+  br %zero, %done, %more
+%done:
+  ret %acc
+%more:
+  %m = sub %n, 1
+  %a = add %acc, %n
+  %r = call @sum(%m, %a)
+  ret %r
+}
+
+fun @main(): i32 {
+%entry:
+  %n = call @getint()
+  %r = call @sum(%n, 0)
+  %k = mod %r, 256
+  ret %k
+}
+";
+
+#[test]
+fn self_tail_recursion_becomes_loop() {
+    let ir = optimize("tail_recursion", TAIL_RECURSION);
+    let sum = &ir[ir.find("fun @sum").unwrap()..ir.find("fun @main").unwrap()];
+    assert!(!sum.contains("call @sum"), "{}", ir);
+    assert!(sum.contains("jump %sum_tail(%m, %a)"), "{}", ir);
+    // 改写成循环之后十万层的“递归”也不会用完栈
+    check_runs("tail_recursion", &ir, &[("0", 0), ("10", 55), ("100000", 80)]);
+}
+
+/// @chain 中的 60 个值一个接一个地死去，@wide 中的 30 个值同时活跃，超过了可分配的寄存器个数
+fn register_pressure_program() -> String {
+    let mut source = String::from("decl @getint(): i32\n\nfun @chain(%x: i32): i32 {\n%entry:\n");
+    let mut last = String::from("%x");
+    for i in 0..30 {
+        source.push_str(&format!("  %c{} = add {}, {}\n  %d{} = xor %c{}, %x\n", i, last, i + 1, i, i));
+        last = format!("%d{}", i);
+    }
+    source.push_str(&format!("  ret {}\n}}\n\nfun @wide(%x: i32): i32 {{\n%entry:\n", last));
+    for i in 0..30 {
+        source.push_str(&format!("  %v{} = add %x, {}\n", i, i * i));
+    }
+    let mut last = String::from("%v0");
+    for i in 1..30 {
+        source.push_str(&format!("  %s{} = xor {}, %v{}\n", i, last, i));
+        last = format!("%s{}", i);
+    }
+    source.push_str(&format!("  ret {}\n}}\n\n", last));
+    source.push_str("fun @main(): i32 {\n%entry:\n  %x = call @getint()\n  %a = call @chain(%x)\n  %b = call @wide(%x)\n");
+    source.push_str("  %r = add %a, %b\n  %k = mod %r, 256\n  ret %k\n}\n");
+    source
+}
+
+/// 汇编中某个函数的部分，到下一个函数为止
+fn function_asm<'a>(asm: &'a str, name: &str) -> &'a str {
+    let start = asm.find(&format!("\n{}:\n", name)).unwrap() + 1;
+    let end = asm[start..].find("\n\n").map_or(asm.len(), |end| start + end);
+    &asm[start..end]
+}
+
+#[test]
+fn linear_scan_reuses_and_spills_registers() {
+    let source = register_pressure_program();
+    let linear = try_compile("pressure_linear.koopa", &source, "-riscv", &["-O1", "--regalloc=linear", "-finline-limit=0"]).unwrap();
+    let greedy = try_compile("pressure_greedy.koopa", &source, "-riscv", &["-O1", "--regalloc=greedy", "-finline-limit=0"]).unwrap();
+    // 死去的值的寄存器被重新使用，@chain 不需要栈帧
+    assert!(!function_asm(&linear, "chain").contains("sp"), "{}", linear);
+    assert!(function_asm(&greedy, "chain").contains("sp"), "{}", greedy);
+    // 同时活跃的值太多时溢出到栈上，但比贪心分配访问栈的次数少
+    let stack_accesses = |asm: &str| function_asm(asm, "wide").matches("(sp)").count();
+    assert!(stack_accesses(&linear) > 0 && stack_accesses(&linear) < stack_accesses(&greedy), "{}", linear);
+    for input in ["5", "-77"] {
+        let (expected, _, stderr) = run_koopa("pressure.koopa", &source, input, &["-O0"]);
+        assert!(stderr.is_empty(), "{}", stderr);
+        let (code, _, stderr) = run_riscv("pressure.koopa", &source, input, &["-O1", "--regalloc=linear", "-finline-limit=0"]);
+        assert_eq!(code, expected, "{}", stderr);
+    }
+}
+
+/// 循环变量通过基本块参数传递，%i2 和 %i、%next 和 %a 之间都有一次复制
+const FIBONACCI_LOOP: &str = "\
+decl @getint(): i32
+
+fun @main(): i32 {
+%entry:
+  %n = call @getint()
+  jump %loop(0, 1, 0)
+%loop(%i: i32, %a: i32, %b: i32):
+  %more = lt %i, %n
+  br %more, %body, %exit
+%body:
+  %next = add %a, %b
+  %i2 = add %i, 1
+  jump %loop(%i2, %next, %a)
+%exit:
+  ret %a
+}
+";
+
+#[test]
+fn graph_coloring_coalesces_moves() {
+    let options = |regalloc: &'static str| ["-O1", regalloc];
+    let graph = try_compile("fibonacci_graph.koopa", FIBONACCI_LOOP, "-riscv", &options("--regalloc=graph")).unwrap();
+    let linear = try_compile("fibonacci_linear.koopa", FIBONACCI_LOOP, "-riscv", &options("--regalloc=linear")).unwrap();
+    let body = |asm: &str| {
+        let start = asm.find(".Lmain_body:").unwrap();
+        asm[start..start + asm[start..].find("\tj\t").unwrap()].to_string()
+    };
+    // %i2 和 %i 合并到同一个寄存器，循环变量原地加一，回到循环头时要复制的值比线性扫描少
+    let increment = |asm: &str| {
+        let line = body(asm).lines().find(|line| line.starts_with("\taddi\t")).unwrap().to_string();
+        let operands: Vec<String> = line.trim().split('\t').nth(1).unwrap().split(',').map(String::from).collect();
+        operands[0] == operands[1]
+    };
+    assert!(increment(&graph), "{}", graph);
+    assert!(body(&graph).matches("(sp)").count() < body(&linear).matches("(sp)").count(), "{}\n{}", graph, linear);
+    for (input, expected) in [("0", 1), ("1", 1), ("10", 89), ("12", 233)] {
+        for options in [options("--regalloc=graph"), options("--regalloc=linear")] {
+            let (code, _, stderr) = run_riscv("fibonacci.koopa", FIBONACCI_LOOP, input, &options);
+            assert_eq!(code, expected, "{:?}\n{}", options, stderr);
+        }
+        assert_eq!(run_koopa("fibonacci.koopa", FIBONACCI_LOOP, input, &["-O0"]).0, expected);
+    }
+}
+
+/// main 和 @f 中都有跨越函数调用的值，都会放在被调用者保存寄存器中
+const CALLEE_SAVED: &str = "\
+decl @getint(): i32
+decl @putint(i32)
+
+fun @f(%x: i32): i32 {
+%entry:
+  %y = call @getint()
+  call @putint(%y)
+  %r = mul %x, %y
+  ret %r
+}
+
+fun @main(): i32 {
+%entry:
+  %a = call @getint()
+  %b = call @getint()
+  %c = call @f(%a)
+  %s = add %a, %b
+  %t = add %s, %c
+  ret %t
+}
+";
+
+#[test]
+fn callee_saved_registers_and_frame_pointer() {
+    for frame_pointer in ["-fomit-frame-pointer", "-fno-omit-frame-pointer"] {
+        let options = ["-O1", "--regalloc=graph", "-finline-limit=0", frame_pointer];
+        let asm = try_compile("callee_saved.koopa", CALLEE_SAVED, "-riscv", &options).unwrap();
+        // 两个函数都使用 s1，@f 在开头保存、在出口恢复，main 中的值因此不会被破坏
+        for name in ["f", "main"] {
+            let function = function_asm(&asm, name);
+            assert!(function.contains("\tsw\ts1,") && function.contains("\tlw\ts1,"), "{}", asm);
+            assert_eq!(function.contains("\taddi\ts0,sp,"), frame_pointer == "-fno-omit-frame-pointer", "{}", asm);
+        }
+        let (code, stdout, stderr) = run_riscv("callee_saved.koopa", CALLEE_SAVED, "3 4 5", &options);
+        assert_eq!((code, stdout.as_str()), (22, "5"), "{:?}\n{}", options, stderr);
+    }
+}
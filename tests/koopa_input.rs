@@ -0,0 +1,138 @@
+//! Koopa IR 输入测试：.koopa 文件（或 --input-format=koopa）跳过 SysY 前端，直接交给优化和后端
+
+mod common;
+
+use common::{run_koopa, run_riscv, try_compile};
+
+/// SysY 前端生成不了的运算：异或和移位
+const SHIFTS: &str = "\
+decl @putint(i32)
+
+fun @main(): i32 {
+%entry:
+  %0 = xor 5, 3
+  %1 = shl %0, 2
+  %2 = sar -64, 3
+  %3 = shr %2, 28
+  %4 = xor %1, %3
+  call @putint(%4)
+  ret 0
+}
+";
+
+/// 后端还不支持的局部数组
+const LOCAL_ARRAY: &str = "\
+fun @main(): i32 {
+%entry:
+  %a = alloc [i32, 4]
+  %p = getelemptr %a, 1
+  store 5, %p
+  %0 = load %p
+  ret %0
+}
+";
+
+/// 局部变量的地址作为基本块参数传递，后端只支持通过地址读写变量、把地址传给被调用者或者存入内存
+const ADDRESS_AS_BLOCK_ARG: &str = "\
+fun @main(): i32 {
+%entry:
+  %a = alloc i32
+  store 1, %a
+  jump %next(%a)
+%next(%p: *i32):
+  %v = load %p
+  ret %v
+}
+";
+
+/// 函数名和基本块名拼接之后相同：@f 中的 %x_y 和 @f_x 中的 %y
+const SIMILAR_LABELS: &str = "\
+fun @f(): i32 {
+%entry:
+  jump %x_y
+%x_y:
+  ret 5
+}
+
+fun @f_x(): i32 {
+%entry:
+  jump %y
+%y:
+  ret 6
+}
+
+fun @main(): i32 {
+%entry:
+  %a = call @f()
+  %b = call @f_x()
+  %r = add %a, %b
+  ret %r
+}
+";
+
+#[test]
+fn koopa_extension_is_detected() {
+    let asm = try_compile("shifts.koopa", SHIFTS, "-riscv", &["-O0"]).unwrap();
+    // 常量操作数使用立即数指令
+    for inst in ["\txori\t", "\tslli\t", "\tsrai\t", "\tsrli\t"] {
+        assert!(asm.contains(inst), "缺少 {}：\n{}", inst.trim(), asm);
+    }
+    let koopa = try_compile("shifts_ir.koopa", SHIFTS, "-koopa", &["-O0"]).unwrap();
+    assert!(koopa.contains("xor") && koopa.contains("sar"));
+}
+
+#[test]
+fn input_format_option_overrides_extension() {
+    try_compile("shifts.txt", SHIFTS, "-riscv", &["--input-format=koopa"]).unwrap();
+    assert!(try_compile("shifts_as_sysy.txt", SHIFTS, "-riscv", &[]).is_err());
+    try_compile("sysy.koopa", "int main() { return 1 << 0; }", "-riscv", &["--input-format=sysy"])
+        .expect_err("SysY 没有移位运算符");
+    try_compile("sysy_source.koopa", "int main() { return 1; }", "-riscv", &["--input-format=sysy"]).unwrap();
+}
+
+#[test]
+fn parse_errors_are_reported() {
+    // 语义错误标出出错的函数
+    let stderr = try_compile("undefined.koopa", "fun @main(): i32 {\n%entry:\n  ret %x\n}\n", "-riscv", &[]).unwrap_err();
+    assert!(stderr.contains("invalid Koopa IR in function '@main'"), "{}", stderr);
+    assert!(stderr.contains("undefined.koopa:1:1"), "{}", stderr);
+    assert!(stderr.contains("ret %x"), "{}", stderr);
+    // 语法错误标出无法解析的语句
+    let stderr = try_compile("syntax.koopa", "fun @main(): i32 {\n%entry:\n  %0 = add 1 2\n  ret %0\n}\n", "-riscv", &[]).unwrap_err();
+    assert!(stderr.contains("invalid Koopa IR syntax"), "{}", stderr);
+    assert!(stderr.contains("syntax.koopa:3:3"), "{}", stderr);
+    // koopa 的前端自己不打印任何内容，它的错误会带有 "at 文件:行:列" 这样的一行
+    assert!(!stderr.contains(" at "), "{}", stderr);
+}
+
+#[test]
+fn codegen_errors_are_diagnostics() {
+    for level in ["-O0", "-O2"] {
+        let stderr = try_compile("local_array.koopa", LOCAL_ARRAY, "-riscv", &[level]).unwrap_err();
+        assert!(stderr.contains("code generation failed"), "{}", stderr);
+        assert!(stderr.contains("in function '@main'"), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn unsupported_address_use_is_reported() {
+    let stderr = try_compile("address_arg.koopa", ADDRESS_AS_BLOCK_ARG, "-riscv", &["-O0"]).unwrap_err();
+    assert!(stderr.contains("address of a local variable used here is not supported by the RISC-V backend"), "{}", stderr);
+    assert!(stderr.contains("(jump)"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn block_labels_are_unique_across_functions() {
+    let asm = try_compile("similar_labels.koopa", SIMILAR_LABELS, "-riscv", &["-O0"]).unwrap();
+    let labels: Vec<&str> = asm.lines().filter(|line| line.starts_with(".L")).collect();
+    let mut unique = labels.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(labels.len(), unique.len(), "{}", asm);
+    let (code, _, stderr) = run_koopa("similar_labels.koopa", SIMILAR_LABELS, "", &["-O0"]);
+    assert_eq!(code, 11, "{}", stderr);
+    let (code, _, stderr) = run_riscv("similar_labels.koopa", SIMILAR_LABELS, "", &["-O0"]);
+    assert_eq!(code, 11, "{}", stderr);
+}
@@ -0,0 +1,25 @@
+//! 整数字面量：超出 32 位范围的字面量应该报告错误，而不是让编译器 panic
+
+mod common;
+
+use common::try_compile;
+
+#[test]
+fn too_large_literals_are_rejected() {
+    for literal in ["2147483649", "4294967296", "0x100000000", "040000000000", "99999999999999999999"] {
+        let source = format!("int main() {{ return {}; }}", literal);
+        let stderr = try_compile("too_large.c", &source, "-koopa", &[]).unwrap_err();
+        assert!(stderr.contains(&format!("integer literal '{}' is too large", literal)), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn too_large_literals_have_locations() {
+    let stderr = try_compile("too_large_line.c", "int main() { return 4294967296; }", "-koopa", &[]).unwrap_err();
+    assert!(stderr.contains("too_large_line.c:1:21"), "{}", stderr);
+    let source = "int main() {\n    int a = 1;\n    return a + 0x1ffffffff;\n}\n";
+    let stderr = try_compile("too_large_multiline.c", source, "-koopa", &[]).unwrap_err();
+    assert!(stderr.contains("too_large_multiline.c:3:16"), "{}", stderr);
+    assert!(stderr.contains("does not fit in 32 bits"), "{}", stderr);
+}
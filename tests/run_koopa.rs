@@ -0,0 +1,115 @@
+//! 解释器测试：-run-koopa 直接执行 Koopa IR，main 的返回值作为退出码
+
+mod common;
+
+use common::run_koopa;
+
+/// 用到运行时库、全局数组、基本块参数和递归调用的程序
+const RUNTIME: &str = "\
+decl @getint(): i32
+decl @getch(): i32
+decl @getarray(*i32): i32
+decl @putint(i32)
+decl @putch(i32)
+decl @putarray(i32, *i32)
+
+global @table = alloc [i32, 4], {10, 20, 30, 40}
+
+fun @sum(%n: i32): i32 {
+%entry:
+  %zero = eq %n, 0
+  br %zero, %base, %recurse
+%base:
+  ret 0
+%recurse:
+  %m = sub %n, 1
+  %s = call @sum(%m)
+  %r = add %s, %n
+  ret %r
+}
+
+fun @main(): i32 {
+%entry:
+  %buf = alloc [i32, 8]
+  %p = getelemptr %buf, 0
+  %n = call @getarray(%p)
+  call @putarray(%n, %p)
+  %c = call @getch()
+  call @putch(%c)
+  %k = call @getint()
+  jump %loop(0, %k)
+%loop(%i: i32, %acc: i32):
+  %more = lt %i, 4
+  br %more, %body, %done
+%body:
+  %e = getelemptr @table, %i
+  %v = load %e
+  %next_acc = add %acc, %v
+  %next_i = add %i, 1
+  jump %loop(%next_i, %next_acc)
+%done:
+  call @putint(%acc)
+  call @putch(10)
+  %s = call @sum(100)
+  call @putint(%s)
+  ret %n
+}
+";
+
+#[test]
+fn sysy_return_value_is_exit_code() {
+    let source = "\
+int main() {
+    int max = 2147483647;
+    int wrapped = max + 1;
+    return (wrapped == -2147483647 - 1) * 10 + -7 / 2 * -1 + -7 % 2;
+}
+";
+    for level in ["-O0", "-O2"] {
+        let (code, stdout, stderr) = run_koopa("wrap.c", source, "", &[level]);
+        assert_eq!(code, 12, "{}", stderr);
+        assert!(stdout.is_empty());
+    }
+}
+
+#[test]
+fn runtime_library() {
+    for level in ["-O0", "-O2"] {
+        let (code, stdout, stderr) = run_koopa("runtime.koopa", RUNTIME, "3 5 7 9x -1", &[level]);
+        assert_eq!(code, 3, "{}", stderr);
+        assert_eq!(stdout, "3: 5 7 9\nx99\n5050");
+    }
+}
+
+#[test]
+fn division_by_zero_traps() {
+    let source = "int main() { int a = 0; return 1 / a; }";
+    for level in ["-O0", "-O1"] {
+        let (code, _, stderr) = run_koopa("div_zero.c", source, "", &[level]);
+        assert_ne!(code, 0);
+        assert!(stderr.contains("runtime error: division by zero"), "{}", stderr);
+        assert!(stderr.contains("in function '@main'"), "{}", stderr);
+    }
+}
+
+#[test]
+fn out_of_bounds_traps() {
+    let source = "\
+decl @putint(i32)
+
+fun @main(): i32 {
+%entry:
+  call @putint(1)
+  %a = alloc [i32, 4]
+  %p = getelemptr %a, 4
+  store 1, %p
+  ret 0
+}
+";
+    let (code, stdout, stderr) = run_koopa("out_of_bounds.koopa", source, "", &["-O0"]);
+    assert_ne!(code, 0);
+    // 出错之前的输出不会丢失
+    assert_eq!(stdout, "1");
+    assert!(stderr.contains("out-of-bounds access to element 4 of an object with 4 elements"), "{}", stderr);
+    assert!(stderr.contains("instruction #3 in %entry (store)"), "{}", stderr);
+}
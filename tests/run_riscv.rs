@@ -0,0 +1,199 @@
+//! 模拟器测试：-run-riscv 生成汇编并在内置的 RV32IM 模拟器中执行，结果应该和解释器一致
+
+mod common;
+
+use common::{run_koopa, run_riscv};
+
+/// 测试时使用的优化级别和寄存器分配方式的组合
+const CONFIGS: [&[&str]; 5] = [
+    &["-O0"],
+    &["-O1", "--regalloc=greedy"],
+    &["-O1", "--regalloc=linear", "-fno-omit-frame-pointer"],
+    &["-O2", "--regalloc=graph"],
+    &["-O2", "--regalloc=linear"]
+];
+
+/// 用到运行时库、超过 8 个参数的调用、基本块参数和递归调用的程序
+const RUNTIME: &str = "\
+decl @getint(): i32
+decl @getch(): i32
+decl @putint(i32)
+decl @putch(i32)
+
+fun @mix(%a: i32, %b: i32, %c: i32, %d: i32, %e: i32, %f: i32, %g: i32, %h: i32, %i: i32, %j: i32): i32 {
+%entry:
+  %x = mul %a, %j
+  %y = sub %i, %b
+  %z = div %h, %c
+  %w = mod %g, %d
+  %s1 = add %x, %y
+  %s2 = add %z, %w
+  %s3 = xor %e, %f
+  %s4 = add %s1, %s2
+  %r = add %s4, %s3
+  ret %r
+}
+
+fun @fib(%n: i32): i32 {
+%entry:
+  %small = lt %n, 2
+  br %small, %base, %recurse
+%base:
+  ret %n
+%recurse:
+  %n1 = sub %n, 1
+  %n2 = sub %n, 2
+  %f1 = call @fib(%n1)
+  %f2 = call @fib(%n2)
+  %r = add %f1, %f2
+  ret %r
+}
+
+fun @main(): i32 {
+%entry:
+  %k = call @getint()
+  %c = call @getch()
+  call @putch(%c)
+  jump %loop(0, %k)
+%loop(%i: i32, %acc: i32):
+  %more = lt %i, 10
+  br %more, %body, %done
+%body:
+  %v = call @fib(%i)
+  %next_acc = add %acc, %v
+  %next_i = add %i, 1
+  jump %loop(%next_i, %next_acc)
+%done:
+  call @putint(%acc)
+  call @putch(32)
+  %m = call @mix(1, 2, 3, 4, 5, 6, 7, 8, 9, %k)
+  call @putint(%m)
+  %r = mod %m, 256
+  ret %r
+}
+";
+
+/// 把局部变量的地址传给被调用者，被调用者通过指针读写调用者的变量
+const SET_THROUGH_POINTER: &str = "\
+fun @set(%p: *i32) {
+%entry:
+  store 42, %p
+  ret
+}
+
+fun @main(): i32 {
+%entry:
+  %a = alloc i32
+  store 1, %a
+  call @set(%a)
+  %v = load %a
+  ret %v
+}
+";
+
+/// 指针存在局部变量中、在循环中通过指针修改变量，以及通过栈传递的指针参数
+const POINTERS: &str = "\
+decl @getint(): i32
+
+fun @inc(%p: *i32) {
+%entry:
+  %v = load %p
+  %w = add %v, 1
+  store %w, %p
+  ret
+}
+
+fun @ninth(%a: i32, %b: i32, %c: i32, %d: i32, %e: i32, %f: i32, %g: i32, %h: i32, %p: *i32): i32 {
+%entry:
+  %v = load %p
+  %r = add %v, %h
+  ret %r
+}
+
+fun @main(): i32 {
+%entry:
+  %a = alloc i32
+  %pp = alloc *i32
+  %sum = alloc i32
+  store 0, %a
+  store 0, %sum
+  store %a, %pp
+  %n = call @getint()
+  jump %loop(0)
+%loop(%i: i32):
+  %c = lt %i, %n
+  br %c, %body, %done
+%body:
+  %x = load %a
+  %s = load %sum
+  %s2 = add %s, %x
+  store %s2, %sum
+  %p = load %pp
+  call @inc(%p)
+  %i2 = add %i, 1
+  jump %loop(%i2)
+%done:
+  %r = load %sum
+  %g = call @ninth(1, 2, 3, 4, 5, 6, 7, 8, %a)
+  %r2 = mul %r, 100
+  %r3 = add %r2, %g
+  ret %r3
+}
+";
+
+#[test]
+fn sysy_exit_codes_match_interpreter() {
+    let source = "\
+int main() {
+    const int n = 7;
+    int max = 2147483647;
+    int a = n * 6 - 1, b = -a / 4, c = a % -5;
+    int wrapped = max + 1;
+    return (wrapped == -2147483647 - 1) * 100 + (a > b && b != c) * 10 + a % 7 + c;
+}
+";
+    let (expected, _, stderr) = run_koopa("expr.c", source, "", &["-O0"]);
+    assert!(stderr.is_empty(), "{}", stderr);
+    for options in CONFIGS {
+        let (code, stdout, stderr) = run_riscv("expr.c", source, "", options);
+        assert_eq!(code, expected, "{:?}\n{}", options, stderr);
+        assert!(stdout.is_empty());
+    }
+}
+
+#[test]
+fn runtime_library_and_calls() {
+    let (expected_code, expected_stdout, stderr) = run_koopa("calls.koopa", RUNTIME, "42!", &["-O0"]);
+    assert!(stderr.is_empty(), "{}", stderr);
+    assert_eq!(expected_stdout, "!130 57");
+    for options in CONFIGS {
+        let (code, stdout, stderr) = run_riscv("calls.koopa", RUNTIME, "42!", options);
+        assert_eq!(code, expected_code, "{:?}\n{}", options, stderr);
+        assert_eq!(stdout, expected_stdout, "{:?}", options);
+    }
+}
+
+#[test]
+fn pointers_to_local_variables() {
+    for (name, source, input, expected) in [("set_pointer.koopa", SET_THROUGH_POINTER, "", 42), ("pointers.koopa", POINTERS, "5", 245)] {
+        let (code, _, stderr) = run_koopa(name, source, input, &["-O0"]);
+        assert_eq!(code, expected, "{}", stderr);
+        // 不内联时指针真正经过函数调用
+        for options in CONFIGS {
+            for inline in ["-finline-limit=0", "-finline-limit=40"] {
+                let options = [options, &[inline]].concat();
+                let (code, _, stderr) = run_riscv(name, source, input, &options);
+                assert_eq!(code, expected, "{} {:?}\n{}", name, options, stderr);
+            }
+        }
+    }
+}
+
+#[test]
+fn invalid_input_is_a_runtime_error() {
+    let (code, _, stderr) = run_riscv("calls.koopa", RUNTIME, "x", &["-O0"]);
+    assert_ne!(code, 0);
+    assert!(stderr.contains("runtime error: getint could not read an integer from the input"), "{}", stderr);
+    // 汇编中的指令和操作数之间是制表符
+    assert!(stderr.contains("of the generated assembly: call\tgetint"), "{}", stderr);
+}
@@ -0,0 +1,124 @@
+//! 大栈帧测试：成千上万个溢出到栈上的值会让栈帧超过 2 KiB，
+//! 此时所有相对 sp 的访问都必须通过 t0 中继，不能直接使用超出 12 位范围的立即数；生成的汇编还要在模拟器中跑出正确的结果。
+
+mod common;
+
+use common::{compile_riscv, run_koopa, run_riscv, try_compile};
+
+/// 生成一个有 n 个局部变量的程序，所有变量一直活跃到函数末尾
+fn many_locals_program(n: usize) -> String {
+    let mut source = String::from("int main() {\n");
+    source.push_str("    int v0 = 1;\n");
+    for i in 1..n {
+        source.push_str(&format!("    int v{} = v{} * 3 % 7 + {};\n", i, i - 1, i % 5));
+    }
+    source.push_str("    int sum = 0;\n");
+    for i in 0..n {
+        source.push_str(&format!("    sum = sum + v{};\n", i));
+    }
+    source.push_str("    return sum % 256;\n}\n");
+    source
+}
+
+/// 生成一个有 n 个局部变量的 Koopa IR 程序，每个变量的地址都被传给被调用者
+/// 这些变量都只能放在栈上，靠后的变量的地址超出 12 位立即数的范围
+fn address_taken_program(n: usize) -> String {
+    let mut source = String::from("fun @inc(%p: *i32) {\n%entry:\n  %v = load %p\n  %w = add %v, 1\n  store %w, %p\n  ret\n}\n\n");
+    source.push_str("fun @main(): i32 {\n%entry:\n");
+    for i in 0..n {
+        source.push_str(&format!("  %v{} = alloc i32\n  store {}, %v{}\n  call @inc(%v{})\n", i, i % 7, i, i));
+    }
+    source.push_str("  %s0 = add 0, 0\n");
+    for i in 0..n {
+        source.push_str(&format!("  %l{} = load %v{}\n  %s{} = add %s{}, %l{}\n", i, i, i + 1, i, i));
+    }
+    source.push_str(&format!("  %r = mod %s{}, 256\n  ret %r\n}}\n", n));
+    source
+}
+
+/// 检查汇编中的所有立即数都在 12 位有符号范围内，并且 t0 只被用作地址中继：
+/// 每次读取 t0 之前，上一条指令一定刚刚写入了 t0
+fn check_assembly(asm: &str) {
+    let in_range = |imm: &str| imm.parse::<i32>().is_ok_and(|v| (-2048..=2047).contains(&v));
+    let mut t0_ready = false;
+    for line in asm.lines().map(str::trim) {
+        let Some((op, operands)) = line.split_once('\t') else {
+            t0_ready = false;
+            continue;
+        };
+        let operands: Vec<&str> = operands.split(',').map(str::trim).collect();
+        match op {
+            "lw" | "sw" => {
+                let (imm, base) = operands[1].trim_end_matches(')').split_once('(').unwrap();
+                assert!(in_range(imm), "访存偏移超出范围：{}", line);
+                if base == "t0" {
+                    assert!(t0_ready, "t0 在使用前没有重新计算：{}", line);
+                }
+            },
+            "addi" => assert!(in_range(operands[2]), "立即数超出范围：{}", line),
+            _ => {}
+        }
+        let reads_t0 = operands.iter().skip(1).any(|o| *o == "t0");
+        if reads_t0 && op != "lw" && op != "sw" {
+            assert!(t0_ready, "t0 在使用前没有重新计算：{}", line);
+        }
+        t0_ready = operands.first() == Some(&"t0") && op != "sw";
+    }
+}
+
+/// 从 main 的第一条指令（移动 sp）中读出栈帧大小
+fn frame_size(asm: &str) -> i32 {
+    let mut lines = asm.lines().skip_while(|l| *l != "main:").skip(1);
+    let first = lines.next().unwrap().trim();
+    let imm = first.rsplit(',').next().unwrap();
+    -imm.parse::<i32>().unwrap()
+}
+
+#[test]
+fn thousands_of_spilled_values() {
+    let source = many_locals_program(3000);
+    for regalloc in ["greedy", "linear", "graph"] {
+        let option = format!("--regalloc={}", regalloc);
+        let asm = compile_riscv(&format!("spill_{}", regalloc), &source, &["-O0", &option]);
+        // 3000 个变量至少需要 12000 字节的栈空间
+        assert!(frame_size(&asm) >= 12000, "栈帧只有 {} 字节", frame_size(&asm));
+        check_assembly(&asm);
+    }
+}
+
+#[test]
+fn large_frame_with_frame_pointer() {
+    let source = many_locals_program(1000);
+    for options in [&["-O0", "-fno-omit-frame-pointer"][..], &["-O2", "-fno-omit-frame-pointer"]] {
+        let asm = compile_riscv(&format!("frame_pointer{}", options[0]), &source, options);
+        check_assembly(&asm);
+    }
+}
+
+#[test]
+fn small_frame_uses_direct_offsets() {
+    let asm = compile_riscv("small", &many_locals_program(10), &["-O0", "--regalloc=greedy"]);
+    assert!(!asm.contains("t0"), "小栈帧不应该使用 t0 中继");
+    check_assembly(&asm);
+}
+
+#[test]
+fn large_frames_run() {
+    let sysy = many_locals_program(1000);
+    let koopa = address_taken_program(700);
+    let cases: [(&str, &str, &[&[&str]]); 2] = [
+        ("run_locals.c", &sysy, &[&["-O0", "--regalloc=greedy"], &["-O0", "--regalloc=graph", "-fno-omit-frame-pointer"]]),
+        ("run_addresses.koopa", &koopa, &[&["-O0", "--regalloc=greedy"], &["-O2", "--regalloc=linear", "-finline-limit=0"]])
+    ];
+    for (name, source, configs) in cases {
+        let (expected, _, stderr) = run_koopa(name, source, "", &["-O0"]);
+        assert!(stderr.is_empty(), "{}", stderr);
+        for &options in configs {
+            let asm = try_compile(name, source, "-riscv", options).unwrap();
+            assert!(frame_size(&asm) > 2048, "{} {:?} 的栈帧只有 {} 字节", name, options, frame_size(&asm));
+            check_assembly(&asm);
+            let (code, _, stderr) = run_riscv(name, source, "", options);
+            assert_eq!(code, expected, "{} {:?}\n{}", name, options, stderr);
+        }
+    }
+}
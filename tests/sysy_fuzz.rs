@@ -0,0 +1,18 @@
+//! 随机程序测试：-sysy-fuzz 生成的程序编译之后的结果应该和参考求值一致
+
+use std::process::Command;
+
+#[test]
+fn random_programs_match_reference() {
+    for options in [&["-O0", "--regalloc=greedy"][..], &["-O1", "--seed=1000"], &["-O2", "--seed=2000"]] {
+        let result = Command::new(env!("CARGO_BIN_EXE_compiler"))
+            .arg("-sysy-fuzz")
+            .arg("300")
+            .args(options)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        assert!(result.status.success(), "{:?}\n{}", options, stdout);
+        assert!(stdout.contains("300 programs checked; 0 failed"), "{}", stdout);
+    }
+}